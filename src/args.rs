@@ -12,11 +12,13 @@ use std::str;
 
 use clap::{self, AppSettings, Arg, ArgMatches};
 use conv::TryFrom;
+use glob::Pattern;
 use itertools::Itertools;
+use semver::{Version, VersionReq};
 use strfmt::FmtError;
 
 use display::{ISSUE_FORMATTERS, format_issue};
-use model::{Issue, Repository};
+use model::{DependencyKind, ForkPolicy, Issue, Repository};
 use super::{NAME, VERSION};
 
 
@@ -53,14 +55,52 @@ pub struct Options {
     /// If -q has been used instead, this will be negative.
     pub verbosity: isize,
 
-    /// Path to a crate manifest (Cargo.toml) to look at for [dependencies].
-    /// If omitted, we'll try to use one in the current directory.
-    pub manifest_path: Option<PathBuf>,
+    /// Paths to crate manifests (Cargo.toml) to look at for [dependencies].
+    /// If empty (and no directories are to be searched),
+    /// we'll try to use the one of the crate in the current directory.
+    pub manifest_paths: Vec<PathBuf>,
+    /// Directories to search for projects to look at.
+    pub recursive_dirs: Vec<PathBuf>,
+    /// Name of a crate from crates.io to look at instead of a local project,
+    /// along with the requirement for its version.
+    pub crate_: Option<(String, VersionReq)>,
+    /// Name of our crate whose reverse dependencies (from crates.io) should be looked at.
+    pub reverse_deps: Option<String>,
+    /// Kinds of dependencies (normal, dev, build) to look at.
+    pub kinds: Vec<DependencyKind>,
+    /// Target triple to consider the platform-specific dependencies for.
+    /// If omitted, dependencies for all platforms are considered.
+    pub target: Option<String>,
+    /// Features to enable in addition to the default ones,
+    /// which determine the optional dependencies being used.
+    pub features: Vec<String>,
+    /// Whether to only look at the optional dependencies enabled by the features.
+    pub only_enabled: bool,
+    /// Patterns of crates (or `owner/repo`) to exclusively look at.
+    pub include: Vec<String>,
+    /// Patterns of crates (or `owner/repo`) to never look at.
+    pub exclude: Vec<String>,
+    /// Which repositories to look at for crates whose repository is a fork.
+    /// If omitted, the setting from the config file is used.
+    pub forks: Option<ForkPolicy>,
+    /// Whether to look at the repositories which have been archived, too.
+    pub include_archived: bool,
+    /// How deep into the dependency graph should we look.
+    /// 1 means only direct dependencies, while None means all of them.
+    pub depth: Option<usize>,
+    /// Whether to obtain the dependencies from `cargo metadata`
+    /// instead of reading the manifests ourselves.
+    pub use_metadata: bool,
+    /// Whether to only list the dependencies whose repositories cannot be determined
+    /// instead of suggesting issues.
+    pub list_unresolved: bool,
     /// Maximum number of issues to yield.
     /// If omitted, we'll keep searching for more indefinitely.
     pub count: Option<usize>,
     /// Optional GitHub personal access token to use for authentication.
     pub github_token: Option<String>,
+    /// Optional GitLab personal access token to use for authentication with gitlab.com.
+    pub gitlab_token: Option<String>,
     /// Optional format string to use when printing issues.
     pub format: Option<String>,
 }
@@ -81,12 +121,41 @@ impl<'a> TryFrom<ArgMatches<'a>> for Options {
         let quiet_count = matches.occurrences_of(OPT_QUIET) as isize;
         let verbosity = verbose_count - quiet_count;
 
-        let manifest_path = matches.value_of(OPT_MANIFEST_PATH).map(PathBuf::from);
+        let manifest_paths = matches.values_of(OPT_MANIFEST_PATH)
+            .map(|vs| vs.map(PathBuf::from).collect()).unwrap_or_else(Vec::new);
+        let recursive_dirs = matches.values_of(OPT_RECURSIVE)
+            .map(|vs| vs.map(PathBuf::from).collect()).unwrap_or_else(Vec::new);
+        let crate_ = matches.value_of(OPT_CRATE).map(|c| parse_crate_spec(c).unwrap());
+        let reverse_deps = matches.value_of(OPT_REVERSE_DEPS).map(String::from);
+        let kinds = matches.values_of(OPT_KINDS)
+            .map(|vs| vs.map(|k| k.parse().unwrap()).unique().collect())
+            .unwrap_or_else(|| vec![DependencyKind::Normal]);
+        let target = matches.value_of(OPT_TARGET).map(String::from);
+        let features = matches.values_of(OPT_FEATURES)
+            .map(|vs| vs.flat_map(|v| v.split_whitespace()).map(String::from).unique().collect())
+            .unwrap_or_else(Vec::new);
+        let only_enabled = matches.is_present(OPT_ONLY_ENABLED);
+        let include = matches.values_of(OPT_INCLUDE)
+            .map(|vs| vs.map(String::from).collect()).unwrap_or_else(Vec::new);
+        let exclude = matches.values_of(OPT_EXCLUDE)
+            .map(|vs| vs.map(String::from).collect()).unwrap_or_else(Vec::new);
+        let forks = matches.value_of(OPT_FORKS).map(|f| f.parse().unwrap());
+        let include_archived = matches.is_present(OPT_INCLUDE_ARCHIVED);
+        let depth = if matches.is_present(OPT_ALL_DEPS) { None } else {
+            Some(matches.value_of(OPT_DEPTH).map(|d| d.parse().unwrap()).unwrap_or(1))
+        };
+        let use_metadata = matches.is_present(OPT_METADATA);
+        let list_unresolved = matches.is_present(OPT_LIST_UNRESOLVED);
         let count = matches.value_of(OPT_COUNT).map(|c| c.parse().unwrap());
         let github_token = matches.value_of(OPT_GITHUB_TOKEN).map(String::from);
+        let gitlab_token = matches.value_of(OPT_GITLAB_TOKEN).map(String::from);
         let format = matches.value_of(OPT_FORMAT).map(String::from);
 
-        Ok(Options{verbosity, manifest_path, count, github_token, format})
+        Ok(Options{
+            verbosity, manifest_paths, recursive_dirs, crate_, reverse_deps,
+            kinds, target, features, only_enabled, include, exclude, forks, include_archived,
+            depth, use_metadata, list_unresolved, count, github_token, gitlab_token, format,
+        })
     }
 }
 
@@ -128,8 +197,24 @@ lazy_static! {
 }
 
 const OPT_MANIFEST_PATH: &str = "manifest-path";
+const OPT_RECURSIVE: &str = "recursive";
+const OPT_CRATE: &str = "crate";
+const OPT_REVERSE_DEPS: &str = "reverse-deps";
+const OPT_KINDS: &str = "kinds";
+const OPT_TARGET: &str = "target";
+const OPT_FEATURES: &str = "features";
+const OPT_ONLY_ENABLED: &str = "only-enabled";
+const OPT_INCLUDE: &str = "include";
+const OPT_EXCLUDE: &str = "exclude";
+const OPT_FORKS: &str = "forks";
+const OPT_INCLUDE_ARCHIVED: &str = "include-archived";
+const OPT_DEPTH: &str = "depth";
+const OPT_ALL_DEPS: &str = "all-deps";
+const OPT_METADATA: &str = "metadata";
+const OPT_LIST_UNRESOLVED: &str = "list-unresolved";
 const OPT_COUNT: &str = "count";
 const OPT_GITHUB_TOKEN: &str = "github-token";
+const OPT_GITLAB_TOKEN: &str = "gitlab-token";
 const OPT_FORMAT: &str = "format";
 const OPT_VERBOSE: &str = "verbose";
 const OPT_QUIET: &str = "quiet";
@@ -147,7 +232,11 @@ fn create_parser<'p>() -> Parser<'p> {
         .about(*ABOUT)
         .long_about(concat!(
             "Look at this crate's [dependencies] and suggest some of their open issues\n",
-            "as potential avenues for making contributions (pull requests)."))
+            "as potential avenues for making contributions (pull requests).\n\n",
+            "Issues are looked for on GitHub, GitLab, Gitea & Forgejo (like Codeberg),\n",
+            "SourceHut, and Bitbucket.\n",
+            "On SourceHut, the issue tracker (on todo.sr.ht) is assumed to have the same name\n",
+            "as the repository, since trackers aren't linked to repositories there."))
 
         .setting(AppSettings::StrictUtf8)
 
@@ -160,9 +249,191 @@ fn create_parser<'p>() -> Parser<'p> {
             .long("manifest-path")
             .takes_value(true)
             .empty_values(false)
-            .multiple(false)
+            .multiple(true)
+            .number_of_values(1)
             .value_name("PATH")
-            .help("Path to a crate manifest to look through"))
+            .help("Path to a crate manifest to look through")
+            .long_help(concat!(
+                "Path to the Cargo.toml of a crate or workspace to look through.\n",
+                "Can be given multiple times to look at several projects at once.\n\n",
+                "If omitted, the nearest Cargo.toml in the current directory\n",
+                "or any of its parent directories is used.\n")))
+        .arg(Arg::with_name(OPT_RECURSIVE)
+            .long("recursive").short("r")
+            .takes_value(true)
+            .empty_values(false)
+            .multiple(true)
+            .number_of_values(1)
+            .value_name("DIR")
+            .help("Look through all the projects under given directory")
+            .long_help(concat!(
+                "Find all the Rust projects within given directory tree and look through them.\n",
+                "Can be given multiple times, and combined with --manifest-path.\n\n",
+                "Build directories (target/) and vendored crates (vendor/) are skipped.\n",
+                "Dependencies used by more of the projects are suggested first.\n")))
+        .arg(Arg::with_name(OPT_CRATE)
+            .long("crate")
+            .takes_value(true)
+            .empty_values(false)
+            .value_name("NAME[@VERSION]")
+            .validator(validate_crate_spec)
+            .conflicts_with_all(&[OPT_MANIFEST_PATH, OPT_RECURSIVE])
+            .help("Look at a crate from crates.io instead of a local project")
+            .long_help(concat!(
+                "Suggest issues for given crate from crates.io and its dependencies,\n",
+                "rather than for the project in the current directory.\n\n",
+                "The version can be exact (serde@1.0.100) or a requirement (serde@1.0).\n",
+                "If omitted, the newest version is used.\n\n",
+                "An exact version is read from Cargo's local cache if it's there,\n",
+                "in which case --depth applies as usual (if the crate comes with a Cargo.lock).\n",
+                "Otherwise, only the direct dependencies of the crate are available.\n")))
+        .arg(Arg::with_name(OPT_REVERSE_DEPS)
+            .long("reverse-deps")
+            .takes_value(true)
+            .empty_values(false)
+            .value_name("CRATE")
+            .validator(validate_crate_name)
+            .conflicts_with_all(&[OPT_MANIFEST_PATH, OPT_RECURSIVE, OPT_CRATE])
+            .help("Look at crates which depend on given crate")
+            .long_help(concat!(
+                "Suggest issues for crates from crates.io that depend on given crate,\n",
+                "starting with the most downloaded ones.\n\n",
+                "Issues which mention the crate by name are pointed out\n",
+                "as especially relevant.\n")))
+
+        .arg(Arg::with_name(OPT_KINDS)
+            .long("kinds")
+            .takes_value(true)
+            .empty_values(false)
+            .multiple(true)
+            .require_delimiter(true)
+            .possible_values(&["normal", "dev", "build"])
+            .value_name("KIND")
+            .help("Kinds of dependencies to look at")
+            .long_help(concat!(
+                "Comma-separated list of dependency kinds to look at:\n",
+                "`normal` for [dependencies], `dev` for [dev-dependencies], ",
+                "and `build` for [build-dependencies].\n\n",
+                "By default, only the normal dependencies are considered.\n")))
+
+        .arg(Arg::with_name(OPT_TARGET)
+            .long("target")
+            .takes_value(true)
+            .empty_values(false)
+            .multiple(false)
+            .value_name("TRIPLE")
+            .help("Only consider platform-specific dependencies for given target")
+            .long_help(concat!(
+                "Target triple (e.g. x86_64-unknown-linux-gnu) to consider\n",
+                "the platform-specific [target.*] dependencies for.\n\n",
+                "If omitted, dependencies for all targets are looked at.\n")))
+
+        .arg(Arg::with_name(OPT_FEATURES)
+            .long("features")
+            .takes_value(true)
+            .empty_values(false)
+            .multiple(true)
+            .use_delimiter(true)
+            .value_name("FEATURES")
+            .help("Features to enable in addition to the default ones")
+            .long_help(concat!(
+                "Comma- or space-separated list of features to consider enabled,\n",
+                "in addition to the default features of the crate(s).\n\n",
+                "Like with Cargo, features of a particular workspace member\n",
+                "can be given as `member/feature`.\n")))
+        .arg(Arg::with_name(OPT_ONLY_ENABLED)
+            .long("only-enabled")
+            .help("Skip optional dependencies that aren't enabled by any feature"))
+
+        .arg(Arg::with_name(OPT_INCLUDE)
+            .long("include")
+            .takes_value(true)
+            .empty_values(false)
+            .validator(validate_pattern)
+            .multiple(true)
+            .number_of_values(1)
+            .value_name("PATTERN")
+            .help("Only look at crates or repositories matching given pattern")
+            .long_help(concat!(
+                "Glob pattern for the names of crates to exclusively look at,\n",
+                "or for their repositories (as `owner/repo`) if it contains a slash.\n",
+                "Can be given multiple times.\n\n",
+                "Patterns from the `include` list in the config file are used as well.\n")))
+        .arg(Arg::with_name(OPT_EXCLUDE)
+            .long("exclude")
+            .takes_value(true)
+            .empty_values(false)
+            .validator(validate_pattern)
+            .multiple(true)
+            .number_of_values(1)
+            .value_name("PATTERN")
+            .help("Skip crates or repositories matching given pattern")
+            .long_help(concat!(
+                "Glob pattern for the names of crates to never look at,\n",
+                "or for their repositories (as `owner/repo`) if it contains a slash.\n",
+                "Can be given multiple times.\n\n",
+                "Patterns from the `exclude` list in the config file are used as well.\n")))
+        .arg(Arg::with_name(OPT_FORKS)
+            .long("forks")
+            .takes_value(true)
+            .empty_values(false)
+            .possible_values(&["fork", "upstream", "both"])
+            .value_name("WHICH")
+            .help("Which repositories to look at for crates hosted in a fork")
+            .long_help(concat!(
+                "Which repositories to look at when the repository of a crate ",
+                "is a fork of another one:\n",
+                "`fork` for just the fork, `upstream` for the repository it was forked from, ",
+                "or `both`.\n\n",
+                "The default is `both`, unless the `forks` setting in the config file ",
+                "says otherwise.\n",
+                "Only GitHub repositories are checked for being forks.\n")))
+        .arg(Arg::with_name(OPT_INCLUDE_ARCHIVED)
+            .long("include-archived")
+            .help("Look at the archived repositories, too")
+            .long_help(concat!(
+                "Look for issues in the repositories which have been archived,\n",
+                "instead of skipping them (which is also what `include_archived = true`\n",
+                "in the config file does).\n",
+                "Only GitHub repositories are checked for being archived.\n")))
+
+        .arg(Arg::with_name(OPT_DEPTH)
+            .long("depth")
+            .takes_value(true)
+            .empty_values(false)
+            .validator(validate_depth)
+            .multiple(false)
+            .value_name("N")
+            .help("How deep into the dependency graph to look")
+            .long_help(concat!(
+                "How many levels of dependencies to look at.\n\n",
+                "The default of 1 means only the direct dependencies of the crate.\n",
+                "Greater values require a Cargo.lock file to read the indirect ones from.\n",
+                "Issues from crates closer to the project are suggested first.\n")))
+        .arg(Arg::with_name(OPT_ALL_DEPS)
+            .long("all-deps")
+            .conflicts_with(OPT_DEPTH)
+            .help("Look at all dependencies, including indirect ones"))
+        .arg(Arg::with_name(OPT_METADATA)
+            .long("metadata")
+            .help("Use `cargo metadata` to find the dependencies")
+            .long_help(concat!(
+                "Ask Cargo for the resolved dependency graph via `cargo metadata`\n",
+                "instead of reading Cargo.toml and Cargo.lock files directly.\n\n",
+                "This handles every manifest feature that Cargo does, ",
+                "but may need to update Cargo.lock or fetch the crate index.\n",
+                "If Cargo cannot be found, the manifests are read as usual.\n")))
+        .arg(Arg::with_name(OPT_LIST_UNRESOLVED)
+            .long("list-unresolved")
+            .conflicts_with_all(&[OPT_CRATE, OPT_REVERSE_DEPS])
+            .help("List the dependencies whose repositories cannot be determined")
+            .long_help(concat!(
+                "Instead of suggesting issues, print the dependencies ",
+                "whose repositories cannot be determined.\n\n",
+                "Their repositories can be given in the `[repositories]` table ",
+                "of the config file\n",
+                "or in `[package.metadata.contribute.repositories]` of Cargo.toml, ",
+                "as `forge:owner/repo`.\n")))
 
         .arg(Arg::with_name(OPT_COUNT)
             .long("count").short("n")
@@ -190,6 +461,17 @@ fn create_parser<'p>() -> Parser<'p> {
                 "https://github.com/settings/tokens.\n",
                 "This helps avoiding rate limit problems when searching for ",
                 "issues to contribute to.\n")))
+        .arg(Arg::with_name(OPT_GITLAB_TOKEN)
+            .long("gitlab-token")
+            .takes_value(true)
+            .empty_values(false)
+            .multiple(false)
+            .value_name("TOKEN")
+            .help("GitLab's personal access token to use")
+            .long_help(concat!(
+                "Access token to use when querying the API of gitlab.com.\n\n",
+                "You can provide a personal access token (with the read_api scope)\n",
+                "generated using https://gitlab.com/-/profile/personal_access_tokens.\n")))
 
         .arg(Arg::with_name(OPT_FORMAT)
             .long("format")
@@ -231,6 +513,64 @@ fn validate_count(count: String) -> Result<(), String> {
     count.parse::<usize>().map(|_| ()).map_err(|e| format!("{}", e))
 }
 
+/// Validator for the --crate flag value.
+#[cfg_attr(feature = "cargo-clippy", allow(needless_pass_by_value))]
+fn validate_crate_spec(spec: String) -> Result<(), String> {
+    parse_crate_spec(&spec).map(|_| ())
+}
+
+/// Validator for the --reverse-deps flag value.
+#[cfg_attr(feature = "cargo-clippy", allow(needless_pass_by_value))]
+fn validate_crate_name(name: String) -> Result<(), String> {
+    check_crate_name(&name)
+}
+
+/// Parse the crate specification given to --crate, i.e. `$NAME[@$VERSION]`.
+///
+/// Like with `cargo install`, a version without any operator must match exactly.
+fn parse_crate_spec(spec: &str) -> Result<(String, VersionReq), String> {
+    let mut parts = spec.splitn(2, '@');
+    let name = parts.next().unwrap_or(spec);
+    check_crate_name(name)?;
+    let version = match parts.next() {
+        Some(v) if Version::parse(v).is_ok() => VersionReq::exact(&Version::parse(v).unwrap()),
+        Some(v) => VersionReq::parse(v).map_err(|e| format!("invalid version: {}", e))?,
+        None => VersionReq::any(),
+    };
+    Ok((name.to_owned(), version))
+}
+
+/// Check whether given string is a valid crate name, as accepted by crates.io:
+/// ASCII letters, digits, `_` and `-`, starting with a letter.
+fn check_crate_name(name: &str) -> Result<(), String> {
+    match name.chars().next() {
+        None => return Err("crate name cannot be empty".into()),
+        Some(c) if !c.is_ascii_alphabetic() =>
+            return Err(format!("crate name must start with a letter, got `{}`", name)),
+        _ => {}
+    }
+    if let Some(c) = name.chars().find(|&c| !(c.is_ascii_alphanumeric() || c == '_' || c == '-')) {
+        return Err(format!("invalid character `{}` in crate name `{}`", c, name));
+    }
+    Ok(())
+}
+
+/// Validator for the --include and --exclude flag values.
+#[cfg_attr(feature = "cargo-clippy", allow(needless_pass_by_value))]
+fn validate_pattern(pattern: String) -> Result<(), String> {
+    Pattern::new(&pattern).map(|_| ()).map_err(|e| format!("{}", e))
+}
+
+/// Validator for the --depth flag value.
+#[cfg_attr(feature = "cargo-clippy", allow(needless_pass_by_value))]
+fn validate_depth(depth: String) -> Result<(), String> {
+    match depth.parse::<usize>() {
+        Ok(0) => Err("depth must be at least 1".into()),
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("{}", e)),
+    }
+}
+
 /// Validator for the --format flag value.
 #[cfg_attr(feature = "cargo-clippy", allow(needless_pass_by_value))]
 fn validate_format(format: String) -> Result<(), String> {
@@ -242,6 +582,10 @@ fn validate_format(format: String) -> Result<(), String> {
             title: "Optimize reticulating spines".into(),
             body: "...".into(),
             comment_count: 0,
+            labels: vec![],
+            dependency: None,
+            dependent: None,
+            mentioned_crate: None,
         };
     }
     format_issue(&format, &*EXAMPLE_ISSUE).map(|_| ()).map_err(|e| match e {
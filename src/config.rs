@@ -0,0 +1,155 @@
+//! Module for the persisted configuration of the program.
+//!
+//! The configuration file is read from $CONFIG_DIR/cargo-contribute/config.toml
+//! (e.g. ~/.config/cargo-contribute/config.toml on Linux),
+//! unless another path is given through $CARGO_CONTRIBUTE_CONFIG.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use dirs;
+use toml;
+
+
+/// Environment variable with a custom path to the configuration file.
+const CONFIG_PATH_ENVVAR: &str = "CARGO_CONTRIBUTE_CONFIG";
+
+const CONFIG_DIR: &str = "cargo-contribute";
+const CONFIG_FILE: &str = "config.toml";
+
+
+/// Configuration of the program, as read from the config file.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct Config {
+    /// Patterns of crate names (or `owner/repo` names) to exclusively look at.
+    pub include: Vec<String>,
+    /// Patterns of crate names (or `owner/repo` names) to never look at.
+    pub exclude: Vec<String>,
+    /// Which repositories to look at for crates whose repository is a fork:
+    /// "fork", "upstream", or "both".
+    pub forks: Option<String>,
+    /// Whether to look at the repositories which have been archived, too.
+    pub include_archived: bool,
+    /// Self-hosted instances of the forges, like GitLab or Forgejo.
+    pub hosts: Vec<HostConfig>,
+    /// Repositories of particular crates as `forge:owner/repo`,
+    /// or "none" if the crate should be ignored.
+    pub repositories: BTreeMap<String, String>,
+}
+
+/// Configuration of a self-hosted forge instance.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+pub struct HostConfig {
+    /// Hostname of the instance, like "git.example.com".
+    pub host: String,
+    /// Forge software that the instance runs: "gitlab", or "gitea" (which includes Forgejo).
+    pub forge: String,
+    /// Root URL of the instance's API, if it's different than the usual one.
+    #[serde(default)]
+    pub api_root: Option<String>,
+    /// Access token to use with the instance.
+    #[serde(default)]
+    pub token: Option<String>,
+}
+
+impl Config {
+    /// Path to the configuration file, if it can be determined.
+    pub fn path() -> Option<PathBuf> {
+        match env::var_os(CONFIG_PATH_ENVVAR) {
+            Some(ref path) if !path.is_empty() => Some(PathBuf::from(path)),
+            _ => dirs::config_dir().map(|dir| dir.join(CONFIG_DIR).join(CONFIG_FILE)),
+        }
+    }
+
+    /// Load the configuration from the config file.
+    /// If there is no such file, the default configuration is returned.
+    pub fn load() -> Result<Self, Error> {
+        match Config::path() {
+            Some(ref path) if path.is_file() => Config::load_from(path),
+            _ => {
+                trace!("No config file found, using default configuration");
+                Ok(Config::default())
+            }
+        }
+    }
+
+    /// Load the configuration from given file.
+    pub fn load_from<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        debug!("Reading configuration from {}", path.display());
+        let content = fs::read_to_string(path)?;
+        toml::from_str(&content).map_err(Error::Toml)
+    }
+}
+
+
+/// Error while reading the configuration file.
+#[derive(Debug, Error)]
+pub enum Error {
+    Io(io::Error),
+    Toml(toml::de::Error),
+}
+
+
+#[cfg(test)]
+mod tests {
+    use toml;
+    use super::Config;
+
+    #[test]
+    fn empty_config() {
+        let config: Config = toml::from_str("").unwrap();
+        assert_eq!(Config::default(), config);
+    }
+
+    #[test]
+    fn filters() {
+        let config: Config = toml::from_str(concat!(
+            "include = [\"serde*\"]\n",
+            "exclude = [\"ourcompany/*\", \"internal-*\"]\n",
+        )).unwrap();
+        assert_eq!(vec!["serde*"], config.include);
+        assert_eq!(vec!["ourcompany/*", "internal-*"], config.exclude);
+    }
+
+    #[test]
+    fn forks_and_archived() {
+        let config: Config = toml::from_str(concat!(
+            "forks = \"upstream\"\n",
+            "include_archived = true\n",
+        )).unwrap();
+        assert_eq!(Some("upstream".to_owned()), config.forks);
+        assert!(config.include_archived);
+    }
+
+    #[test]
+    fn hosts() {
+        let config: Config = toml::from_str(concat!(
+            "[[hosts]]\n",
+            "host = \"git.example.com\"\n",
+            "forge = \"forgejo\"\n",
+            "token = \"secret\"\n",
+        )).unwrap();
+        assert_eq!(1, config.hosts.len());
+        assert_eq!("git.example.com", config.hosts[0].host);
+        assert_eq!("forgejo", config.hosts[0].forge);
+        assert_eq!(None, config.hosts[0].api_root);
+        assert_eq!(Some("secret".to_owned()), config.hosts[0].token);
+    }
+
+    #[test]
+    fn repositories() {
+        let config: Config = toml::from_str(concat!(
+            "[repositories]\n",
+            "foo = \"gitlab:someone/foo\"\n",
+            "internal-thing = \"none\"\n",
+        )).unwrap();
+        assert_eq!(2, config.repositories.len());
+        assert_eq!("gitlab:someone/foo", config.repositories["foo"]);
+        assert_eq!("none", config.repositories["internal-thing"]);
+    }
+}
@@ -25,11 +25,11 @@ lazy_static! {
         ),
         "number" => Fmt::new(
             |issue| format!("{}", issue.number).into(),
-            "GitHub issue number (ID)",
+            "Issue number (ID) within its repository",
         ),
         "url" => Fmt::new(
             |issue| issue.url.as_str().into(),
-            "URL to issue's HTML page on GitHub",
+            "URL to issue's HTML page",
         ),
         "title" => Fmt::new(
             |issue| issue.title.as_str().into(),
@@ -43,6 +43,35 @@ lazy_static! {
             |issue| format!("{}", issue.comment_count).into(),
             "Number of comments the issue has",
         ),
+        "crate" => Fmt::new(
+            |issue| issue.dependency.as_ref().map(|d| d.name()).unwrap_or("").into(),
+            "Name of the dependency that led us to the issue (our crate with --reverse-deps)",
+        ),
+        "alias" => Fmt::new(
+            |issue| issue.dependency.as_ref().map(|d| d.alias()).unwrap_or("").into(),
+            "Name that the dependency is referred to in Cargo.toml (if it's been renamed)",
+        ),
+        "kind" => Fmt::new(
+            |issue| issue.dependency.as_ref().map(|d| d.kind().as_str()).unwrap_or("").into(),
+            "Kind of the dependency: normal, dev, or build",
+        ),
+        "feature" => Fmt::new(
+            |issue| issue.dependency.as_ref().and_then(|d| d.feature()).unwrap_or("").into(),
+            "Feature that enables the dependency (if it's optional)",
+        ),
+        "ref" => Fmt::new(
+            |issue| issue.dependency.as_ref().and_then(|d| d.git_reference())
+                .map(|r| r.value()).unwrap_or("").into(),
+            "Git branch, tag, or revision that the dependency is pinned to (if any)",
+        ),
+        "dependent" => Fmt::new(
+            |issue| issue.dependent.as_ref().map(|c| c.as_str()).unwrap_or("").into(),
+            "Crate depending on ours that the issue's repository belongs to (with --reverse-deps)",
+        ),
+        "mentions" => Fmt::new(
+            |issue| issue.mentioned_crate.as_ref().map(|c| c.as_str()).unwrap_or("").into(),
+            "Crate that the issue mentions (with --reverse-deps)",
+        ),
     };
 }
 
@@ -0,0 +1,217 @@
+//! Module for making calls to the Bitbucket Cloud API.
+
+use futures::{Future, Stream};
+use hyper;
+use hyper::client::Connect;
+use url::form_urlencoded;
+
+use model::{Issue, Repository};
+use super::forge_api::{fetch_json, ForgeClient, HostSettings, paginate};
+
+pub use super::forge_api::Error;
+
+
+/// Root URL of the Bitbucket Cloud API.
+const API_ROOT: &str = "https://api.bitbucket.org/2.0";
+
+/// Number of issues to request per page (which is also the maximum).
+const ISSUES_PER_PAGE: usize = 50;
+
+/// Query for the issues that are open and unassigned.
+/// Bitbucket has two open states: "new" (not yet triaged) and "open".
+const PENDING_ISSUES_QUERY: &str = r#"(state="new" OR state="open") AND assignee=null"#;
+
+/// Priority of issues that Bitbucket users tend to give to the easy ones.
+const TRIVIAL_PRIORITY: &str = "trivial";
+
+
+/// Page of issues as returned by the Bitbucket API.
+#[derive(Debug, Deserialize)]
+struct IssuesPage {
+    /// URL of the next page, if there is one.
+    #[serde(default)]
+    next: Option<String>,
+    values: Vec<IssueItem>,
+}
+
+/// Issue as returned by the Bitbucket API.
+#[derive(Debug, Deserialize)]
+pub struct IssueItem {
+    pub id: u64,
+    pub title: String,
+    #[serde(default)]
+    pub content: Option<Content>,
+    /// Priority of the issue, from "trivial" to "blocker".
+    #[serde(default)]
+    pub priority: Option<String>,
+    /// Component of the project that the issue is about.
+    /// Bitbucket has no labels, so this is the closest thing.
+    #[serde(default)]
+    pub component: Option<Component>,
+    pub links: Links,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Content {
+    #[serde(default)]
+    pub raw: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Component {
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Links {
+    pub html: Link,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Link {
+    pub href: String,
+}
+
+impl IssueItem {
+    /// Labels of the issue, as far as Bitbucket has them.
+    ///
+    /// Besides the component, trivial issues are labeled as "easy".
+    pub fn labels(&self) -> Vec<&str> {
+        let mut result = vec![];
+        if let Some(ref component) = self.component {
+            result.push(component.name.as_str());
+        }
+        if self.priority.as_ref().map(|p| p == TRIVIAL_PRIORITY).unwrap_or(false) {
+            result.push("easy");
+        }
+        result
+    }
+
+    /// Convert to the `Issue` from given repository.
+    pub fn into_issue(self, repo: Repository) -> Issue {
+        let labels = self.labels().into_iter().map(String::from).collect();
+        Issue{
+            repo,
+            number: self.id,
+            url: self.links.html.href,
+            title: self.title,
+            body: self.content.and_then(|c| c.raw).unwrap_or_else(String::new),
+            // Comments aren't included in the issue listing.
+            comment_count: 0,
+            labels,
+            dependency: None,
+            dependent: None,
+            mentioned_crate: None,
+        }
+    }
+}
+
+
+/// Client for the Bitbucket API.
+#[derive(Clone, Debug)]
+pub struct Client<C: Clone> {
+    http: hyper::Client<C>,
+    /// Settings of particular Bitbucket hosts, whose API is the one of bitbucket.org by default.
+    hosts: HostSettings,
+}
+
+impl<C: Clone> Client<C> {
+    #[inline]
+    pub fn with_http(http: hyper::Client<C>) -> Self {
+        Client{http, hosts: HostSettings::default()}
+    }
+
+    fn api_root(&self, host: &str) -> String {
+        self.hosts.api_root(host).unwrap_or(API_ROOT).to_owned()
+    }
+}
+
+impl<C: Clone> ForgeClient for Client<C> {
+    #[inline]
+    fn hosts_mut(&mut self) -> &mut HostSettings {
+        &mut self.hosts
+    }
+}
+
+impl<C: Clone + Connect> Client<C> {
+    /// Return a stream of all open & unassigned issues in given Bitbucket repository,
+    /// most recently updated first.
+    pub fn pending_issues(
+        &self, repo: Repository
+    ) -> Box<dyn Stream<Item=IssueItem, Error=Error>> {
+        debug!("Querying for issues in {:?}", repo);
+        let query: String = form_urlencoded::byte_serialize(PENDING_ISSUES_QUERY.as_bytes())
+            .collect();
+        let first_url = format!("{}/repositories/{}/{}/issues?q={}&sort=-updated_on&pagelen={}",
+            self.api_root(&repo.host), repo.owner, repo.name, query, ISSUES_PER_PAGE);
+
+        let client = self.clone();
+        paginate(first_url, move |url| {
+            Box::new(client.issues_page(&repo, &url).map(|page| (page.values, page.next)))
+        })
+    }
+
+    /// Fetch the page of issues from given URL.
+    /// Subsequent pages are linked to by the API itself.
+    fn issues_page(
+        &self, repo: &Repository, url: &str
+    ) -> Box<dyn Future<Item=IssuesPage, Error=Error>> {
+        trace!("Fetching Bitbucket issues from {}", url);
+        let auth = self.hosts.token(&repo.host)
+            .map(|token| ("Authorization", format!("Bearer {}", token)));
+        Box::new(fetch_json(&self.http, url, auth, repo).map(|page| {
+            page.unwrap_or_else(|| IssuesPage{next: None, values: vec![]})
+        }))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use futures::Stream;
+    use hyper;
+    use tokio_core::reactor::Core;
+    use model::{Forge, Repository};
+    use testing::MockServer;
+    use super::{Client, ForgeClient};
+
+    const ISSUES_PATH: &str = concat!(
+        "/2.0/repositories/owner/repo/issues",
+        "?q=%28state%3D%22new%22+OR+state%3D%22open%22%29+AND+assignee%3Dnull",
+        "&sort=-updated_on&pagelen=50");
+
+    #[test]
+    fn pending_issues() {
+        let server = MockServer::start(vec![
+            (ISSUES_PATH, r#"{
+                "next": "{server_url}/2.0/repositories/owner/repo/issues?page=2",
+                "values": [{
+                    "id": 9, "title": "Tidy up the examples", "content": {"raw": "Please"},
+                    "priority": "trivial", "component": null,
+                    "links": {"html": {"href": "https://bitbucket.org/owner/repo/issues/9"}}
+                }]
+            }"#),
+            ("/2.0/repositories/owner/repo/issues?page=2", r#"{
+                "values": [{
+                    "id": 5, "title": "Support async", "content": {"raw": null},
+                    "priority": "major", "component": {"name": "help wanted"},
+                    "links": {"html": {"href": "https://bitbucket.org/owner/repo/issues/5"}}
+                }]
+            }"#),
+        ]);
+        let mut core = Core::new().unwrap();
+        let mut client = Client::with_http(hyper::Client::new(&core.handle()));
+        client.configure_host("bitbucket.org", Some(&format!("{}/2.0", server.url())), None);
+
+        let repo = Repository::with_host(Forge::Bitbucket, "bitbucket.org", "owner", "repo");
+        let issues = core.run(client.pending_issues(repo.clone()).collect()).unwrap();
+        assert_eq!(2, issues.len());
+        assert_eq!(vec!["easy"], issues[0].labels());
+        assert_eq!(vec!["help wanted"], issues[1].labels());
+
+        let issue = issues.into_iter().nth(1).unwrap().into_issue(repo);
+        assert_eq!(5, issue.number);
+        assert_eq!("", issue.body);
+        assert_eq!("https://bitbucket.org/owner/repo/issues/5", issue.url);
+    }
+}
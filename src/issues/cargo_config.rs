@@ -0,0 +1,267 @@
+//! Module for reading Cargo configuration files (.cargo/config.toml).
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use dirs;
+use toml;
+use url::Url;
+
+
+/// Index of the crates.io registry, as used by Git-based Cargo.
+pub const CRATES_IO_INDEX: &str = "https://github.com/rust-lang/crates.io-index";
+/// Sparse index of the crates.io registry.
+pub const CRATES_IO_SPARSE_INDEX: &str = "sparse+https://index.crates.io/";
+
+/// Names of the configuration files within a .cargo directory, in order of precedence.
+const CONFIG_FILE_NAMES: &[&str] = &["config.toml", "config"];
+
+
+/// Return the directory where Cargo keeps its global state.
+///
+/// This is $CARGO_HOME if set, or ~/.cargo otherwise.
+pub fn cargo_home() -> Option<PathBuf> {
+    env::var_os("CARGO_HOME")
+        .filter(|h| !h.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| dirs::home_dir().map(|home| home.join(".cargo")))
+}
+
+
+/// Relevant parts of the Cargo configuration.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct CargoConfig {
+    /// Alternative registries, by their names.
+    #[serde(default)]
+    pub registries: BTreeMap<String, RegistryConfig>,
+    /// Sources of crates and their replacements, by their names.
+    #[serde(default, rename = "source")]
+    pub sources: BTreeMap<String, SourceConfig>,
+}
+
+/// Configuration of a single alternative registry, i.e. a [registries.$NAME] section.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct RegistryConfig {
+    /// URL of the registry index.
+    #[serde(default)]
+    pub index: Option<String>,
+}
+
+/// Configuration of a single source of crates, i.e. a [source.$NAME] section.
+///
+/// Sources are typically used to replace crates.io (whose source is named "crates-io")
+/// with a mirror, or with a local directory of vendored crates.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct SourceConfig {
+    /// Name of the source that replaces this one.
+    #[serde(default)]
+    pub replace_with: Option<String>,
+    /// Directory with vendored crates, as created by `cargo vendor`.
+    #[serde(default)]
+    pub directory: Option<PathBuf>,
+    /// Directory with a local registry, i.e. an index and the .crate files.
+    #[serde(default)]
+    pub local_registry: Option<PathBuf>,
+}
+
+impl CargoConfig {
+    /// Load the Cargo configuration that applies to given directory.
+    ///
+    /// Like Cargo itself, this merges the config files from the directory and all its parents,
+    /// followed by the one in $CARGO_HOME. Files that are closer to the directory take precedence.
+    pub fn load<P: AsRef<Path>>(dir: P) -> Result<Self, Error> {
+        let dir = dir.as_ref();
+        let mut config_dirs: Vec<_> = match dir.canonicalize() {
+            Ok(dir) => dir.ancestors().map(|d| d.join(".cargo")).collect(),
+            Err(e) => {
+                warn!("Cannot resolve directory {}: {}", dir.display(), e);
+                vec![]
+            }
+        };
+        if let Some(home) = cargo_home() {
+            if !config_dirs.contains(&home) {
+                config_dirs.push(home);
+            }
+        }
+
+        let mut result = CargoConfig::default();
+        for config_dir in config_dirs.iter().rev() {
+            let path = CONFIG_FILE_NAMES.iter()
+                .map(|n| config_dir.join(n)).find(|p| p.is_file());
+            let path = match path {
+                Some(p) => p,
+                None => continue,
+            };
+            trace!("Reading Cargo config file: {}", path.display());
+            let content = fs::read_to_string(&path)?;
+            let mut config: CargoConfig = toml::from_str(&content)?;
+            // Paths in the config are relative to the directory containing .cargo/.
+            if let Some(base) = config_dir.parent() {
+                config.resolve_paths(base);
+            }
+            result.merge(config);
+        }
+        Ok(result)
+    }
+
+    /// Merge another config into this one, with the other one taking precedence.
+    fn merge(&mut self, other: CargoConfig) {
+        for (name, registry) in other.registries {
+            let entry = self.registries.entry(name).or_insert_with(RegistryConfig::default);
+            if registry.index.is_some() {
+                entry.index = registry.index;
+            }
+        }
+        for (name, source) in other.sources {
+            let entry = self.sources.entry(name).or_insert_with(SourceConfig::default);
+            if source.replace_with.is_some() {
+                entry.replace_with = source.replace_with;
+            }
+            if source.directory.is_some() {
+                entry.directory = source.directory;
+            }
+            if source.local_registry.is_some() {
+                entry.local_registry = source.local_registry;
+            }
+        }
+    }
+
+    /// Make the relative paths in the config absolute, using given base directory.
+    fn resolve_paths(&mut self, base: &Path) {
+        for source in self.sources.values_mut() {
+            source.directory = source.directory.take().map(|d| base.join(d));
+            source.local_registry = source.local_registry.take().map(|r| base.join(r));
+        }
+    }
+}
+
+impl CargoConfig {
+    /// Return the index URL of the alternative registry with given name.
+    ///
+    /// The index can be also overridden with $CARGO_REGISTRIES_<NAME>_INDEX.
+    pub fn registry_index(&self, name: &str) -> Option<String> {
+        let env_var = format!("CARGO_REGISTRIES_{}_INDEX", name.to_uppercase().replace('-', "_"));
+        env::var(&env_var).ok()
+            .or_else(|| self.registries.get(name)?.index.clone())
+    }
+
+    /// Find the name of the alternative registry with given index URL.
+    /// Returns None for crates.io or an unknown registry.
+    pub fn registry_for_index(&self, index: &str) -> Option<&str> {
+        let index = normalize_index_url(index);
+        self.registries.iter()
+            .find(|&(_, r)| r.index.as_ref().map(|i| normalize_index_url(i)) == Some(index))
+            .map(|(name, _)| name.as_str())
+    }
+}
+
+impl CargoConfig {
+    /// Return the source that will actually be used in place of the one with given name,
+    /// following the chain of `replace-with` entries.
+    ///
+    /// crates.io is referred to as "crates-io", while alternative registries use their names.
+    pub fn replaced_source(&self, name: &str) -> Option<&SourceConfig> {
+        let mut current = name;
+        // Guard against cycles, which Cargo would reject anyway.
+        for _ in 0..self.sources.len() {
+            let source = self.sources.get(current)?;
+            match source.replace_with {
+                Some(ref r) => current = r,
+                None => return if current == name { None } else { Some(source) },
+            }
+        }
+        warn!("Cycle in the replacements of source `{}`", name);
+        None
+    }
+}
+
+/// Check whether given index URL belongs to crates.io.
+pub fn is_crates_io_index(index: &str) -> bool {
+    let index = normalize_index_url(index);
+    index == normalize_index_url(CRATES_IO_INDEX)
+        || index == normalize_index_url(CRATES_IO_SPARSE_INDEX)
+}
+
+/// Return the hostname of given registry index URL.
+///
+/// Cargo uses it as a prefix of the directories in the registry cache
+/// that hold the crates downloaded from this index.
+pub fn index_host(index: &str) -> Option<String> {
+    let index = normalize_index_url(index);
+    let index = if index.starts_with("sparse+") { &index["sparse+".len()..] } else { index };
+    let url = Url::parse(index).map_err(|e| {
+        warn!("Invalid registry index URL {}: {}", index, e);
+    }).ok()?;
+    url.host_str().map(|h| h.to_owned())
+}
+
+/// Bring a registry index URL to a form that can be compared with other index URLs.
+fn normalize_index_url(index: &str) -> &str {
+    // Sources in Cargo.lock are prefixed by their type, e.g. "registry+https://...".
+    let index = if index.starts_with("registry+") { &index["registry+".len()..] } else { index };
+    index.trim_end_matches('/').trim_end_matches(".git")
+}
+
+
+/// Error while reading Cargo configuration.
+#[derive(Debug, Error)]
+pub enum Error {
+    Io(io::Error),
+    Toml(toml::de::Error),
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use toml;
+    use super::{CargoConfig, index_host, is_crates_io_index};
+
+    #[test]
+    fn registry_for_index() {
+        let config: CargoConfig = toml::from_str(concat!(
+            "[registries.company]\n",
+            "index = \"sparse+https://crates.example.com/index/\"\n",
+        )).unwrap();
+        assert_eq!(Some("sparse+https://crates.example.com/index/".into()),
+            config.registry_index("company"));
+        assert_eq!(Some("company"),
+            config.registry_for_index("sparse+https://crates.example.com/index"));
+        assert_eq!(None, config.registry_for_index("https://example.com/other-index"));
+    }
+
+    #[test]
+    fn replaced_source() {
+        let config: CargoConfig = toml::from_str(concat!(
+            "[source.crates-io]\n",
+            "replace-with = \"mirror\"\n",
+            "[source.mirror]\n",
+            "replace-with = \"vendored-sources\"\n",
+            "[source.vendored-sources]\n",
+            "directory = \"vendor\"\n",
+        )).unwrap();
+        let source = config.replaced_source("crates-io").unwrap();
+        assert_eq!(Some(Path::new("vendor")), source.directory.as_ref().map(|d| d.as_path()));
+        assert!(config.replaced_source("vendored-sources").is_none());
+        assert!(config.replaced_source("company").is_none());
+    }
+
+    #[test]
+    fn crates_io_index() {
+        assert!(is_crates_io_index("registry+https://github.com/rust-lang/crates.io-index"));
+        assert!(is_crates_io_index("sparse+https://index.crates.io/"));
+        assert!(!is_crates_io_index("sparse+https://crates.example.com/index/"));
+    }
+
+    #[test]
+    fn index_hosts() {
+        assert_eq!(Some("github.com".into()),
+            index_host("registry+https://github.com/rust-lang/crates.io-index"));
+        assert_eq!(Some("index.crates.io".into()), index_host("sparse+https://index.crates.io/"));
+        assert_eq!(None, index_host("not a URL"));
+    }
+}
@@ -0,0 +1,410 @@
+//! Module for reading the resolved dependency graph from Cargo.lock.
+
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use semver::{Version, VersionReq};
+use toml;
+
+use model::{Dependency, GitReference};
+use super::cargo_config::{CargoConfig, is_crates_io_index};
+use super::cargo_toml;
+
+
+/// Name of the file where Cargo stores the resolved dependency graph.
+const LOCKFILE_NAME: &str = "Cargo.lock";
+
+/// Prefix of the package `source` for crates coming from a registry.
+const REGISTRY_SOURCE_PREFIX: &str = "registry+";
+/// Prefix of the package `source` for crates coming from a sparse registry.
+const SPARSE_REGISTRY_SOURCE_PREFIX: &str = "sparse+";
+/// Prefix of the package `source` for crates coming from a Git repository.
+const GIT_SOURCE_PREFIX: &str = "git+";
+
+
+/// Find the Cargo.lock that applies to the crate with given manifest.
+///
+/// Like with Cargo, this is the one next to the root manifest of the crate's workspace,
+/// or next to the crate's own manifest if it isn't part of a workspace.
+pub fn find_lockfile<P: AsRef<Path>>(manifest_path: P) -> Option<PathBuf> {
+    let manifest_path = manifest_path.as_ref();
+    let root_manifest = match cargo_toml::find_workspace_root(manifest_path) {
+        Ok(Some(root)) => root,
+        Ok(None) => manifest_path.to_owned(),
+        Err(e) => {
+            debug!("Cannot find workspace root of {}: {}", manifest_path.display(), e);
+            manifest_path.to_owned()
+        }
+    };
+    let path = cargo_toml::manifest_dir(&root_manifest).join(LOCKFILE_NAME);
+    if path.is_file() {
+        trace!("Using {} for manifest {}", path.display(), manifest_path.display());
+        Some(path)
+    } else {
+        debug!("No {} found for manifest {}", LOCKFILE_NAME, manifest_path.display());
+        None
+    }
+}
+
+/// Read the Cargo.lock file from given path.
+pub fn read_lockfile<P: AsRef<Path>>(path: P) -> Result<Lockfile, Error> {
+    let path = path.as_ref();
+    trace!("Reading lockfile: {}", path.display());
+
+    let content = fs::read_to_string(path)?;
+    let mut raw: RawLockfile = toml::from_str(&content)?;
+
+    // Older versions of Cargo.lock keep the checksums in a separate [metadata] section,
+    // under keys like "checksum $NAME $VERSION ($SOURCE)".
+    if !raw.metadata.is_empty() {
+        for package in &mut raw.package {
+            if package.checksum.is_none() {
+                let key = format!("checksum {}", package.id());
+                package.checksum = raw.metadata.get(&key).cloned();
+            }
+        }
+    }
+
+    let lockfile = Lockfile::new(raw.package);
+    debug!("{} locked packages found in {}", lockfile.packages.len(), path.display());
+    Ok(lockfile)
+}
+
+
+/// Contents of the Cargo.lock file.
+#[derive(Debug)]
+pub struct Lockfile {
+    packages: Vec<LockedPackage>,
+    /// Indices of packages, grouped by their names.
+    by_name: HashMap<String, Vec<usize>>,
+}
+
+/// A single package from the resolved dependency graph.
+#[derive(Clone, Debug, Deserialize)]
+pub struct LockedPackage {
+    /// Name of the crate.
+    pub name: String,
+    /// Exact version of the crate.
+    pub version: String,
+    /// Where the crate comes from, like "registry+https://github.com/rust-lang/crates.io-index".
+    /// Path dependencies and workspace members don't have any.
+    #[serde(default)]
+    pub source: Option<String>,
+    /// SHA256 checksum of the crate's package, if it comes from a registry.
+    /// Unlike the name & version, this tells apart the same crate
+    /// published to different registries.
+    #[serde(default)]
+    pub checksum: Option<String>,
+    /// Dependencies of the package, formatted as "$NAME [$VERSION [($SOURCE)]]".
+    #[serde(default)]
+    pub dependencies: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawLockfile {
+    #[serde(default)]
+    package: Vec<LockedPackage>,
+    #[serde(default)]
+    metadata: BTreeMap<String, String>,
+}
+
+impl Lockfile {
+    fn new(packages: Vec<LockedPackage>) -> Self {
+        let mut by_name = HashMap::new();
+        for (i, package) in packages.iter().enumerate() {
+            by_name.entry(package.name.clone()).or_insert_with(Vec::new).push(i);
+        }
+        Lockfile{packages, by_name}
+    }
+
+    /// Return the version of given crate that has been locked for given requirement.
+    pub fn locked_version(&self, name: &str, req: &VersionReq) -> Option<Version> {
+        self.by_name.get(name)?.iter()
+            .map(|&i| &self.packages[i])
+            .filter(|p| p.source.is_some())
+            .filter_map(|p| Version::parse(&p.version).ok())
+            .filter(|v| req.matches(v))
+            .max()
+    }
+
+    /// Walk the dependency graph breadth-first, starting from packages with given names
+    /// and exact versions, and return all the packages that are reachable from them
+    /// along with the number of edges that separates them from the starting ones.
+    ///
+    /// A starting package without a version is only found if it's the sole one of its name.
+    /// Starting packages have depth zero and are not included in the result.
+    /// Only the packages no further than `max_depth` (if given) are returned.
+    pub fn reachable_packages<'n, I>(
+        &self, start: I, max_depth: Option<usize>
+    ) -> Vec<(usize, &LockedPackage)>
+        where I: IntoIterator<Item=(&'n str, Option<&'n Version>)>
+    {
+        let mut visited = HashSet::new();
+        let mut queue = VecDeque::new();
+        for (name, version) in start {
+            let indices = self.by_name.get(name).map(|i| i.as_slice()).unwrap_or(&[]);
+            let start_indices: Vec<_> = match version {
+                Some(v) => indices.iter().cloned()
+                    .filter(|&i| Version::parse(&self.packages[i].version).ok().as_ref() == Some(v))
+                    .collect(),
+                None if indices.len() > 1 => {
+                    warn!("Cannot tell which of the {} locked versions of `{}` is used",
+                        indices.len(), name);
+                    vec![]
+                }
+                None => indices.to_vec(),
+            };
+            for i in start_indices {
+                if visited.insert(i) {
+                    queue.push_back((0, i));
+                }
+            }
+        }
+
+        let mut result = vec![];
+        while let Some((depth, i)) = queue.pop_front() {
+            if depth > 0 {
+                result.push((depth, &self.packages[i]));
+            }
+            if max_depth.map(|md| depth >= md).unwrap_or(false) {
+                continue;
+            }
+            for dep in &self.packages[i].dependencies {
+                match self.resolve(dep) {
+                    Some(j) => if visited.insert(j) {
+                        queue.push_back((depth + 1, j));
+                    },
+                    None => warn!("Cannot resolve `{}` in the dependencies of {}",
+                        dep, self.packages[i].id()),
+                }
+            }
+        }
+        result
+    }
+
+    /// Find the package referred to in the `dependencies` array of another one.
+    fn resolve(&self, dep: &str) -> Option<usize> {
+        let mut parts = dep.splitn(3, ' ');
+        let name = parts.next()?;
+        let version = parts.next();
+        let source = parts.next().map(|s| s.trim_start_matches('(').trim_end_matches(')'));
+        self.by_name.get(name)?.iter().cloned().find(|&i| {
+            let package = &self.packages[i];
+            version.map(|v| v == package.version).unwrap_or(true)
+                && source.map(|s| Some(s) == package.source.as_ref().map(|s| s.as_str()))
+                    .unwrap_or(true)
+        })
+    }
+}
+
+impl LockedPackage {
+    /// Identifier of the package, as used in the [metadata] section of old lockfiles.
+    fn id(&self) -> String {
+        match self.source {
+            Some(ref s) => format!("{} {} ({})", self.name, self.version, s),
+            None => format!("{} {}", self.name, self.version),
+        }
+    }
+
+    /// Return the index URL of the alternative registry that the package comes from.
+    /// Returns None for crates.io packages and those that don't come from a registry.
+    fn registry_index(&self) -> Option<&str> {
+        let source = self.source.as_ref()?;
+        if !(source.starts_with(REGISTRY_SOURCE_PREFIX)
+                || source.starts_with(SPARSE_REGISTRY_SOURCE_PREFIX)) {
+            return None;
+        }
+        if is_crates_io_index(source) { None } else { Some(source.as_str()) }
+    }
+
+    /// Convert the locked package into a `Dependency`.
+    ///
+    /// Alternative registries are referred to by the names they have in given Cargo config,
+    /// or by their index URLs if they aren't configured.
+    ///
+    /// Returns None for packages without a source (i.e. path dependencies),
+    /// as the lockfile doesn't tell where exactly they are.
+    pub fn to_dependency(&self, config: &CargoConfig) -> Option<Dependency> {
+        let source = self.source.as_ref()?;
+        if source.starts_with(REGISTRY_SOURCE_PREFIX)
+                || source.starts_with(SPARSE_REGISTRY_SOURCE_PREFIX) {
+            let version = Version::parse(&self.version).ok()?;
+            let dep = Dependency::with_version(&self.name, format!("={}", version))
+                .with_locked_version(version);
+            return Some(match self.registry_index() {
+                Some(index) => dep.with_registry(config.registry_for_index(index).unwrap_or(index)),
+                None => dep,
+            });
+        }
+        if source.starts_with(GIT_SOURCE_PREFIX) {
+            // Strip the prefix, the ?branch=... query, and the #commit fragment.
+            // The reference is taken from the query if present, otherwise it's the exact commit.
+            let source = &source[GIT_SOURCE_PREFIX.len()..];
+            let (source, commit) = split_off(source, '#');
+            let (url, query) = split_off(source, '?');
+            let dep = Dependency::with_git_url(&self.name, url);
+            let reference = query.and_then(GitReference::from_url_query)
+                .or_else(|| commit.map(|c| GitReference::Rev(c.to_owned())));
+            return Some(match reference {
+                Some(r) => dep.with_git_reference(r),
+                None => dep,
+            });
+        }
+        warn!("Unrecognized source of locked package {}: {}", self.name, source);
+        None
+    }
+}
+
+
+/// Split the string at the first occurrence of given separator.
+fn split_off(s: &str, sep: char) -> (&str, Option<&str>) {
+    match s.find(sep) {
+        Some(idx) => (&s[..idx], Some(&s[idx + sep.len_utf8()..])),
+        None => (s, None),
+    }
+}
+
+
+/// Error while reading Cargo.lock.
+#[derive(Debug, Error)]
+pub enum Error {
+    Io(io::Error),
+    Toml(toml::de::Error),
+}
+
+
+#[cfg(test)]
+mod tests {
+    use semver::{Version, VersionReq};
+    use toml;
+    use model::{CrateLocation, GitReference};
+    use testing::create_temp_dir;
+    use super::{find_lockfile, LockedPackage, Lockfile, RawLockfile, read_lockfile};
+
+    const LOCKFILE: &str = r#"
+[[package]]
+name = "app"
+version = "0.1.0"
+dependencies = [
+ "log 0.4.8 (registry+https://github.com/rust-lang/crates.io-index)",
+ "serde 1.0.104 (registry+https://github.com/rust-lang/crates.io-index)",
+]
+
+[[package]]
+name = "cfg-if"
+version = "0.1.10"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "log"
+version = "0.3.9"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+dependencies = [
+ "log 0.4.8 (registry+https://github.com/rust-lang/crates.io-index)",
+]
+
+[[package]]
+name = "log"
+version = "0.4.8"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+dependencies = [
+ "cfg-if 0.1.10 (registry+https://github.com/rust-lang/crates.io-index)",
+]
+
+[[package]]
+name = "serde"
+version = "1.0.104"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+"#;
+
+    fn lockfile() -> Lockfile {
+        let raw: RawLockfile = toml::from_str(LOCKFILE).unwrap();
+        Lockfile::new(raw.package)
+    }
+
+    #[test]
+    fn locked_version() {
+        let lockfile = lockfile();
+        let req = |r| VersionReq::parse(r).unwrap();
+        assert_eq!(Some(Version::new(0, 4, 8)), lockfile.locked_version("log", &req("0.4")));
+        assert_eq!(Some(Version::new(0, 3, 9)), lockfile.locked_version("log", &req("0.3")));
+        assert_eq!(None, lockfile.locked_version("log", &req("0.5")));
+        assert_eq!(None, lockfile.locked_version("app", &req("*")));
+    }
+
+    #[test]
+    fn reachable_packages() {
+        let lockfile = lockfile();
+        let depths = |max_depth| -> Vec<_> {
+            lockfile.reachable_packages(vec![("app", None)], max_depth).into_iter()
+                .map(|(d, p)| (d, p.name.as_str())).collect()
+        };
+        assert_eq!(vec![(1, "log"), (1, "serde"), (2, "cfg-if")], depths(None));
+        assert_eq!(vec![(1, "log"), (1, "serde")], depths(Some(1)));
+
+        // Only the given version of a crate is walked from.
+        let (old_log, new_log) = (Version::new(0, 3, 9), Version::new(0, 4, 8));
+        let versions = |version| -> Vec<_> {
+            lockfile.reachable_packages(vec![("log", version)], None).into_iter()
+                .map(|(d, p)| (d, p.name.as_str(), p.version.as_str())).collect()
+        };
+        assert_eq!(vec![(1, "log", "0.4.8"), (2, "cfg-if", "0.1.10")], versions(Some(&old_log)));
+        assert_eq!(vec![(1, "cfg-if", "0.1.10")], versions(Some(&new_log)));
+        assert!(versions(None).is_empty());
+    }
+
+    #[test]
+    fn lockfile_of_workspace_root() {
+        let dir = create_temp_dir(&[
+            ("Cargo.lock", ""),
+            ("ws/Cargo.toml", "[workspace]\nmembers = [\"app\"]\n"),
+            ("ws/Cargo.lock", ""),
+            ("ws/app/Cargo.toml", "[package]\nname = \"app\"\nversion = \"0.1.0\"\n"),
+            ("standalone/Cargo.toml", "[package]\nname = \"foo\"\nversion = \"0.1.0\"\n"),
+        ]);
+        assert_eq!(Some(dir.join("ws/Cargo.lock")), find_lockfile(dir.join("ws/app/Cargo.toml")));
+        // The lockfile of some unrelated parent directory doesn't apply.
+        assert_eq!(None, find_lockfile(dir.join("standalone/Cargo.toml")));
+    }
+
+    #[test]
+    fn checksums() {
+        let dir = create_temp_dir(&[
+            ("new/Cargo.lock", concat!(
+                "[[package]]\nname = \"log\"\nversion = \"0.4.8\"\n",
+                "source = \"registry+https://github.com/rust-lang/crates.io-index\"\n",
+                "checksum = \"abcd\"\n")),
+            ("old/Cargo.lock", concat!(
+                "[[package]]\nname = \"log\"\nversion = \"0.4.8\"\n",
+                "source = \"registry+https://github.com/rust-lang/crates.io-index\"\n",
+                "[metadata]\n",
+                "\"checksum log 0.4.8 (registry+https://github.com/rust-lang/crates.io-index)\"",
+                " = \"abcd\"\n")),
+        ]);
+        for path in &["new/Cargo.lock", "old/Cargo.lock"] {
+            let lockfile = read_lockfile(dir.join(path)).unwrap();
+            assert_eq!(Some("abcd"), lockfile.packages[0].checksum.as_ref().map(|c| c.as_str()));
+        }
+    }
+
+    #[test]
+    fn git_package_to_dependency() {
+        let package = LockedPackage{
+            name: "foo".into(),
+            version: "0.1.0".into(),
+            source: Some("git+https://github.com/foo-rs/foo?branch=dev#0123abcd".into()),
+            checksum: None,
+            dependencies: vec![],
+        };
+        let dep = package.to_dependency(&Default::default()).unwrap();
+        match *dep.location() {
+            CrateLocation::Git{ref url, ref reference} => {
+                assert_eq!("https://github.com/foo-rs/foo", url);
+                assert_eq!(Some(GitReference::Branch("dev".into())), *reference);
+            }
+            ref l => panic!("unexpected location: {:?}", l),
+        }
+    }
+}
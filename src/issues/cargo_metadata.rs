@@ -0,0 +1,483 @@
+//! Module for reading the resolved dependency graph from `cargo metadata`.
+//!
+//! This is an alternative to reading Cargo.toml and Cargo.lock ourselves,
+//! which makes Cargo take care of all the intricacies of the manifest format.
+
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::env;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use serde_json;
+
+use model::{Dependency, DependencyKind, Package};
+use super::cargo_config::CargoConfig;
+use super::cargo_lock::LockedPackage;
+use super::cargo_toml::{self, DependencyFilter};
+use super::features;
+
+
+/// Version of the `cargo metadata` output format that we understand.
+const FORMAT_VERSION: &str = "1";
+
+
+/// Run `cargo metadata` for the project with given Cargo.toml manifest,
+/// resolving the dependency graph with given features enabled (besides the default ones).
+pub fn read_metadata<P: AsRef<Path>>(
+    manifest_path: P, features: &[String]
+) -> Result<Metadata, Error> {
+    let manifest_path = manifest_path.as_ref();
+    // When we're invoked as `cargo contribute`, Cargo tells us where it is.
+    let cargo = env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+    trace!("Running {:?} metadata for {}", cargo, manifest_path.display());
+
+    let mut command = Command::new(&cargo);
+    command.args(&["metadata", "--format-version", FORMAT_VERSION, "--manifest-path"])
+        .arg(manifest_path);
+    if !features.is_empty() {
+        command.args(&["--features", &features.join(",")]);
+    }
+    let output = command.output()?;
+    if !output.status.success() {
+        return Err(Error::Cargo(String::from_utf8_lossy(&output.stderr).trim().to_owned()));
+    }
+    let metadata: Metadata = serde_json::from_slice(&output.stdout)?;
+    debug!("`cargo metadata` returned {} packages", metadata.packages.len());
+    Ok(metadata)
+}
+
+
+/// Output of `cargo metadata`.
+#[derive(Debug, Deserialize)]
+pub struct Metadata {
+    packages: Vec<MetadataPackage>,
+    /// IDs of the packages that are members of the workspace.
+    workspace_members: Vec<String>,
+    /// Resolved dependency graph. Missing if `--no-deps` has been passed.
+    #[serde(default)]
+    resolve: Option<Resolve>,
+}
+
+/// Single package from `cargo metadata` output.
+#[derive(Debug, Deserialize)]
+struct MetadataPackage {
+    id: String,
+    name: String,
+    version: String,
+    #[serde(default)]
+    source: Option<String>,
+    manifest_path: PathBuf,
+    /// Dependencies as declared in the package's manifest.
+    #[serde(default)]
+    dependencies: Vec<DependencyDecl>,
+    /// Features of the package, as declared in its manifest.
+    #[serde(default)]
+    features: BTreeMap<String, Vec<String>>,
+
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    authors: Vec<String>,
+    #[serde(default)]
+    license: Option<String>,
+    #[serde(default)]
+    keywords: Vec<String>,
+    #[serde(default)]
+    categories: Vec<String>,
+    #[serde(default)]
+    homepage: Option<String>,
+    #[serde(default)]
+    documentation: Option<String>,
+    #[serde(default)]
+    repository: Option<String>,
+}
+
+/// Dependency as declared in a package manifest.
+#[derive(Debug, Deserialize)]
+struct DependencyDecl {
+    /// Actual name of the dependent package.
+    name: String,
+    /// Name the dependency has been renamed to, if any.
+    #[serde(default)]
+    rename: Option<String>,
+    #[serde(default)]
+    optional: bool,
+}
+
+impl DependencyDecl {
+    /// Name that the dependency is referred to in the manifest.
+    fn alias(&self) -> &str {
+        self.rename.as_ref().unwrap_or(&self.name)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Resolve {
+    nodes: Vec<ResolveNode>,
+}
+
+/// Package in the resolved dependency graph, along with its outgoing edges.
+#[derive(Debug, Deserialize)]
+struct ResolveNode {
+    id: String,
+    #[serde(default)]
+    deps: Vec<ResolveDep>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResolveDep {
+    /// ID of the package that is depended on.
+    pkg: String,
+    /// Kinds of the dependency, with their platforms.
+    /// Older versions of Cargo don't provide this.
+    #[serde(default)]
+    dep_kinds: Vec<DepKind>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DepKind {
+    /// "dev", "build", or null for normal dependencies.
+    #[serde(default)]
+    kind: Option<String>,
+    #[serde(default)]
+    target: Option<String>,
+}
+
+
+/// Dependency of the project read from `cargo metadata` output.
+#[derive(Debug)]
+pub struct ResolvedDependency {
+    /// Path to the manifest of the crate that depends on this one,
+    /// or the project's root manifest for transitive dependencies.
+    pub manifest_path: PathBuf,
+    pub dependency: Dependency,
+    /// Distance from the project in the dependency graph
+    /// (1 for direct dependencies, 2 for their dependencies, and so on).
+    pub depth: usize,
+    /// Manifest of the dependency itself.
+    pub package: Package,
+}
+
+impl Metadata {
+    /// List the dependencies of all the workspace members
+    /// (matching given filter), followed by their own dependencies up to `max_depth`.
+    ///
+    /// Dependencies are listed only once, and the workspace members themselves are omitted.
+    pub fn list_dependencies(
+        &self, root_manifest: &Path, filter: &DependencyFilter, max_depth: Option<usize>,
+        config: &CargoConfig,
+    ) -> Vec<ResolvedDependency> {
+        let resolve = match self.resolve {
+            Some(ref r) => r,
+            None => {
+                warn!("`cargo metadata` output doesn't contain the dependency graph");
+                return vec![];
+            }
+        };
+        let packages: HashMap<_, _> = self.packages.iter().map(|p| (p.id.as_str(), p)).collect();
+        let nodes: HashMap<_, _> = resolve.nodes.iter().map(|n| (n.id.as_str(), n)).collect();
+        let members: HashSet<_> = self.workspace_members.iter().map(|m| m.as_str()).collect();
+
+        // Start with the direct dependencies of workspace members that match the filter.
+        let mut visited = HashSet::new();
+        let mut queue = VecDeque::new();
+        for member in &self.workspace_members {
+            let member = member.as_str();
+            let (member_package, node) = match (packages.get(member), nodes.get(member)) {
+                (Some(p), Some(n)) => (p, n),
+                _ => continue,
+            };
+            let enabled = member_package.enabled_dependencies(&filter.features);
+            for dep in &node.deps {
+                if members.contains(dep.pkg.as_str()) {
+                    continue;
+                }
+                let accepted = accepted_kind(dep, filter);
+                let (kind, target) = match accepted {
+                    Some(kt) => kt,
+                    None => continue,
+                };
+                if !visited.insert(dep.pkg.as_str()) {
+                    continue;
+                }
+                let decl = packages.get(dep.pkg.as_str())
+                    .and_then(|p| member_package.declaration_of(&p.name));
+                let direct = DirectDependency{
+                    alias: decl.and_then(|d| d.rename.as_ref()).map(|r| r.as_str()),
+                    kind,
+                    target,
+                    optional: decl.map(|d| d.optional).unwrap_or(false),
+                    feature: decl.and_then(|d| enabled.get(d.alias())).cloned(),
+                };
+                queue.push_back((1, dep.pkg.as_str(), member_package.manifest_path.as_path(),
+                    Some(direct)));
+            }
+        }
+
+        // Walk the rest of the graph breadth-first.
+        let mut result = vec![];
+        while let Some((depth, id, manifest_path, direct)) = queue.pop_front() {
+            let package = match packages.get(id) {
+                Some(p) => p,
+                None => { warn!("Package {} not found in `cargo metadata` output", id); continue; }
+            };
+            let alias = direct.as_ref().and_then(|d| d.alias);
+            if let Some(dep) = package.to_dependency(alias, config) {
+                let dep = match direct {
+                    Some(direct) => direct.apply(dep),
+                    None => dep,
+                };
+                result.push(ResolvedDependency{
+                    manifest_path: manifest_path.to_owned(),
+                    dependency: dep,
+                    depth,
+                    package: package.to_package(),
+                });
+            }
+
+            if max_depth.map(|md| depth >= md).unwrap_or(false) {
+                continue;
+            }
+            for dep in nodes.get(id).map(|n| n.deps.as_slice()).unwrap_or(&[]) {
+                if !members.contains(dep.pkg.as_str()) && visited.insert(dep.pkg.as_str()) {
+                    queue.push_back((depth + 1, dep.pkg.as_str(), root_manifest, None));
+                }
+            }
+        }
+        result
+    }
+}
+
+/// Attributes of a direct dependency of a workspace member.
+struct DirectDependency<'m> {
+    /// Name the dependency has been renamed to, if any.
+    alias: Option<&'m str>,
+    kind: DependencyKind,
+    target: Option<&'m str>,
+    optional: bool,
+    /// Feature that enables the dependency, if it's optional.
+    feature: Option<String>,
+}
+
+impl<'m> DirectDependency<'m> {
+    fn apply(self, dep: Dependency) -> Dependency {
+        let mut dep = dep.with_kind(self.kind).with_optional(self.optional);
+        if let Some(target) = self.target {
+            dep = dep.with_target(target);
+        }
+        if let Some(feature) = self.feature {
+            dep = dep.with_feature(feature);
+        }
+        dep
+    }
+}
+
+/// Determine the kind and target of a direct dependency, as long as they are accepted.
+fn accepted_kind<'d>(
+    dep: &'d ResolveDep, filter: &DependencyFilter
+) -> Option<(DependencyKind, Option<&'d str>)> {
+    if dep.dep_kinds.is_empty() {
+        return if filter.accepts(DependencyKind::Normal, None) {
+            Some((DependencyKind::Normal, None))
+        } else {
+            None
+        };
+    }
+    dep.dep_kinds.iter()
+        .filter_map(|dk| {
+            let kind = match dk.kind {
+                Some(ref k) => k.parse().ok()?,
+                None => DependencyKind::Normal,
+            };
+            let target = dk.target.as_ref().map(|t| t.as_str());
+            if filter.accepts(kind, target) { Some((kind, target)) } else { None }
+        })
+        .next()
+}
+
+impl MetadataPackage {
+    /// Return the declaration of a dependency on given package.
+    fn declaration_of(&self, name: &str) -> Option<&DependencyDecl> {
+        // The same package may be declared multiple times (e.g. for different targets),
+        // in which case we prefer the renamed declarations.
+        self.dependencies.iter()
+            .filter(|d| d.name == name)
+            .max_by_key(|d| d.rename.is_some())
+    }
+
+    /// Determine which optional dependencies are enabled by the default features
+    /// and given ones, mapping their names to the features that enable them.
+    fn enabled_dependencies(&self, requested: &[String]) -> HashMap<String, String> {
+        let optional: HashSet<_> = self.dependencies.iter()
+            .filter(|d| d.optional).map(|d| d.alias()).collect();
+        features::enabled_dependencies(&self.name, &self.features, &optional, requested)
+    }
+
+    /// Create a `Dependency` on this package, optionally renamed to given alias.
+    fn to_dependency(&self, alias: Option<&str>, config: &CargoConfig) -> Option<Dependency> {
+        let dep = match self.source {
+            // The source of registry and Git packages is the same as in Cargo.lock.
+            Some(_) => LockedPackage{
+                name: self.name.clone(),
+                version: self.version.clone(),
+                source: self.source.clone(),
+                checksum: None,
+                dependencies: vec![],
+            }.to_dependency(config)?,
+            None => {
+                let dir = cargo_toml::manifest_dir(&self.manifest_path);
+                Dependency::with_path(&self.name, dir)
+            }
+        };
+        Some(match alias {
+            Some(alias) => dep.with_alias(alias),
+            None => dep,
+        })
+    }
+
+    fn to_package(&self) -> Package {
+        Package{
+            name: self.name.clone(),
+            version: self.version.clone(),
+            description: self.description.clone().unwrap_or_default(),
+            authors: self.authors.clone(),
+            license: self.license.clone(),
+            keywords: self.keywords.clone(),
+            categories: self.categories.clone(),
+            homepage: self.homepage.clone(),
+            documentation: self.documentation.clone(),
+            repository: self.repository.clone(),
+        }
+    }
+}
+
+
+/// Error while obtaining the metadata from Cargo.
+#[derive(Debug, Error)]
+pub enum Error {
+    /// Error running Cargo.
+    Io(io::Error),
+    /// Error parsing the output of `cargo metadata`.
+    Json(serde_json::Error),
+    /// `cargo metadata` has failed.
+    #[error(msg_embedded, no_from, non_std)]
+    Cargo(String),
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use serde_json;
+    use model::{CrateLocation, DependencyKind};
+    use issues::cargo_toml::DependencyFilter;
+    use super::Metadata;
+
+    const METADATA: &str = r#"{
+        "packages": [
+            {
+                "id": "app 0.1.0 (path+file:///work/app)",
+                "name": "app", "version": "0.1.0", "source": null,
+                "manifest_path": "/work/app/Cargo.toml",
+                "dependencies": [
+                    {"name": "log", "rename": null},
+                    {"name": "serde", "rename": "serde1", "optional": true},
+                    {"name": "proptest", "rename": null}
+                ],
+                "features": {"default": ["json"], "json": ["dep:serde1"]}
+            },
+            {
+                "id": "log 0.4.8 (registry+https://github.com/rust-lang/crates.io-index)",
+                "name": "log", "version": "0.4.8",
+                "source": "registry+https://github.com/rust-lang/crates.io-index",
+                "manifest_path": "/cargo/registry/src/log-0.4.8/Cargo.toml",
+                "repository": "https://github.com/rust-lang/log"
+            },
+            {
+                "id": "cfg-if 0.1.10 (registry+https://github.com/rust-lang/crates.io-index)",
+                "name": "cfg-if", "version": "0.1.10",
+                "source": "registry+https://github.com/rust-lang/crates.io-index",
+                "manifest_path": "/cargo/registry/src/cfg-if-0.1.10/Cargo.toml",
+                "repository": "https://github.com/alexcrichton/cfg-if"
+            },
+            {
+                "id": "serde 1.0.104 (registry+https://github.com/rust-lang/crates.io-index)",
+                "name": "serde", "version": "1.0.104",
+                "source": "registry+https://github.com/rust-lang/crates.io-index",
+                "manifest_path": "/cargo/registry/src/serde-1.0.104/Cargo.toml",
+                "description": null, "homepage": "https://serde.rs"
+            },
+            {
+                "id": "proptest 0.9.4 (git+https://github.com/AltSysrq/proptest#abcd)",
+                "name": "proptest", "version": "0.9.4",
+                "source": "git+https://github.com/AltSysrq/proptest#abcd",
+                "manifest_path": "/cargo/git/checkouts/proptest/Cargo.toml"
+            }
+        ],
+        "workspace_members": ["app 0.1.0 (path+file:///work/app)"],
+        "resolve": {
+            "nodes": [
+                {
+                    "id": "app 0.1.0 (path+file:///work/app)",
+                    "deps": [
+                        {"pkg":
+                            "log 0.4.8 (registry+https://github.com/rust-lang/crates.io-index)",
+                         "dep_kinds": [{"kind": null, "target": null}]},
+                        {"pkg":
+                            "serde 1.0.104 (registry+https://github.com/rust-lang/crates.io-index)",
+                         "dep_kinds": [{"kind": null, "target": "cfg(unix)"}]},
+                        {"pkg": "proptest 0.9.4 (git+https://github.com/AltSysrq/proptest#abcd)",
+                         "dep_kinds": [{"kind": "dev", "target": null}]}
+                    ]
+                },
+                {
+                    "id": "log 0.4.8 (registry+https://github.com/rust-lang/crates.io-index)",
+                    "deps": [
+                        {"pkg":
+                            "cfg-if 0.1.10 (registry+https://github.com/rust-lang/crates.io-index)"}
+                    ]
+                },
+                {"id": "cfg-if 0.1.10 (registry+https://github.com/rust-lang/crates.io-index)"},
+                {"id": "serde 1.0.104 (registry+https://github.com/rust-lang/crates.io-index)"},
+                {"id": "proptest 0.9.4 (git+https://github.com/AltSysrq/proptest#abcd)"}
+            ]
+        }
+    }"#;
+
+    #[test]
+    fn direct_dependencies() {
+        let metadata: Metadata = serde_json::from_str(METADATA).unwrap();
+        let filter = DependencyFilter{
+            kinds: vec![DependencyKind::Normal, DependencyKind::Development],
+            target: Some("x86_64-pc-windows-msvc".into()),
+            ..DependencyFilter::default()
+        };
+        let deps = metadata.list_dependencies(
+            Path::new("/work/app/Cargo.toml"), &filter, Some(1), &Default::default());
+        let names: Vec<_> = deps.iter().map(|d| (d.dependency.alias(), d.depth)).collect();
+        assert_eq!(vec![("log", 1), ("proptest", 1)], names);
+        assert_eq!(DependencyKind::Development, deps[1].dependency.kind());
+        assert!(deps[1].dependency.location().is_git());
+        assert_eq!(Some("https://github.com/rust-lang/log".into()), deps[0].package.repository);
+    }
+
+    #[test]
+    fn transitive_dependencies() {
+        let metadata: Metadata = serde_json::from_str(METADATA).unwrap();
+        let deps = metadata.list_dependencies(
+            Path::new("/work/app/Cargo.toml"), &DependencyFilter::default(), None,
+            &Default::default());
+        let names: Vec<_> = deps.iter().map(|d| (d.dependency.alias(), d.depth)).collect();
+        assert_eq!(vec![("log", 1), ("serde1", 1), ("cfg-if", 2)], names);
+        assert_eq!("serde", deps[1].dependency.name());
+        assert!(deps[1].dependency.is_optional());
+        assert_eq!(Some("json"), deps[1].dependency.feature());
+        match *deps[1].dependency.location() {
+            CrateLocation::Registry{ref registry, ..} => assert!(registry.is_none()),
+            ref l => panic!("unexpected location: {:?}", l),
+        }
+        assert_eq!(Path::new("/work/app/Cargo.toml"), deps[2].manifest_path);
+    }
+}
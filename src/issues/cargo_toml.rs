@@ -1,13 +1,17 @@
 //! Module for reading the crate manifest, Cargo.toml.
 
-use std::fs::File;
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{self, File};
 use std::io::{self, Read};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
+use glob::glob;
 use serde::de::{Deserialize, Error as SerdeDeError};
 use toml::{self, Value as Toml};
 
-use model::{Dependency, Package};
+use model::{Dependency, DependencyKind, Package};
+use super::features;
+use super::target;
 
 
 /// Read [package] information from given Cargo.toml manifest.
@@ -16,44 +20,482 @@ pub fn read_package<P: AsRef<Path>>(manifest_path: P) -> Result<Package, Error>
     trace!("Reading [package] from manifest: {}", path.display());
 
     let content = read_file_to_string(path)?;
-    let manifest: Toml = toml::from_str(&content)?;
+    parse_package(&content, path)
+}
+
+/// Parse [package] information from the content of a Cargo.toml manifest
+/// that comes from given path.
+pub fn parse_package<P: AsRef<Path>>(content: &str, manifest_path: P) -> Result<Package, Error> {
+    let manifest: Toml = toml::from_str(content)?;
     let package = manifest.get("package")
         .ok_or_else(|| Error::Toml(toml::de::Error::custom(format!(
-            "[package] section not found in {}", path.display()))))?;
+            "[package] section not found in {}", manifest_path.as_ref().display()))))?;
     Deserialize::deserialize(package.clone()).map_err(Error::Toml)
 }
 
+/// Read the overrides of dependencies' repositories from given Cargo.toml manifest,
+/// i.e. the `[package.metadata.contribute.repositories]` table
+/// (or `[workspace.metadata.contribute.repositories]` in a workspace root).
+///
+/// The overrides map crate names to their repositories, which are not validated here.
+pub fn read_repository_overrides<P: AsRef<Path>>(
+    manifest_path: P
+) -> Result<BTreeMap<String, String>, Error> {
+    let path = manifest_path.as_ref();
+    let manifest = read_manifest(path)?;
+
+    let mut result = BTreeMap::new();
+    for section in &["workspace", "package"] {
+        let overrides = manifest.get(section)
+            .and_then(|s| s.get("metadata"))
+            .and_then(|m| m.get("contribute"))
+            .and_then(|c| c.get("repositories"));
+        if let Some(overrides) = overrides {
+            trace!("Reading [{}.metadata.contribute.repositories] from manifest: {}",
+                section, path.display());
+            let overrides: BTreeMap<String, String> = Deserialize::deserialize(overrides.clone())?;
+            result.extend(overrides);
+        }
+    }
+    Ok(result)
+}
+
+
+/// List the manifests of all the crates that make up the project
+/// described by given Cargo.toml.
+///
+/// For a regular crate, this is just the manifest itself.
+/// For a workspace root, it is the root [package] (unless the manifest is virtual)
+/// followed by all the [workspace] members, minus the excluded ones.
+pub fn list_workspace_members<P: AsRef<Path>>(manifest_path: P) -> Result<Vec<PathBuf>, Error> {
+    let path = manifest_path.as_ref();
+    trace!("Reading [workspace] members from manifest: {}", path.display());
+
+    let manifest = read_manifest(path)?;
+
+    let mut result = vec![];
+    if manifest.get("package").is_some() {
+        result.push(path.to_owned());
+    }
+    let workspace = match manifest.get("workspace") {
+        Some(&Toml::Table(ref t)) => t,
+        Some(v) => return Err(Error::Toml(toml::de::Error::custom(format!(
+            "[workspace] must be a table, got {}", v.type_str())))),
+        None => {
+            if result.is_empty() {
+                return Err(Error::Toml(toml::de::Error::custom(format!(
+                    "neither [package] nor [workspace] found in {}", path.display()))));
+            }
+            return Ok(result);
+        }
+    };
+
+    let root_dir = manifest_dir(path);
+    let members = read_string_array(workspace, "members")?;
+    let excluded: Vec<_> = read_string_array(workspace, "exclude")?.into_iter()
+        .filter_map(|ex| root_dir.join(ex).canonicalize().ok())
+        .collect();
+    for member in members {
+        let pattern = format!("{}/{}", root_dir.display(), member);
+        trace!("Globbing for workspace members with pattern: {}", pattern);
+        let dirs = glob(&pattern).map_err(|e| Error::Toml(toml::de::Error::custom(format!(
+            "invalid workspace member pattern `{}`: {}", member, e))))?;
+        for dir in dirs.filter_map(Result::ok) {
+            let member_manifest = dir.join("Cargo.toml");
+            if !member_manifest.is_file() {
+                trace!("Skipping {} as it has no Cargo.toml", dir.display());
+                continue;
+            }
+            let canonical_dir = dir.canonicalize()?;
+            if excluded.iter().any(|ex| canonical_dir.starts_with(ex)) {
+                debug!("Workspace member {} is excluded", dir.display());
+                continue;
+            }
+            if !result.contains(&member_manifest) {
+                result.push(member_manifest);
+            }
+        }
+    }
+    debug!("{} workspace member(s) found in {}", result.len(), path.display());
+    Ok(result)
+}
+
+
+/// Criteria for selecting which dependencies are read from a crate manifest.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DependencyFilter {
+    /// Kinds of dependencies to include.
+    pub kinds: Vec<DependencyKind>,
+    /// Target triple to include the platform-specific dependencies for.
+    /// If omitted, platform-specific dependencies for all targets are included.
+    pub target: Option<String>,
+    /// Features to enable in addition to the default ones.
+    pub features: Vec<String>,
+    /// Whether to only include optional dependencies that are enabled by the features.
+    pub only_enabled: bool,
+}
+
+impl Default for DependencyFilter {
+    fn default() -> Self {
+        DependencyFilter{
+            kinds: vec![DependencyKind::Normal],
+            target: None,
+            features: vec![],
+            only_enabled: false,
+        }
+    }
+}
+
+impl DependencyFilter {
+    /// Whether a dependency of given kind, specific to given target (if any),
+    /// should be included.
+    pub fn accepts(&self, kind: DependencyKind, target: Option<&str>) -> bool {
+        self.kinds.contains(&kind) && target.map(|t| self.accepts_target(t)).unwrap_or(true)
+    }
+
+    /// Whether dependencies from given [target.$SPEC] section should be included.
+    fn accepts_target(&self, spec: &str) -> bool {
+        match self.target {
+            Some(ref triple) => target::matches(spec, triple),
+            None => true,
+        }
+    }
+}
+
 
 /// List the dependencies of a crate described by given Cargo.toml manifest.
-pub fn list_dependencies<P: AsRef<Path>>(manifest_path: P) -> Result<Vec<Dependency>, Error> {
+///
+/// Only the dependencies that match given filter are included.
+pub fn list_dependencies<P: AsRef<Path>>(
+    manifest_path: P, filter: &DependencyFilter
+) -> Result<Vec<Dependency>, Error> {
     let path = manifest_path.as_ref();
     trace!("Reading dependencies from manifest: {}", path.display());
 
-    let content = read_file_to_string(path)?;
-    let manifest: Toml = toml::from_str(&content)?;
-    match manifest.get("dependencies") {
-        None => {
-            debug!("No dependencies found in {}", path.display());
-            Ok(vec![])
+    let manifest = read_manifest(path)?;
+    let mut inherited = InheritedDependencies::new(path);
+
+    let mut result = vec![];
+    for &kind in &filter.kinds {
+        for dep in read_dependency_section(&manifest, kind, &mut inherited)? {
+            result.push(dep.with_kind(kind));
         }
+    }
+    match manifest.get("target") {
+        None => {}
+        Some(&Toml::Table(ref targets)) => {
+            for (spec, target_manifest) in targets {
+                if !filter.accepts_target(spec) {
+                    trace!("Skipping dependencies for target `{}`", spec);
+                    continue;
+                }
+                for &kind in &filter.kinds {
+                    for dep in read_dependency_section(target_manifest, kind, &mut inherited)? {
+                        result.push(dep.with_kind(kind).with_target(spec));
+                    }
+                }
+            }
+        }
+        Some(v) => return Err(Error::Toml(toml::de::Error::custom(format!(
+            "[target] must be a table, got {}", v.type_str())))),
+    }
+    let result = resolve_optional_dependencies(&manifest, result, filter)?;
+
+    if result.is_empty() {
+        debug!("No dependencies found in {}", path.display());
+    } else {
+        debug!("{} dependencies found in {}", result.len(), path.display());
+    }
+    Ok(result)
+}
+
+/// Determine which of the optional dependencies are enabled by the features,
+/// skipping those which aren't if the filter says so.
+fn resolve_optional_dependencies(
+    manifest: &Toml, deps: Vec<Dependency>, filter: &DependencyFilter
+) -> Result<Vec<Dependency>, Error> {
+    if !deps.iter().any(|d| d.is_optional()) {
+        return Ok(deps);
+    }
+    let package = manifest.get("package").and_then(|p| p.get("name")).and_then(|n| n.as_str())
+        .unwrap_or("");
+    let features = match manifest.get("features") {
+        None => BTreeMap::new(),
+        Some(&Toml::Table(ref t)) => t.keys()
+            .map(|f| read_string_array(t, f).map(|entries| (f.clone(), entries)))
+            .collect::<Result<_, _>>()?,
+        Some(v) => return Err(Error::Toml(toml::de::Error::custom(format!(
+            "[features] must be a table, got {}", v.type_str())))),
+    };
+    Ok(filter_optional_dependencies(package, &features, deps, filter))
+}
+
+/// Determine which of the optional dependencies of a package with given [features]
+/// are enabled, skipping those which aren't if the filter says so.
+pub fn filter_optional_dependencies(
+    package: &str, features: &BTreeMap<String, Vec<String>>, deps: Vec<Dependency>,
+    filter: &DependencyFilter,
+) -> Vec<Dependency> {
+    let optional: HashSet<_> = deps.iter().filter(|d| d.is_optional()).map(|d| d.alias()).collect();
+    let enabled = features::enabled_dependencies(package, features, &optional, &filter.features);
+
+    deps.into_iter().filter_map(|dep| {
+        if !dep.is_optional() {
+            return Some(dep);
+        }
+        match enabled.get(dep.alias()).cloned() {
+            Some(feature) => Some(dep.with_feature(feature)),
+            None if filter.only_enabled => {
+                debug!("Skipping optional dependency `{}` which isn't enabled", dep.alias());
+                None
+            }
+            None => Some(dep),
+        }
+    }).collect()
+}
+
+/// Read dependencies of given kind from the relevant section of manifest's TOML
+/// (which may be either the entire manifest, or its [target.$SPEC] part).
+fn read_dependency_section(
+    manifest: &Toml, kind: DependencyKind, inherited: &mut InheritedDependencies
+) -> Result<Vec<Dependency>, Error> {
+    let section = kind.manifest_section();
+    // Cargo also accepts the legacy spelling with an underscore, e.g. [dev_dependencies].
+    let deps = manifest.get(section).or_else(|| manifest.get(&*section.replace('-', "_")));
+    match deps {
+        None => Ok(vec![]),
         Some(&Toml::Table(ref t)) => {
             let result: Result<Vec<_>, _> = t.iter()
-                .map(|(name, v)| Dependency::from_toml(name, v).map_err(Error::Toml))
+                .map(|(name, v)| {
+                    match *v {
+                        Toml::Table(ref spec) if is_inherited(spec) => {
+                            let v = inherited.resolve(name, spec)?;
+                            Dependency::from_toml(name, &v).map_err(Error::Toml)
+                        }
+                        _ => Dependency::from_toml(name, v).map_err(Error::Toml),
+                    }
+                })
                 .collect();
-            match result {
-                Ok(ref deps) =>
-                    debug!("{} dependencies found in {}",deps.len(), path.display()),
-                Err(ref e) =>
-                    error!("Error while parsing dependencies in {}: {}", path.display(), e),
+            if let Err(ref e) = result {
+                error!("Error while parsing [{}]: {}", section, e);
             }
             result
         }
         Some(v) => Err(Error::Toml(toml::de::Error::custom(format!(
-            "[dependencies] must be a table, got {}", v.type_str())))),
+            "[{}] must be a table, got {}", section, v.type_str())))),
     }
 }
 
 
+/// Find the manifest of the crate that given directory belongs to.
+///
+/// Like Cargo, this looks for the nearest Cargo.toml in the directory itself
+/// and then its parent directories. Returns None if there is no such manifest.
+pub fn find_manifest<P: AsRef<Path>>(dir: P) -> Option<PathBuf> {
+    let dir = dir.as_ref();
+    let start_dir = dir.canonicalize().unwrap_or_else(|_| dir.to_owned());
+    let result = start_dir.ancestors()
+        .map(|d| d.join("Cargo.toml"))
+        .find(|candidate| candidate.is_file());
+    if let Some(ref path) = result {
+        trace!("Nearest manifest for {} is {}", dir.display(), path.display());
+    }
+    result
+}
+
+/// Names of directories that are skipped when looking for projects:
+/// build artifacts and vendored crates.
+const SKIPPED_DIRS: &[&str] = &["target", "vendor"];
+
+/// Find all the projects within given directory tree.
+///
+/// The result contains the root manifests of projects, so that workspace members
+/// are represented by the manifest of their workspace. Build directories,
+/// vendored crates, and hidden directories (like .git) are not looked into.
+pub fn find_projects<P: AsRef<Path>>(dir: P) -> Vec<PathBuf> {
+    let mut manifests = vec![];
+    collect_manifests(dir.as_ref(), &mut manifests);
+    trace!("{} manifests found under {}", manifests.len(), dir.as_ref().display());
+
+    let mut result = vec![];
+    for manifest in manifests {
+        let root = match find_workspace_root(&manifest) {
+            Ok(root) => root.unwrap_or(manifest),
+            Err(e) => {
+                warn!("Skipping manifest {}: {}", manifest.display(), e);
+                continue;
+            }
+        };
+        let root = root.canonicalize().unwrap_or(root);
+        if !result.contains(&root) {
+            result.push(root);
+        }
+    }
+    debug!("{} projects found under {}", result.len(), dir.as_ref().display());
+    result
+}
+
+fn collect_manifests(dir: &Path, result: &mut Vec<PathBuf>) {
+    let manifest = dir.join("Cargo.toml");
+    if manifest.is_file() {
+        result.push(manifest);
+    }
+    let mut subdirs: Vec<_> = match fs::read_dir(dir) {
+        Ok(entries) => entries.filter_map(Result::ok)
+            .filter(|e| e.file_type().map(|t| t.is_dir()).unwrap_or(false))
+            .map(|e| e.path())
+            .collect(),
+        Err(e) => {
+            warn!("Cannot read directory {}: {}", dir.display(), e);
+            return;
+        }
+    };
+    subdirs.sort();
+    for subdir in subdirs {
+        let name = subdir.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        if name.starts_with('.') || SKIPPED_DIRS.contains(&name) {
+            trace!("Skipping directory {}", subdir.display());
+            continue;
+        }
+        collect_manifests(&subdir, result);
+    }
+}
+
+/// Find the root manifest of the workspace that the crate with given manifest belongs to.
+///
+/// Like Cargo, this respects the `package.workspace` key if present,
+/// and otherwise looks for the nearest manifest with a [workspace] section
+/// in the parent directories. Returns None if the crate isn't part of any workspace.
+pub fn find_workspace_root<P: AsRef<Path>>(manifest_path: P) -> Result<Option<PathBuf>, Error> {
+    let path = manifest_path.as_ref();
+    let manifest = read_manifest(path)?;
+    if manifest.get("workspace").is_some() {
+        return Ok(Some(path.canonicalize()?));
+    }
+
+    let explicit_root = manifest.get("package")
+        .and_then(|p| p.get("workspace")).and_then(|w| w.as_str());
+    if let Some(root_dir) = explicit_root {
+        let root = manifest_dir(path).join(root_dir).canonicalize()?.join("Cargo.toml");
+        trace!("Workspace root of {} given explicitly as {}", path.display(), root.display());
+        return Ok(Some(root));
+    }
+
+    let start_dir = manifest_dir(path).canonicalize()?;
+    for dir in start_dir.ancestors().skip(1) {
+        let candidate = dir.join("Cargo.toml");
+        if !candidate.is_file() {
+            continue;
+        }
+        match read_manifest(&candidate) {
+            Ok(ref m) if m.get("workspace").is_some() => {
+                trace!("Found workspace root of {} at {}", path.display(), candidate.display());
+                return Ok(Some(candidate));
+            }
+            Ok(_) => {}
+            Err(e) => debug!("Skipping unreadable manifest {}: {}", candidate.display(), e),
+        }
+    }
+    Ok(None)
+}
+
+
+/// Dependencies from [workspace.dependencies] that member crates can inherit
+/// with `workspace = true`.
+///
+/// They are only loaded once the first inherited dependency is encountered.
+struct InheritedDependencies<'p> {
+    /// Path to the manifest of the member crate.
+    manifest_path: &'p Path,
+    /// Directory of the workspace root and its [workspace.dependencies].
+    workspace: Option<(PathBuf, toml::value::Table)>,
+}
+
+impl<'p> InheritedDependencies<'p> {
+    fn new(manifest_path: &'p Path) -> Self {
+        InheritedDependencies{manifest_path, workspace: None}
+    }
+
+    /// Resolve a member's dependency spec that has `workspace = true`
+    /// into the full dependency spec.
+    ///
+    /// Keys that the member is allowed to specify (`features`, `optional`, and `default-features`)
+    /// are merged with the inherited spec, while any others are ignored like Cargo does.
+    fn resolve(&mut self, name: &str, member_spec: &toml::value::Table) -> Result<Toml, Error> {
+        if self.workspace.is_none() {
+            self.workspace = Some(self.load()?);
+        }
+        let (ref root_dir, ref workspace_deps) = *self.workspace.as_ref().unwrap();
+
+        let mut spec = match workspace_deps.get(name) {
+            Some(&Toml::String(ref v)) => {
+                let mut t = toml::value::Table::new();
+                t.insert("version".into(), Toml::String(v.clone()));
+                t
+            }
+            Some(&Toml::Table(ref t)) => t.clone(),
+            Some(v) => return Err(Error::Toml(toml::de::Error::custom(format!(
+                "workspace dependency `{}` must be a string or a table, got {}",
+                name, v.type_str())))),
+            None => return Err(Error::Toml(toml::de::Error::custom(format!(
+                "dependency `{}` not found in [workspace.dependencies]", name)))),
+        };
+        // Paths in [workspace.dependencies] are relative to the workspace root.
+        let root_path = spec.get("path").and_then(|p| p.as_str()).map(|p| root_dir.join(p));
+        if let Some(path) = root_path {
+            spec.insert("path".into(), Toml::String(path.display().to_string()));
+        }
+
+        for (key, value) in member_spec {
+            match key.as_str() {
+                "workspace" => {}
+                "features" => {
+                    let mut features = match spec.remove("features") {
+                        Some(Toml::Array(a)) => a,
+                        _ => vec![],
+                    };
+                    if let Toml::Array(ref extra) = *value {
+                        features.extend(extra.iter().cloned());
+                    }
+                    spec.insert("features".into(), Toml::Array(features));
+                }
+                "optional" | "default-features" | "default_features" => {
+                    spec.insert(key.clone(), value.clone());
+                }
+                _ => warn!("Ignoring key `{}` of dependency `{}` inherited from workspace in {}",
+                    key, name, self.manifest_path.display()),
+            }
+        }
+        trace!("Dependency `{}` inherited from workspace as: {:?}", name, spec);
+        Ok(Toml::Table(spec))
+    }
+
+    /// Load [workspace.dependencies] from the workspace root.
+    fn load(&self) -> Result<(PathBuf, toml::value::Table), Error> {
+        let root = find_workspace_root(self.manifest_path)?.ok_or_else(|| {
+            Error::Toml(toml::de::Error::custom(format!(
+                "{} inherits dependencies but is not part of a workspace",
+                self.manifest_path.display())))
+        })?;
+        debug!("Reading [workspace.dependencies] from {}", root.display());
+
+        let manifest = read_manifest(&root)?;
+        let deps = match manifest.get("workspace").and_then(|w| w.get("dependencies")) {
+            Some(&Toml::Table(ref t)) => t.clone(),
+            Some(v) => return Err(Error::Toml(toml::de::Error::custom(format!(
+                "[workspace.dependencies] must be a table, got {}", v.type_str())))),
+            None => toml::value::Table::new(),
+        };
+        Ok((manifest_dir(&root).to_owned(), deps))
+    }
+}
+
+/// Whether given dependency spec is inheriting from the workspace (`workspace = true`).
+fn is_inherited(spec: &toml::value::Table) -> bool {
+    spec.get("workspace").and_then(|w| w.as_bool()).unwrap_or(false)
+}
+
+
 /// Error while reading Cargo.toml manifest.
 #[derive(Debug, Error)]
 pub enum Error {
@@ -64,9 +506,325 @@ pub enum Error {
 
 // Utility functions
 
+/// Read given manifest file as TOML.
+pub fn read_manifest(path: &Path) -> Result<Toml, Error> {
+    let content = read_file_to_string(path)?;
+    toml::from_str(&content).map_err(Error::Toml)
+}
+
+/// Return the directory containing given manifest file.
+pub fn manifest_dir(manifest_path: &Path) -> &Path {
+    match manifest_path.parent() {
+        Some(dir) if dir != Path::new("") => dir,
+        _ => Path::new("."),
+    }
+}
+
+/// Read an optional array of strings from given TOML table.
+fn read_string_array(table: &toml::value::Table, key: &str) -> Result<Vec<String>, Error> {
+    match table.get(key) {
+        None => Ok(vec![]),
+        Some(&Toml::Array(ref a)) => a.iter()
+            .map(|v| v.as_str().map(String::from).ok_or_else(|| {
+                Error::Toml(toml::de::Error::custom(format!(
+                    "`{}` must only contain strings, got {}", key, v.type_str())))
+            }))
+            .collect(),
+        Some(v) => Err(Error::Toml(toml::de::Error::custom(format!(
+            "`{}` must be an array, got {}", key, v.type_str())))),
+    }
+}
+
 fn read_file_to_string<P: AsRef<Path>>(path: P) -> io::Result<String> {
     let mut file = File::open(path)?;
     let mut content = String::with_capacity(1024);
     file.read_to_string(&mut content)?;
     Ok(content)
 }
+
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::path::{Component, Path, PathBuf};
+    use semver::VersionReq;
+    use model::{CrateLocation, DependencyKind};
+    use testing::create_temp_dir;
+    use super::{DependencyFilter, find_manifest, find_projects, find_workspace_root,
+                list_dependencies, list_workspace_members, read_repository_overrides};
+
+    #[test]
+    fn members_of_single_crate() {
+        let root = create_temp_dir(&[
+            ("Cargo.toml", "[package]\nname = \"foo\"\nversion = \"0.1.0\"\n"),
+        ]);
+        let members = list_workspace_members(root.join("Cargo.toml")).unwrap();
+        assert_eq!(vec![root.join("Cargo.toml")], members);
+    }
+
+    #[test]
+    fn members_of_virtual_workspace() {
+        let root = create_temp_dir(&[
+            ("Cargo.toml", "[workspace]\nmembers = [\"crates/*\"]\nexclude = [\"crates/old\"]\n"),
+            ("crates/foo/Cargo.toml", "[package]\nname = \"foo\"\nversion = \"0.1.0\"\n"),
+            ("crates/bar/Cargo.toml", "[package]\nname = \"bar\"\nversion = \"0.1.0\"\n"),
+            ("crates/old/Cargo.toml", "[package]\nname = \"old\"\nversion = \"0.1.0\"\n"),
+            ("crates/README.md", ""),
+        ]);
+        let mut members = list_workspace_members(root.join("Cargo.toml")).unwrap();
+        members.sort();
+        assert_eq!(vec![
+            root.join("crates/bar/Cargo.toml"),
+            root.join("crates/foo/Cargo.toml"),
+        ], members);
+    }
+
+    #[test]
+    fn members_of_workspace_with_root_package() {
+        let root = create_temp_dir(&[
+            ("Cargo.toml", concat!(
+                "[package]\nname = \"foo\"\nversion = \"0.1.0\"\n",
+                "[workspace]\nmembers = [\"foo-derive\"]\n")),
+            ("foo-derive/Cargo.toml", "[package]\nname = \"foo-derive\"\nversion = \"0.1.0\"\n"),
+        ]);
+        let members = list_workspace_members(root.join("Cargo.toml")).unwrap();
+        assert_eq!(vec![
+            root.join("Cargo.toml"),
+            root.join("foo-derive/Cargo.toml"),
+        ], members);
+    }
+
+    #[test]
+    fn repository_overrides() {
+        let root = create_temp_dir(&[
+            ("Cargo.toml", concat!(
+                "[package]\nname = \"foo\"\nversion = \"0.1.0\"\n",
+                "[package.metadata.contribute.repositories]\n",
+                "bar = \"gitlab:owner/bar\"\n",
+                "baz = \"none\"\n")),
+        ]);
+        let overrides = read_repository_overrides(root.join("Cargo.toml")).unwrap();
+        assert_eq!(2, overrides.len());
+        assert_eq!("gitlab:owner/bar", overrides["bar"]);
+        assert_eq!("none", overrides["baz"]);
+    }
+
+    #[test]
+    fn dependencies_of_selected_kinds() {
+        let root = create_temp_dir(&[
+            ("Cargo.toml", concat!(
+                "[package]\nname = \"foo\"\nversion = \"0.1.0\"\n",
+                "[dependencies]\nlog = \"0.4\"\n",
+                "[dev-dependencies]\nproptest = \"0.9\"\n",
+                "[build-dependencies]\nbindgen = \"0.51\"\n")),
+        ]);
+        let manifest_path = root.join("Cargo.toml");
+
+        let deps = list_dependencies(&manifest_path, &DependencyFilter::default()).unwrap();
+        let names: Vec<_> = deps.iter().map(|d| d.name()).collect();
+        assert_eq!(vec!["log"], names);
+
+        let filter = DependencyFilter{
+            kinds: vec![DependencyKind::Development, DependencyKind::Build],
+            ..DependencyFilter::default()
+        };
+        let deps = list_dependencies(&manifest_path, &filter).unwrap();
+        let names_and_kinds: Vec<_> = deps.iter().map(|d| (d.name(), d.kind())).collect();
+        assert_eq!(vec![
+            ("proptest", DependencyKind::Development),
+            ("bindgen", DependencyKind::Build),
+        ], names_and_kinds);
+    }
+
+    #[test]
+    fn dependencies_for_target() {
+        let root = create_temp_dir(&[
+            ("Cargo.toml", concat!(
+                "[package]\nname = \"foo\"\nversion = \"0.1.0\"\n",
+                "[target.'cfg(unix)'.dependencies]\nlibc = \"0.2\"\n",
+                "[target.'cfg(windows)'.dependencies]\nwinapi = \"0.3\"\n")),
+        ]);
+        let manifest_path = root.join("Cargo.toml");
+
+        let deps = list_dependencies(&manifest_path, &DependencyFilter::default()).unwrap();
+        assert_eq!(2, deps.len());
+
+        let filter = DependencyFilter{
+            target: Some("x86_64-unknown-linux-gnu".into()),
+            ..DependencyFilter::default()
+        };
+        let deps = list_dependencies(&manifest_path, &filter).unwrap();
+        assert_eq!(1, deps.len());
+        assert_eq!("libc", deps[0].name());
+        assert_eq!(Some("cfg(unix)"), deps[0].target());
+    }
+
+    #[test]
+    fn renamed_dependency() {
+        let root = create_temp_dir(&[
+            ("Cargo.toml", concat!(
+                "[package]\nname = \"foo\"\nversion = \"0.1.0\"\n",
+                "[dependencies]\n",
+                "futures01 = { package = \"futures\", version = \"0.1\" }\n",
+                "futures = \"0.3\"\n")),
+        ]);
+        let deps = list_dependencies(root.join("Cargo.toml"), &DependencyFilter::default())
+            .unwrap();
+        let names: Vec<_> = deps.iter().map(|d| (d.alias(), d.name())).collect();
+        assert_eq!(vec![("futures", "futures"), ("futures01", "futures")], names);
+        assert!(!deps[0].is_renamed());
+        assert!(deps[1].is_renamed());
+    }
+
+    #[test]
+    fn optional_dependencies() {
+        let root = create_temp_dir(&[
+            ("Cargo.toml", concat!(
+                "[package]\nname = \"foo\"\nversion = \"0.1.0\"\n",
+                "[features]\ndefault = [\"std\"]\nstd = [\"libc\"]\nparallel = [\"dep:rayon\"]\n",
+                "[dependencies]\n",
+                "libc = { version = \"0.2\", optional = true }\n",
+                "log = \"0.4\"\n",
+                "rayon = { version = \"1.0\", optional = true }\n")),
+        ]);
+        let manifest_path = root.join("Cargo.toml");
+        let deps = list_dependencies(&manifest_path, &DependencyFilter::default()).unwrap();
+        let deps: Vec<_> = deps.iter().map(|d| (d.name(), d.is_optional(), d.feature())).collect();
+        assert_eq!(vec![
+            ("libc", true, Some("std")), ("log", false, None), ("rayon", true, None),
+        ], deps);
+
+        let filter = DependencyFilter{only_enabled: true, ..DependencyFilter::default()};
+        let deps = list_dependencies(&manifest_path, &filter).unwrap();
+        let names: Vec<_> = deps.iter().map(|d| d.name()).collect();
+        assert_eq!(vec!["libc", "log"], names);
+
+        let filter = DependencyFilter{
+            features: vec!["foo/parallel".into()],
+            only_enabled: true,
+            ..DependencyFilter::default()
+        };
+        let deps = list_dependencies(&manifest_path, &filter).unwrap();
+        assert_eq!(Some("parallel"), deps[2].feature());
+    }
+
+    #[test]
+    fn inherited_dependencies() {
+        let root = create_temp_dir(&[
+            ("Cargo.toml", concat!(
+                "[workspace]\nmembers = [\"crates/*\"]\n",
+                "[workspace.dependencies]\n",
+                "log = \"0.4\"\n",
+                "serde = { version = \"1.0\", features = [\"std\"] }\n",
+                "common = { path = \"crates/common\" }\n")),
+            ("crates/common/Cargo.toml", "[package]\nname = \"common\"\nversion = \"0.1.0\"\n"),
+            ("crates/app/Cargo.toml", concat!(
+                "[package]\nname = \"app\"\nversion = \"0.1.0\"\n",
+                "[dependencies]\n",
+                "log = { workspace = true, version = \"0.3\" }\n",
+                "serde = { workspace = true, features = [\"derive\"], optional = true }\n",
+                "common = { workspace = true }\n")),
+        ]);
+        let deps = list_dependencies(root.join("crates/app/Cargo.toml"), &DependencyFilter::default())
+            .unwrap();
+        let deps: Vec<_> = deps.iter().map(|d| (d.name(), d.location())).collect();
+        assert_eq!(3, deps.len());
+        match deps[0] {
+            ("common", &CrateLocation::Filesystem{ref path}) =>
+                assert_eq!(root.join("crates/common"), *path),
+            ref d => panic!("unexpected dependency: {:?}", d),
+        }
+        // Members cannot override the version of an inherited dependency.
+        match deps[1] {
+            ("log", &CrateLocation::Registry{ref version, ..}) =>
+                assert_eq!(VersionReq::parse("0.4").unwrap(), *version),
+            ref d => panic!("unexpected dependency: {:?}", d),
+        }
+        match deps[2] {
+            ("serde", &CrateLocation::Registry{ref version, ..}) =>
+                assert_eq!(VersionReq::parse("1.0").unwrap(), *version),
+            ref d => panic!("unexpected dependency: {:?}", d),
+        }
+    }
+
+    #[test]
+    fn inherited_dependency_outside_workspace() {
+        let root = create_temp_dir(&[
+            ("Cargo.toml", concat!(
+                "[package]\nname = \"foo\"\nversion = \"0.1.0\"\n",
+                "[dependencies]\nlog = { workspace = true }\n")),
+        ]);
+        assert!(list_dependencies(root.join("Cargo.toml"), &DependencyFilter::default()).is_err());
+    }
+
+    #[test]
+    fn inherited_path_dependency_with_relative_manifest_path() {
+        let root = create_temp_dir(&[
+            ("Cargo.toml", concat!(
+                "[package]\nname = \"app\"\nversion = \"0.1.0\"\n",
+                "[dependencies]\ncommon = { workspace = true }\n",
+                "[workspace]\nmembers = [\"crates/*\"]\n",
+                "[workspace.dependencies]\ncommon = { path = \"crates/common\" }\n")),
+            ("crates/common/Cargo.toml", "[package]\nname = \"common\"\nversion = \"0.1.0\"\n"),
+            ("crates/tool/Cargo.toml", concat!(
+                "[package]\nname = \"tool\"\nversion = \"0.1.0\"\nworkspace = \"../..\"\n",
+                "[dependencies]\ncommon = { workspace = true }\n")),
+        ]);
+        for member in &["Cargo.toml", "crates/tool/Cargo.toml"] {
+            let manifest_path = relative_to_cwd(&root.join(member));
+            assert!(manifest_path.is_relative());
+            assert_eq!(Some(root.join("Cargo.toml")),
+                find_workspace_root(&manifest_path).unwrap());
+            let deps = list_dependencies(&manifest_path, &DependencyFilter::default()).unwrap();
+            match *deps[0].location() {
+                CrateLocation::Filesystem{ref path} =>
+                    assert_eq!(root.join("crates/common"), *path),
+                ref l => panic!("unexpected location: {:?}", l),
+            }
+        }
+    }
+
+    #[test]
+    fn manifest_in_parent_directory() {
+        let root = create_temp_dir(&[
+            ("Cargo.toml", "[workspace]\nmembers = [\"crates/*\"]\n"),
+            ("crates/app/Cargo.toml", "[package]\nname = \"app\"\nversion = \"0.1.0\"\n"),
+            ("crates/app/src/bin/main.rs", "fn main() {}\n"),
+        ]);
+        let manifest = find_manifest(root.join("crates/app/src/bin")).unwrap();
+        assert_eq!(root.join("crates/app/Cargo.toml"), manifest);
+        assert_eq!(Some(root.join("Cargo.toml")), find_workspace_root(&manifest).unwrap());
+        assert_eq!(Some(root.join("Cargo.toml")), find_manifest(root.join("crates")));
+    }
+
+    #[test]
+    fn projects_in_directory_tree() {
+        let manifest = |name| format!("[package]\nname = \"{}\"\nversion = \"0.1.0\"\n", name);
+        let root = create_temp_dir(&[
+            ("app/Cargo.toml", &manifest("app")),
+            ("app/target/package/app-0.1.0/Cargo.toml", &manifest("app")),
+            ("app/vendor/log/Cargo.toml", &manifest("log")),
+            ("libs/Cargo.toml", "[workspace]\nmembers = [\"a\", \"b\"]\n"),
+            ("libs/a/Cargo.toml", &manifest("a")),
+            ("libs/b/Cargo.toml", &manifest("b")),
+            ("tool/.git/Cargo.toml", &manifest("git")),
+            ("tool/Cargo.toml", &manifest("tool")),
+        ]);
+        let projects = find_projects(&root);
+        assert_eq!(vec![
+            root.join("app/Cargo.toml"),
+            root.join("libs/Cargo.toml"),
+            root.join("tool/Cargo.toml"),
+        ], projects);
+    }
+
+    /// Express given absolute path relative to the current directory.
+    fn relative_to_cwd(path: &Path) -> PathBuf {
+        let is_normal = |c: &Component| match *c { Component::Normal(_) => true, _ => false };
+        let cwd = env::current_dir().unwrap();
+        let mut result: PathBuf = cwd.components().filter(&is_normal)
+            .map(|_| Component::ParentDir).collect();
+        result.extend(path.components().filter(&is_normal));
+        result
+    }
+}
@@ -1,20 +1,36 @@
-//! Module for communicating with crates.io API.
+//! Module for communicating with crates.io API,
+//! or the compatible API of an alternative registry.
 
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::rc::Rc;
 use std::str::FromStr;
 
 use chrono::{DateTime, Utc};
 use futures::{future, Future as StdFuture};
+use futures::future::Shared;
 use hyper::{self, StatusCode, Uri};
 use hyper::client::{Connect, HttpConnector};
+use semver::{Version, VersionReq};
+use serde::de::DeserializeOwned;
 use serde_json;
 use tokio_core::reactor::Handle;
 
 use ext::futures::{BoxFuture, FutureExt};
 use ext::hyper::BodyExt;
+use model::{Dependency, DependencyKind};
 use util::{HttpsConnector, https_client};
+use super::cargo_config::CRATES_IO_INDEX;
 
 
-const API_ROOT: &str = "https://crates.io/api/v1/";
+const CRATES_IO_API_ROOT: &str = "https://crates.io/api/v1/";
+
+/// Prefix of the URLs of sparse registry indices.
+const SPARSE_INDEX_PREFIX: &str = "sparse+";
+
+/// Number of reverse dependencies to request per page (which is also the maximum).
+const REVERSE_DEPS_PER_PAGE: usize = 100;
 
 
 /// Structure holding information about a single crate.
@@ -22,6 +38,22 @@ const API_ROOT: &str = "https://crates.io/api/v1/";
 pub struct Crate {
     #[serde(rename = "crate")]
     pub metadata: Metadata,
+    /// All the published versions of the crate.
+    #[serde(default)]
+    pub versions: Vec<CrateVersion>,
+}
+
+impl Crate {
+    /// Find the newest version of the crate that matches given requirement
+    /// and hasn't been yanked.
+    pub fn find_version(&self, req: &VersionReq) -> Option<&CrateVersion> {
+        self.versions.iter()
+            .filter(|v| !v.yanked)
+            .filter_map(|v| Version::parse(&v.num).ok().map(|num| (num, v)))
+            .filter(|&(ref num, _)| req.matches(num))
+            .max_by(|&(ref a, _), &(ref b, _)| a.cmp(b))
+            .map(|(_, v)| v)
+    }
 }
 
 /// Basic crate metadata.
@@ -61,10 +93,121 @@ pub struct Metadata {
 }
 
 
+/// Single published version of a crate.
+#[derive(Debug, Deserialize)]
+pub struct CrateVersion {
+    /// The version number.
+    pub num: String,
+    #[serde(default)]
+    pub yanked: bool,
+    /// Features of this version of the crate, as declared in its manifest.
+    #[serde(default)]
+    pub features: BTreeMap<String, Vec<String>>,
+}
+
+/// Dependencies of a particular version of a crate.
+#[derive(Debug, Deserialize)]
+struct CrateDependencies {
+    dependencies: Vec<CrateDependency>,
+}
+
+/// Dependency of a particular version of a crate.
+#[derive(Debug, Deserialize)]
+pub struct CrateDependency {
+    /// Name of the crate that is depended on.
+    pub crate_id: String,
+    /// Version requirement of the dependency.
+    pub req: String,
+    /// Kind of the dependency: "normal", "dev", or "build".
+    pub kind: String,
+    #[serde(default)]
+    pub optional: bool,
+    /// Platform that the dependency is specific to, if any.
+    #[serde(default)]
+    pub target: Option<String>,
+}
+
+impl CrateDependency {
+    /// Convert to the `Dependency` on a crate from the same registry.
+    /// Returns None if the dependency is invalid.
+    pub fn to_dependency(&self) -> Option<Dependency> {
+        let kind: DependencyKind = self.kind.parse().ok()?;
+        VersionReq::parse(&self.req).ok()?;
+        let dep = Dependency::with_version(&self.crate_id, &self.req)
+            .with_kind(kind)
+            .with_optional(self.optional);
+        Some(match self.target {
+            Some(ref target) => dep.with_target(target),
+            None => dep,
+        })
+    }
+}
+
+
+/// Single page of crates which depend on a particular crate.
+#[derive(Debug)]
+pub struct ReverseDependencies {
+    /// Names of the dependent crates.
+    pub crates: Vec<String>,
+    /// Total number of the dependencies on the crate, across all pages.
+    pub total: usize,
+    /// Number of the next page, if there are any more.
+    pub next_page: Option<usize>,
+}
+
+/// Response of the reverse dependencies endpoint.
+#[derive(Debug, Deserialize)]
+struct ReverseDependenciesResponse {
+    /// Versions of the dependent crates, one for each dependency.
+    versions: Vec<DependentVersion>,
+    meta: ReverseDependenciesMeta,
+}
+
+#[derive(Debug, Deserialize)]
+struct DependentVersion {
+    #[serde(rename = "crate")]
+    crate_: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ReverseDependenciesMeta {
+    total: usize,
+}
+
+impl ReverseDependenciesResponse {
+    fn into_page(self, page: usize) -> ReverseDependencies {
+        let has_more = !self.versions.is_empty() && page * REVERSE_DEPS_PER_PAGE < self.meta.total;
+        // A crate may depend on ours more than once (e.g. as both normal & dev-dependency).
+        let mut crates: Vec<String> = vec![];
+        for version in self.versions {
+            if !crates.contains(&version.crate_) {
+                crates.push(version.crate_);
+            }
+        }
+        ReverseDependencies{
+            crates,
+            total: self.meta.total,
+            next_page: if has_more { Some(page + 1) } else { None },
+        }
+    }
+}
+
+
+/// Configuration of a registry, as stored in the config.json file at its index root.
+#[derive(Debug, Deserialize)]
+struct IndexConfig {
+    /// Base URL of the registry's web API.
+    #[serde(default)]
+    api: Option<String>,
+}
+
+
 /// Client for the crates.io API.
 #[derive(Clone, Debug)]
 pub struct Client<C: Clone> {
     http: hyper::Client<C>,
+    /// Root URL of the API, including the /api/v1/ part.
+    api_root: String,
 }
 
 impl Client<HttpConnector> {
@@ -82,35 +225,234 @@ impl Client<HttpsConnector> {
 impl<C: Clone> Client<C> {
     #[inline]
     pub fn with_http(http: hyper::Client<C>) -> Self {
-        Client{http}
+        Client::with_api_root(http, CRATES_IO_API_ROOT)
+    }
+
+    /// Create a client for a crates.io-compatible API under given root URL
+    /// (e.g. "https://crates.io/api/v1/").
+    #[inline]
+    pub fn with_api_root<R: ToString>(http: hyper::Client<C>, api_root: R) -> Self {
+        Client{http, api_root: api_root.to_string()}
+    }
+
+    /// Whether this client is talking to crates.io (rather than an alternative registry).
+    #[inline]
+    pub fn is_crates_io(&self) -> bool {
+        self.api_root == CRATES_IO_API_ROOT
     }
 }
 
 impl<C: Clone + Connect> Client<C> {
+    /// Create a client for the registry with given index URL.
+    ///
+    /// The API root is read from the config.json file of the index,
+    /// which is only possible for sparse indices (i.e. ones with a "sparse+" URL).
+    /// Returns None if the registry doesn't have an API.
+    pub fn for_index<I: AsRef<str>>(http: hyper::Client<C>, index: I) -> Future<Option<Self>> {
+        let index = index.as_ref();
+        if !index.starts_with(SPARSE_INDEX_PREFIX) {
+            warn!(concat!("Cannot determine API of registry with index {}: ",
+                "only sparse indices are supported"), index);
+            return future::ok(None).into_box();
+        }
+        let index = index[SPARSE_INDEX_PREFIX.len()..].trim_end_matches('/').to_owned();
+        let url = match Uri::from_str(&format!("{}/config.json", index)) {
+            Ok(url) => url,
+            Err(e) => {
+                warn!("Invalid registry index URL {}: {}", index, e);
+                return future::ok(None).into_box();
+            }
+        };
+        trace!("Fetching registry config from {}", url);
+        http.get(url).map_err(Error::Http).and_then(move |resp| {
+            let status = resp.status();
+            if !status.is_success() {
+                warn!("Cannot fetch config of registry {}: unexpected response code {}",
+                    index, status);
+                return future::ok(None).into_box();
+            }
+            resp.body().into_bytes().map_err(Error::Http).and_then(move |bytes| {
+                let config: IndexConfig = serde_json::from_reader(&bytes[..])?;
+                Ok(config.api.map(|api| {
+                    let api_root = format!("{}/api/v1/", api.trim_end_matches('/'));
+                    debug!("API of registry {} found at {}", index, api_root);
+                    Client::with_api_root(http, api_root)
+                }).or_else(|| {
+                    warn!("Registry {} doesn't provide an API", index); None
+                }))
+            }).into_box()
+        }).into_box()
+    }
+
     /// Lookup a crate by name, returning its metadata.
     /// Returns None if the crate couldn't be found
     pub fn lookup_crate(&self, id: String) -> Future<Option<Crate>> {
-        trace!("Looking up crate `{}` on crates.io...", id);
-        let url = Uri::from_str(&format!("{}/crates/{}", API_ROOT, id)).unwrap();
+        trace!("Looking up crate `{}` on {}...", id, self.registry_name());
+        self.fetch(format!("crates/{}", id), format!("crate `{}`", id))
+    }
+
+    /// Lookup the dependencies of given version of a crate.
+    /// Returns None if the crate or its version couldn't be found.
+    pub fn lookup_dependencies(
+        &self, id: String, version: String
+    ) -> Future<Option<Vec<CrateDependency>>> {
+        trace!("Looking up dependencies of crate `{}={}` on {}...",
+            id, version, self.registry_name());
+        let path = format!("crates/{}/{}/dependencies", id, version);
+        self.fetch(path, format!("crate `{}={}`", id, version))
+            .map(|deps: Option<CrateDependencies>| deps.map(|d| d.dependencies))
+            .into_box()
+    }
+
+    /// Lookup a page of crates that depend on given crate, starting from page 1.
+    /// Returns None if the crate couldn't be found.
+    ///
+    /// Dependent crates are ordered by their number of downloads, most popular first.
+    pub fn lookup_reverse_dependencies(
+        &self, id: String, page: usize
+    ) -> Future<Option<ReverseDependencies>> {
+        trace!("Looking up reverse dependencies of crate `{}` on {} (page {})...",
+            id, self.registry_name(), page);
+        let path = format!("crates/{}/reverse_dependencies?page={}&per_page={}",
+            id, page, REVERSE_DEPS_PER_PAGE);
+        self.fetch(path, format!("crate `{}`", id))
+            .map(move |resp: Option<ReverseDependenciesResponse>| resp.map(|r| r.into_page(page)))
+            .into_box()
+    }
+
+    /// Fetch a resource under given path of the API, returning None if it doesn't exist.
+    fn fetch<T>(&self, path: String, what: String) -> Future<Option<T>>
+        where T: DeserializeOwned + 'static
+    {
+        let registry = self.registry_name();
+        let url = format!("{}/{}", self.api_root.trim_end_matches('/'), path);
+        let url = match Uri::from_str(&url) {
+            Ok(url) => url,
+            Err(e) => return future::err(Error::Http(e.into())).into_box(),
+        };
         self.http.get(url).map_err(Error::Http).and_then(move |resp| {
             let status = resp.status();
             if status.is_success() {
-                debug!("Successful response from crates.io for `{}`", id);
+                debug!("Successful response from {} for {}", registry, what);
                 resp.body().into_bytes().map_err(Error::Http)
                     .and_then(|bytes| {
                         serde_json::from_reader(&bytes[..]).map(Some).map_err(Error::Json)
                     }).into_box()
             } else if status == StatusCode::NotFound {
-                warn!("Crate `{}` not found on crates.io", id);
+                warn!("{} not found on {}", what, registry);
                 future::ok(None).into_box()
             } else {
                 error!(
-                    "Unexpected response code from crates.io while looking up crate `{}`: {}",
-                    id, status);
+                    "Unexpected response code from {} while looking up {}: {}",
+                    registry, what, status);
                 future::err(Error::Http(hyper::Error::Status)).into_box()
             }
         }).into_box()
     }
+
+    /// Human-readable name of the registry, for logging purposes.
+    fn registry_name(&self) -> String {
+        if self.is_crates_io() {
+            "crates.io".into()
+        } else {
+            self.api_root.clone()
+        }
+    }
+}
+
+
+/// Clients for all the registries that dependencies may come from:
+/// crates.io, as well as any alternative registries.
+#[derive(Clone)]
+pub struct Registries<C: Clone> {
+    crates_io: Client<C>,
+    /// Index URLs of alternative registries, by their names.
+    indices: HashMap<String, String>,
+    /// Clients of alternative registries by their index URLs, shared between all the lookups
+    /// (and clones of this object) so that the config of each registry is only fetched once.
+    clients: Rc<RefCell<HashMap<String, RegistryClient<C>>>>,
+}
+
+/// Future resolving to the client of an alternative registry (if it has an API).
+type RegistryClient<C> = Shared<BoxFuture<'static, Option<Client<C>>, ()>>;
+
+impl<C: Clone> Registries<C> {
+    #[inline]
+    pub fn new(crates_io: Client<C>) -> Self {
+        Registries{
+            crates_io,
+            indices: HashMap::new(),
+            clients: Rc::new(RefCell::new(HashMap::new())),
+        }
+    }
+
+    /// Make an alternative registry with given name and index URL known.
+    #[inline]
+    pub fn add<N: ToString, I: ToString>(&mut self, name: N, index: I) {
+        self.indices.insert(name.to_string(), index.to_string());
+    }
+
+    /// Return the index URL of given registry (or crates.io if None), if known.
+    ///
+    /// Registries that aren't known by name can also be referred to by their index URL.
+    pub fn index<'a>(&'a self, registry: Option<&'a str>) -> Option<&'a str> {
+        match registry {
+            None => Some(CRATES_IO_INDEX),
+            Some(r) => self.indices.get(r).map(|i| i.as_str())
+                .or_else(|| if r.contains("://") { Some(r) } else { None }),
+        }
+    }
+}
+
+impl<C: Clone + Connect> Registries<C> {
+    /// Lookup a crate by name in given registry (or crates.io if None),
+    /// returning its metadata.
+    pub fn lookup_crate(&self, registry: Option<&str>, id: String) -> Future<Option<Crate>> {
+        let registry = match registry {
+            Some(r) => r,
+            None => return self.crates_io.lookup_crate(id),
+        };
+        match self.index(Some(registry)) {
+            Some(index) => {
+                // Errors of the client have already been logged, and they mean the same
+                // as the registry not having an API: its crates cannot be looked up.
+                self.client_for_index(index).then(move |client| {
+                    match client.ok().and_then(|c| (*c).clone()) {
+                        Some(c) => c.lookup_crate(id),
+                        None => future::ok(None).into_box(),
+                    }
+                }).into_box()
+            }
+            None => {
+                warn!("Unknown registry `{}` of crate `{}`; is it listed in .cargo/config.toml?",
+                    registry, id);
+                future::ok(None).into_box()
+            }
+        }
+    }
+
+    /// Return the client of the alternative registry with given index URL,
+    /// creating it the first time the registry is asked for.
+    fn client_for_index(&self, index: &str) -> RegistryClient<C> {
+        let mut clients = self.clients.borrow_mut();
+        let http = self.crates_io.http.clone();
+        clients.entry(index.to_owned()).or_insert_with(|| {
+            let index = index.to_owned();
+            Client::for_index(http, index.clone()).or_else(move |e| {
+                warn!("Cannot use registry with index {}: {}", index, e);
+                Ok(None)
+            }).into_box().shared()
+        }).clone()
+    }
+}
+
+impl<C: Clone + fmt::Debug> fmt::Debug for Registries<C> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Registries")
+            .field("crates_io", &self.crates_io)
+            .field("indices", &self.indices)
+            .finish()
+    }
 }
 
 
@@ -125,3 +467,129 @@ pub enum Error {
     /// JSON error.
     Json(serde_json::Error),
 }
+
+
+#[cfg(test)]
+mod tests {
+    use tokio_core::reactor::Core;
+    use testing::MockServer;
+    use semver::VersionReq;
+    use super::{Client, Registries};
+
+    const CRATE_JSON: &str = r#"{"crate": {
+        "id": "foo", "name": "foo", "description": "Internal crate",
+        "created_at": "2018-01-01T00:00:00Z", "updated_at": "2018-01-02T00:00:00Z",
+        "repository": "https://github.com/company/foo"
+    }}"#;
+
+    #[test]
+    fn lookup_crate_in_alternative_registry() {
+        let server = MockServer::start(vec![
+            ("/index/config.json", r#"{"dl": "{server_url}/dl", "api": "{server_url}/"}"#),
+            ("/api/v1/crates/foo", CRATE_JSON),
+        ]);
+        let mut core = Core::new().unwrap();
+        let mut registries = Registries::new(Client::new(&core.handle()));
+        registries.add("company", format!("sparse+{}/index/", server.url()));
+
+        let crate_ = core.run(registries.lookup_crate(Some("company"), "foo".into())).unwrap();
+        assert_eq!(Some("https://github.com/company/foo"),
+            crate_.and_then(|c| c.metadata.repo_url).as_ref().map(|u| u.as_str()));
+        let missing = core.run(registries.lookup_crate(Some("company"), "bar".into())).unwrap();
+        assert!(missing.is_none());
+
+        // The registry's config is only fetched once, including by clones of the registries.
+        let registries = registries.clone();
+        core.run(registries.lookup_crate(Some("company"), "foo".into())).unwrap();
+        assert_eq!(1, server.request_count("/index/config.json"));
+        assert_eq!(2, server.request_count("/api/v1/crates/foo"));
+    }
+
+    #[test]
+    fn crate_versions_and_dependencies() {
+        let server = MockServer::start(vec![
+            ("/api/v1/crates/foo", r#"{
+                "crate": {
+                    "id": "foo", "name": "foo", "description": "",
+                    "created_at": "2018-01-01T00:00:00Z", "updated_at": "2018-01-02T00:00:00Z"
+                },
+                "versions": [
+                    {"num": "2.0.0", "yanked": true},
+                    {"num": "1.2.0", "yanked": false, "features": {"default": ["std"]}},
+                    {"num": "1.1.0", "yanked": false}
+                ]
+            }"#),
+            ("/api/v1/crates/foo/1.2.0/dependencies", r#"{"dependencies": [
+                {"crate_id": "log", "req": "^0.4", "kind": "normal", "optional": false},
+                {"crate_id": "libc", "req": "^0.2", "kind": "normal", "optional": true,
+                 "target": "cfg(unix)"},
+                {"crate_id": "proptest", "req": "^0.9", "kind": "dev", "optional": false}
+            ]}"#),
+        ]);
+        let mut core = Core::new().unwrap();
+        let client = Client::with_api_root(
+            Client::new(&core.handle()).http, format!("{}/api/v1/", server.url()));
+
+        let crate_ = core.run(client.lookup_crate("foo".into())).unwrap().unwrap();
+        let version = crate_.find_version(&VersionReq::any()).unwrap();
+        assert_eq!("1.2.0", version.num);
+        assert_eq!(1, version.features.len());
+        assert!(crate_.find_version(&VersionReq::parse("=1.1.0").unwrap()).is_some());
+        assert!(crate_.find_version(&VersionReq::parse("2").unwrap()).is_none());
+
+        let deps = core.run(client.lookup_dependencies("foo".into(), "1.2.0".into()))
+            .unwrap().unwrap();
+        let deps: Vec<_> = deps.iter().filter_map(|d| d.to_dependency()).collect();
+        assert_eq!(3, deps.len());
+        assert!(deps[1].is_optional());
+        assert_eq!(Some("cfg(unix)"), deps[1].target());
+        assert_eq!("dev", deps[2].kind().as_str());
+    }
+
+    #[test]
+    fn reverse_dependencies() {
+        let server = MockServer::start(vec![
+            ("/api/v1/crates/foo/reverse_dependencies?page=1&per_page=100", r#"{
+                "dependencies": [
+                    {"crate_id": "foo", "req": "^1", "kind": "normal", "optional": false},
+                    {"crate_id": "foo", "req": "^1", "kind": "dev", "optional": false},
+                    {"crate_id": "foo", "req": "^1", "kind": "normal", "optional": true}
+                ],
+                "versions": [
+                    {"crate": "bar", "num": "0.3.0"},
+                    {"crate": "bar", "num": "0.3.0"},
+                    {"crate": "baz", "num": "2.1.0"}
+                ],
+                "meta": {"total": 150}
+            }"#),
+        ]);
+        let mut core = Core::new().unwrap();
+        let client = Client::with_api_root(
+            Client::new(&core.handle()).http, format!("{}/api/v1/", server.url()));
+
+        let rev_deps = core.run(client.lookup_reverse_dependencies("foo".into(), 1))
+            .unwrap().unwrap();
+        assert_eq!(vec!["bar", "baz"], rev_deps.crates);
+        assert_eq!(Some(2), rev_deps.next_page);
+        let missing = core.run(client.lookup_reverse_dependencies("qux".into(), 1)).unwrap();
+        assert!(missing.is_none());
+    }
+
+    #[test]
+    fn lookup_crate_in_unknown_registry() {
+        let mut core = Core::new().unwrap();
+        let registries = Registries::new(Client::new(&core.handle()));
+        let crate_ = core.run(registries.lookup_crate(Some("unknown"), "foo".into())).unwrap();
+        assert!(crate_.is_none());
+    }
+
+    #[test]
+    fn lookup_crate_in_unreachable_registry() {
+        let server = MockServer::start(Vec::<(&str, &str)>::new());
+        let mut core = Core::new().unwrap();
+        let mut registries = Registries::new(Client::new(&core.handle()));
+        registries.add("company", format!("sparse+{}/index/", server.url()));
+        let crate_ = core.run(registries.lookup_crate(Some("company"), "foo".into())).unwrap();
+        assert!(crate_.is_none());
+    }
+}
@@ -0,0 +1,116 @@
+//! Module for resolving which optional dependencies are enabled by crate features.
+
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+
+
+/// Name of the feature that is enabled unless requested otherwise.
+const DEFAULT_FEATURE: &str = "default";
+
+/// Prefix of [features] entries that refer to optional dependencies explicitly.
+const DEP_PREFIX: &str = "dep:";
+
+
+/// Determine which optional dependencies of a crate are enabled
+/// by its default features together with the requested ones.
+///
+/// Requested features can be qualified with the crate's package name (`$PACKAGE/$FEATURE`),
+/// and any that refer to other packages are interpreted like entries of [features].
+///
+/// Returns a map from names of the enabled dependencies (as used in the manifest)
+/// to the features which directly enable them.
+pub fn enabled_dependencies<'d, I, S>(
+    package: &str, features: &BTreeMap<String, Vec<String>>,
+    optional_deps: &HashSet<&'d str>, requested: I,
+) -> HashMap<String, String>
+    where I: IntoIterator<Item=S>, S: AsRef<str>
+{
+    let mut queue: VecDeque<(String, String)> = VecDeque::new();
+    queue.push_back((DEFAULT_FEATURE.to_owned(), DEFAULT_FEATURE.to_owned()));
+    for feature in requested {
+        let feature = feature.as_ref();
+        let prefix = format!("{}/", package);
+        let feature = if feature.starts_with(&prefix) { &feature[prefix.len()..] } else { feature };
+        queue.push_back((feature.to_owned(), feature.to_owned()));
+    }
+
+    let mut result = HashMap::new();
+    let mut visited = HashSet::new();
+    while let Some((entry, enabled_by)) = queue.pop_front() {
+        // Entries can refer to a dependency explicitly (dep:$NAME),
+        // enable its feature ($NAME/$FEATURE, which also enables the dependency itself),
+        // or do that weakly ($NAME?/$FEATURE, which doesn't).
+        let dep = if entry.starts_with(DEP_PREFIX) {
+            Some(&entry[DEP_PREFIX.len()..])
+        } else if let Some(idx) = entry.find('/') {
+            let name = &entry[..idx];
+            if name.ends_with('?') { None } else { Some(name) }
+        } else {
+            if !visited.insert(entry.clone()) {
+                continue;
+            }
+            match features.get(&entry) {
+                Some(entries) => {
+                    for e in entries {
+                        queue.push_back((e.clone(), entry.clone()));
+                    }
+                    None
+                }
+                // Optional dependencies also act as implicit features.
+                None => Some(entry.as_str()),
+            }
+        };
+        match dep {
+            Some(name) if optional_deps.contains(name) => {
+                if !result.contains_key(name) {
+                    trace!("Optional dependency `{}` enabled by feature `{}`", name, enabled_by);
+                    result.insert(name.to_owned(), enabled_by.clone());
+                }
+            }
+            Some(name) if name != DEFAULT_FEATURE => {
+                trace!("Unknown feature or optional dependency `{}` of {}", name, package);
+            }
+            _ => {}
+        }
+    }
+    result
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, HashSet};
+    use super::enabled_dependencies;
+
+    fn features(entries: &[(&str, &[&str])]) -> BTreeMap<String, Vec<String>> {
+        entries.iter()
+            .map(|&(f, es)| (f.to_owned(), es.iter().map(|e| e.to_string()).collect()))
+            .collect()
+    }
+
+    #[test]
+    fn default_features() {
+        let features = features(&[
+            ("default", &["std"]),
+            ("std", &["dep:libc", "serde?/std"]),
+            ("serialization", &["serde"]),
+        ]);
+        let optional: HashSet<_> = vec!["libc", "serde", "rayon"].into_iter().collect();
+        let enabled = enabled_dependencies("app", &features, &optional, &[] as &[&str]);
+        assert_eq!(1, enabled.len());
+        assert_eq!(Some(&"std".to_owned()), enabled.get("libc"));
+    }
+
+    #[test]
+    fn requested_features() {
+        let features = features(&[
+            ("serialization", &["serde", "chrono/serde"]),
+        ]);
+        let optional: HashSet<_> = vec!["serde", "chrono", "rayon"].into_iter().collect();
+        let enabled = enabled_dependencies(
+            "app", &features, &optional, &["app/serialization", "rayon"]);
+        assert_eq!(3, enabled.len());
+        assert_eq!(Some(&"serialization".to_owned()), enabled.get("serde"));
+        assert_eq!(Some(&"serialization".to_owned()), enabled.get("chrono"));
+        assert_eq!(Some(&"rayon".to_owned()), enabled.get("rayon"));
+    }
+}
@@ -0,0 +1,134 @@
+//! Module implementing the include/exclude filters for dependencies and their repositories.
+
+use glob::{MatchOptions, Pattern, PatternError};
+
+use model::Repository;
+
+
+/// Filter on the names of crates and the repositories they come from.
+///
+/// Each pattern is a glob which applies to crate names,
+/// or to repositories (as `owner/repo`) if it contains a slash.
+#[derive(Clone, Debug, Default)]
+pub struct NameFilter {
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+}
+
+impl NameFilter {
+    /// Create a filter from given lists of include & exclude patterns.
+    ///
+    /// If there are any include patterns for crates (or repositories),
+    /// only the crates (or repositories) matching one of them are accepted.
+    /// Anything that matches an exclude pattern is rejected.
+    pub fn new<I, E>(include: I, exclude: E) -> Result<Self, PatternError>
+        where I: IntoIterator, I::Item: AsRef<str>, E: IntoIterator, E::Item: AsRef<str>
+    {
+        let include: Result<Vec<_>, _> = include.into_iter()
+            .map(|p| Pattern::new(p.as_ref())).collect();
+        let exclude: Result<Vec<_>, _> = exclude.into_iter()
+            .map(|p| Pattern::new(p.as_ref())).collect();
+        Ok(NameFilter{include: include?, exclude: exclude?})
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+}
+
+impl NameFilter {
+    /// Whether given crate should be looked at.
+    pub fn accepts_crate(&self, name: &str) -> bool {
+        // Crate names are case-sensitive.
+        self.accepts(&[name], false, &MatchOptions::new())
+    }
+
+    /// Whether issues from given repository should be suggested.
+    ///
+    /// The patterns are matched against `owner/repo` regardless of the forge,
+    /// as well as against the repository's prefixed form (e.g. `gitlab:owner/repo`).
+    pub fn accepts_repo(&self, repo: &Repository) -> bool {
+        // Owner & repo names on the code hosting sites are not case-sensitive.
+        let options = MatchOptions{case_sensitive: false, ..MatchOptions::new()};
+        let path = format!("{}/{}", repo.owner, repo.name);
+        let display = repo.to_string();
+        self.accepts(&[display.as_str(), path.as_str()], true, &options)
+    }
+
+    /// Whether an item known under given names passes the filter.
+    /// The first name is the one reported in the logs.
+    fn accepts(&self, names: &[&str], is_repo: bool, options: &MatchOptions) -> bool {
+        let applies = |p: &&Pattern| is_repo_pattern(p) == is_repo;
+        let matches = |p: &&Pattern| names.iter().any(|n| p.matches_with(n, options));
+        let mut include = self.include.iter().filter(applies).peekable();
+        if include.peek().is_some() && !include.any(|p| matches(&p)) {
+            trace!("`{}` doesn't match any of the include patterns", names[0]);
+            return false;
+        }
+        if let Some(p) = self.exclude.iter().filter(applies).find(matches) {
+            trace!("`{}` is excluded by pattern `{}`", names[0], p);
+            return false;
+        }
+        true
+    }
+}
+
+/// Whether given pattern applies to repositories rather than crates.
+fn is_repo_pattern(pattern: &Pattern) -> bool {
+    pattern.as_str().contains('/')
+}
+
+
+#[cfg(test)]
+mod tests {
+    use model::{Forge, Repository};
+    use super::NameFilter;
+
+    #[test]
+    fn empty_filter_accepts_everything() {
+        let filter = NameFilter::default();
+        assert!(filter.accepts_crate("serde"));
+        assert!(filter.accepts_repo(&Repository::new("serde-rs", "serde")));
+    }
+
+    #[test]
+    fn crate_patterns() {
+        let filter = NameFilter::new(&["serde*", "tokio-*"], &["serde_derive"]).unwrap();
+        assert!(filter.accepts_crate("serde"));
+        assert!(filter.accepts_crate("serde_json"));
+        assert!(filter.accepts_crate("tokio-core"));
+        assert!(!filter.accepts_crate("serde_derive"));
+        assert!(!filter.accepts_crate("tokio"));
+        // Crate patterns don't restrict the repositories.
+        assert!(filter.accepts_repo(&Repository::new("rust-lang", "log")));
+    }
+
+    #[test]
+    fn repo_patterns() {
+        let filter = NameFilter::new(&[] as &[&str], &["OurCompany/*", "rust-lang/log"]).unwrap();
+        assert!(filter.accepts_crate("log"));
+        assert!(!filter.accepts_repo(&Repository::new("ourcompany", "internal")));
+        assert!(!filter.accepts_repo(&Repository::new("rust-lang", "log")));
+        assert!(filter.accepts_repo(&Repository::new("rust-lang", "regex")));
+    }
+
+    #[test]
+    fn repo_patterns_on_other_forges() {
+        let gitlab = Repository::with_host(Forge::GitLab, "gitlab.com", "ourcompany", "internal");
+        let gitea = Repository::with_host(Forge::Gitea, "codeberg.org", "someone", "thing");
+        let filter = NameFilter::new(&[] as &[&str], &["OurCompany/*"]).unwrap();
+        assert!(!filter.accepts_repo(&gitlab));
+        assert!(filter.accepts_repo(&gitea));
+
+        let filter = NameFilter::new(&["someone/*", "gitlab:*/*"], &[] as &[&str]).unwrap();
+        assert!(filter.accepts_repo(&gitlab));
+        assert!(filter.accepts_repo(&gitea));
+        assert!(!filter.accepts_repo(&Repository::new("rust-lang", "log")));
+    }
+
+    #[test]
+    fn invalid_pattern() {
+        assert!(NameFilter::new(&["[serde"], &[] as &[&str]).is_err());
+    }
+}
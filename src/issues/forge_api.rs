@@ -0,0 +1,199 @@
+//! Module with the parts shared by clients of the forges' REST APIs.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use futures::{future, stream, Future, Stream};
+use hyper::{self, Method, Request, StatusCode, Uri};
+use hyper::client::Connect;
+use hyper::header::{Location, UserAgent};
+use serde::de::DeserializeOwned;
+use serde_json;
+
+use ext::futures::FutureExt;
+use ext::hyper::BodyExt;
+use model::Repository;
+use ::USER_AGENT;
+
+
+/// Maximum number of redirects to follow for a single request.
+const MAX_REDIRECTS: usize = 5;
+
+/// Maximum number of pages of results to fetch for a single query,
+/// so that huge issue trackers don't keep us busy indefinitely.
+const MAX_PAGES: usize = 10;
+
+
+/// Client of a forge's API that can talk to any number of the forge's instances.
+pub trait ForgeClient {
+    /// Settings of the particular forge instances.
+    fn hosts_mut(&mut self) -> &mut HostSettings;
+
+    /// Configure given instance of the forge,
+    /// optionally with a non-standard URL of its API and/or an access token to use.
+    fn configure_host(&mut self, host: &str, api_root: Option<&str>, token: Option<&str>) {
+        let hosts = self.hosts_mut();
+        if let Some(api_root) = api_root {
+            hosts.api_roots.insert(host.to_owned(), api_root.trim_end_matches('/').to_owned());
+        }
+        if let Some(token) = token {
+            hosts.tokens.insert(host.to_owned(), token.to_owned());
+        }
+    }
+}
+
+/// Settings of the forge instances that a client talks to, keyed by their hostnames.
+#[derive(Clone, Debug, Default)]
+pub struct HostSettings {
+    /// Root URLs of the API, if they're different than the forge's usual one.
+    api_roots: HashMap<String, String>,
+    /// Access tokens to authenticate with.
+    tokens: HashMap<String, String>,
+}
+
+impl HostSettings {
+    /// Root URL of the API of given host (without a trailing slash), if it's been configured.
+    #[inline]
+    pub fn api_root(&self, host: &str) -> Option<&str> {
+        self.api_roots.get(host).map(|r| r.as_str())
+    }
+
+    /// Access token to use with given host, if there is one.
+    #[inline]
+    pub fn token(&self, host: &str) -> Option<&str> {
+        self.tokens.get(host).map(|t| t.as_str())
+    }
+}
+
+
+/// Stream the results of an API query that are split into pages.
+///
+/// Given the cursor of a page (starting with `first`), `fetch_page` should return its items
+/// along with the cursor of the next page, if there is one.
+/// Only up to `MAX_PAGES` are fetched.
+pub fn paginate<P, T, F>(first: P, mut fetch_page: F) -> Box<dyn Stream<Item=T, Error=Error>>
+    where P: 'static,
+          T: 'static,
+          F: FnMut(P) -> Box<dyn Future<Item=(Vec<T>, Option<P>), Error=Error>> + 'static
+{
+    let pages = stream::unfold(Some((first, 1)), move |state| {
+        let (cursor, count) = state?;
+        Some(fetch_page(cursor).map(move |(items, next)| {
+            let next = match next {
+                Some(_) if count >= MAX_PAGES => {
+                    debug!("Stopping after {} pages of results", MAX_PAGES);
+                    None
+                }
+                next => next.map(|n| (n, count + 1)),
+            };
+            (stream::iter_ok(items), next)
+        }))
+    });
+    Box::new(pages.flatten())
+}
+
+
+/// Fetch a JSON resource of given repository from a forge's API,
+/// optionally sending an authorization header along with the request.
+///
+/// Returns None if the repository (or its issue tracker) cannot be accessed.
+/// This isn't treated as an error, since repositories listed in crate manifests
+/// may be invalid, outdated, or private. The same goes for hitting the API's rate limits.
+///
+/// Redirects (which forges use e.g. for renamed repositories) are followed.
+pub fn fetch_json<C, T>(
+    http: &hyper::Client<C>, url: &str, auth: Option<(&'static str, String)>,
+    repo: &Repository,
+) -> Box<dyn Future<Item=Option<T>, Error=Error>>
+    where C: Clone + Connect, T: DeserializeOwned + 'static
+{
+    fetch_json_redirected(http, url, auth, repo, MAX_REDIRECTS)
+}
+
+fn fetch_json_redirected<C, T>(
+    http: &hyper::Client<C>, url: &str, auth: Option<(&'static str, String)>,
+    repo: &Repository, redirects_left: usize,
+) -> Box<dyn Future<Item=Option<T>, Error=Error>>
+    where C: Clone + Connect, T: DeserializeOwned + 'static
+{
+    let uri = match Uri::from_str(url) {
+        Ok(u) => u,
+        Err(e) => return future::err(Error::Http(e.into())).into_box(),
+    };
+    let mut request = Request::new(Method::Get, uri);
+    // Some APIs (like GitHub's) refuse to serve requests without a User-Agent.
+    request.headers_mut().set(UserAgent::new(USER_AGENT.to_string()));
+    if let Some((header, ref value)) = auth {
+        request.headers_mut().set_raw(header, value.clone());
+    }
+    let (http, url, repo) = (http.clone(), url.to_owned(), repo.clone());
+    http.request(request).map_err(Error::Http).and_then(move |resp| {
+        let status = resp.status();
+        if status.is_redirection() && redirects_left > 0 {
+            if let Some(location) = resp.headers().get::<Location>() {
+                trace!("Following redirect to {}", location);
+                // Don't hand our credentials over to a different host.
+                let auth = if host_of(location) == host_of(&url) { auth } else { None };
+                return fetch_json_redirected(&http, location, auth, &repo, redirects_left - 1);
+            }
+        }
+        if status.is_success() {
+            return resp.body().into_bytes().map_err(Error::Http)
+                .and_then(|bytes| serde_json::from_reader(&bytes[..]).map(Some).map_err(Error::Json))
+                .into_box();
+        }
+        match status {
+            StatusCode::NotFound | StatusCode::Unauthorized | StatusCode::Forbidden => {
+                warn!("Cannot access issues of repository {}: {}", repo, status);
+                future::ok(None).into_box()
+            }
+            StatusCode::TooManyRequests => {
+                warn!("API rate limit hit on repo {}", repo);
+                future::ok(None).into_box()
+            }
+            _ => {
+                error!("Unexpected response code from {} for repository {}: {}",
+                    repo.host, repo, status);
+                future::err(Error::Http(hyper::Error::Status)).into_box()
+            }
+        }
+    }).into_box()
+}
+
+
+/// Host (and port) of given URL, if it has one.
+fn host_of(url: &str) -> Option<String> {
+    Uri::from_str(url).ok().and_then(|u| u.authority().map(String::from))
+}
+
+
+/// Error that can occur while talking to a forge's API.
+#[derive(Debug, Error)]
+pub enum Error {
+    /// HTTP error.
+    Http(hyper::Error),
+    /// JSON error.
+    Json(serde_json::Error),
+}
+
+
+#[cfg(test)]
+mod tests {
+    use futures::{future, Future, Stream};
+    use super::{MAX_PAGES, paginate};
+
+    #[test]
+    fn pagination() {
+        let items = paginate(1, |page: usize| {
+            let next = if page < 3 { Some(page + 1) } else { None };
+            Box::new(future::ok((vec![page * 10, page * 10 + 1], next)))
+        });
+        assert_eq!(vec![10, 11, 20, 21, 30, 31], items.collect().wait().unwrap());
+    }
+
+    #[test]
+    fn pagination_limit() {
+        let items = paginate(1, |page: usize| Box::new(future::ok((vec![page], Some(page + 1)))));
+        assert_eq!(MAX_PAGES, items.collect().wait().unwrap().len());
+    }
+}
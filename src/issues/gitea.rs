@@ -0,0 +1,179 @@
+//! Module for making calls to the API of Gitea,
+//! as well as its compatible forks like Forgejo (which runs codeberg.org).
+
+use futures::{Future, Stream};
+use hyper;
+use hyper::client::Connect;
+use url::form_urlencoded;
+
+use model::{Issue, Repository};
+use super::forge_api::{fetch_json, ForgeClient, HostSettings, paginate};
+
+pub use super::forge_api::Error;
+
+
+/// Number of issues to request per page (which is also the default maximum).
+const ISSUES_PER_PAGE: usize = 50;
+
+
+/// Issue as returned by the Gitea API.
+#[derive(Debug, Deserialize)]
+pub struct IssueItem {
+    pub number: u64,
+    pub title: String,
+    #[serde(default)]
+    pub body: Option<String>,
+    /// URL to the HTML page of the issue.
+    pub html_url: String,
+    /// Number of comments on the issue.
+    #[serde(default)]
+    pub comments: usize,
+    #[serde(default)]
+    pub labels: Vec<Label>,
+    #[serde(default)]
+    pub assignees: Option<Vec<User>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Label {
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct User {
+    pub login: String,
+}
+
+impl IssueItem {
+    /// Whether the issue is assigned to anyone.
+    pub fn is_assigned(&self) -> bool {
+        self.assignees.as_ref().map(|a| !a.is_empty()).unwrap_or(false)
+    }
+
+    /// Convert to the `Issue` from given repository.
+    pub fn into_issue(self, repo: Repository) -> Issue {
+        Issue{
+            repo,
+            number: self.number,
+            url: self.html_url,
+            title: self.title,
+            body: self.body.unwrap_or_else(String::new),
+            comment_count: self.comments,
+            labels: self.labels.into_iter().map(|l| l.name).collect(),
+            dependency: None,
+            dependent: None,
+            mentioned_crate: None,
+        }
+    }
+}
+
+
+/// Client for the Gitea API.
+#[derive(Clone, Debug)]
+pub struct Client<C: Clone> {
+    http: hyper::Client<C>,
+    /// Settings of particular Gitea hosts, whose API is usually at https://$HOST/api/v1.
+    hosts: HostSettings,
+}
+
+impl<C: Clone> Client<C> {
+    #[inline]
+    pub fn with_http(http: hyper::Client<C>) -> Self {
+        Client{http, hosts: HostSettings::default()}
+    }
+
+    fn api_root(&self, host: &str) -> String {
+        self.hosts.api_root(host).map(String::from)
+            .unwrap_or_else(|| format!("https://{}/api/v1", host))
+    }
+}
+
+impl<C: Clone> ForgeClient for Client<C> {
+    #[inline]
+    fn hosts_mut(&mut self) -> &mut HostSettings {
+        &mut self.hosts
+    }
+}
+
+impl<C: Clone + Connect> Client<C> {
+    /// Return a stream of the open & unassigned issues in given Gitea repository
+    /// which have any of given labels, most recently updated first.
+    pub fn pending_issues(
+        &self, repo: Repository, labels: &[&str]
+    ) -> Box<dyn Stream<Item=IssueItem, Error=Error>> {
+        debug!("Querying for issues in {:?}", repo);
+        let client = self.clone();
+        let labels = labels.join(",");
+        let issues = paginate(1, move |page| {
+            Box::new(client.issues_page(&repo, &labels, page).map(move |issues| {
+                let next_page = if issues.len() < ISSUES_PER_PAGE { None } else { Some(page + 1) };
+                (issues, next_page)
+            }))
+        });
+        // There is no API parameter for unassigned issues, so we have to filter them here.
+        Box::new(issues.filter(|ii| !ii.is_assigned()))
+    }
+
+    /// Fetch given page of the open issues with any of given (comma-separated) labels
+    /// in a repository. Pages are numbered from 1.
+    fn issues_page(
+        &self, repo: &Repository, labels: &str, page: usize
+    ) -> Box<dyn Future<Item=Vec<IssueItem>, Error=Error>> {
+        let labels: String = form_urlencoded::byte_serialize(labels.as_bytes()).collect();
+        let url = format!(
+            concat!("{}/repos/{}/{}/issues?state=open&type=issues&labels={}",
+                "&sort=recentupdate&limit={}&page={}"),
+            self.api_root(&repo.host), repo.owner, repo.name, labels, ISSUES_PER_PAGE, page);
+        trace!("Fetching Gitea issues from {}", url);
+
+        let auth = self.hosts.token(&repo.host)
+            .map(|token| ("Authorization", format!("token {}", token)));
+        Box::new(fetch_json(&self.http, &url, auth, repo).map(Option::unwrap_or_default))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use futures::Stream;
+    use hyper;
+    use tokio_core::reactor::Core;
+    use model::{Forge, Repository};
+    use testing::MockServer;
+    use super::{Client, ForgeClient};
+
+    const ISSUES_PATH: &str = concat!(
+        "/api/v1/repos/owner/repo/issues?state=open&type=issues&labels=help+wanted%2Ceasy",
+        "&sort=recentupdate&limit=50&page=1");
+
+    #[test]
+    fn pending_issues() {
+        let server = MockServer::start(vec![
+            (ISSUES_PATH, r#"[
+                {
+                    "number": 3, "title": "Support Windows", "body": "",
+                    "html_url": "https://codeberg.org/owner/repo/issues/3", "comments": 1,
+                    "labels": [{"name": "help wanted"}], "assignees": null
+                },
+                {
+                    "number": 2, "title": "Fix the docs", "body": null,
+                    "html_url": "https://codeberg.org/owner/repo/issues/2", "comments": 0,
+                    "labels": [], "assignees": [{"login": "someone"}]
+                }
+            ]"#),
+        ]);
+        let mut core = Core::new().unwrap();
+        let mut client = Client::with_http(hyper::Client::new(&core.handle()));
+        client.configure_host("codeberg.org", Some(&format!("{}/api/v1", server.url())), None);
+
+        let repo = Repository::with_host(Forge::Gitea, "codeberg.org", "owner", "repo");
+        let issues = client.pending_issues(repo.clone(), &["help wanted", "easy"]);
+        let issues = core.run(issues.collect()).unwrap();
+        assert_eq!(1, issues.len());
+        assert_eq!("help wanted", issues[0].labels[0].name);
+
+        let issue = issues.into_iter().next().unwrap().into_issue(repo);
+        assert_eq!(3, issue.number);
+        assert_eq!(1, issue.comment_count);
+    }
+}
@@ -1,18 +1,91 @@
 //! Module for making GitHub API calls.
+//!
+//! This covers both github.com and the GitHub Enterprise servers.
 
-use futures::{future, Stream};
-use hubcaps::{Error, Github, SortDirection};
+use futures::{future, Future, Stream};
+use hubcaps::{Credentials, Error, Github, SortDirection};
 use hubcaps::errors::ErrorKind;
 use hubcaps::search::{IssuesItem, IssuesSort, SearchIssuesOptions};
-use hyper::StatusCode;
+use hyper::{self, StatusCode};
 use hyper::client::Connect;
 use itertools::Itertools;
 
 use model::Repository;
+use super::forge_api::{self, fetch_json};
+use ::USER_AGENT;
+
+
+/// Repository as returned by the GitHub repos API.
+#[derive(Debug, Deserialize)]
+pub struct RepoItem {
+    pub name: String,
+    pub owner: Owner,
+    /// Whether the repository has been archived (made read-only).
+    #[serde(default)]
+    pub archived: bool,
+    #[serde(default)]
+    pub fork: bool,
+    /// Repository that this one has been forked from, if it's a fork.
+    #[serde(default)]
+    pub parent: Option<Box<RepoItem>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Owner {
+    pub login: String,
+}
+
+
+/// Client for the API of a single GitHub host.
+#[derive(Clone, Debug)]
+pub struct Client<C: Clone + Connect> {
+    /// Client for the endpoints supported by hubcaps.
+    github: Github<C>,
+    /// HTTP client for the endpoints that hubcaps doesn't support (well enough).
+    http: hyper::Client<C>,
+    api_root: String,
+    token: Option<String>,
+}
+
+impl<C: Clone + Connect> Client<C> {
+    /// Create a client for the GitHub API at given root URL (like "https://api.github.com"),
+    /// optionally using a personal access token.
+    pub fn new<R: ToString>(http: hyper::Client<C>, api_root: R, token: Option<&str>) -> Self {
+        let api_root = api_root.to_string().trim_end_matches('/').to_owned();
+        let credentials = token.map(|t| Credentials::Token(t.to_owned()));
+        Client{
+            github: Github::custom(
+                api_root.clone(), USER_AGENT.to_string(), credentials, http.clone()),
+            http,
+            api_root,
+            token: token.map(String::from),
+        }
+    }
+
+    /// Look up given repository, following any redirects from its old name(s).
+    ///
+    /// Returns None if the repository doesn't exist or is inaccessible.
+    pub fn lookup_repo(
+        &self, repo: &Repository,
+    ) -> Box<dyn Future<Item=Option<RepoItem>, Error=forge_api::Error>> {
+        let url = format!("{}/repos/{}/{}", self.api_root, repo.owner, repo.name);
+        trace!("Looking up GitHub repository at {}", url);
+        let auth = self.token.as_ref().map(|t| ("Authorization", format!("token {}", t)));
+        fetch_json(&self.http, &url, auth, repo)
+    }
+
+    /// Return a stream of all open & unassigned issues in given GitHub repository.
+    #[inline]
+    pub fn pending_issues(
+        &self, repo: Repository
+    ) -> Box<dyn Stream<Item=IssuesItem, Error=Error>> {
+        pending_issues(&self.github, repo)
+    }
+}
 
 
 /// Return a stream of all open & unassigned issues in given GitHub repository.
-pub fn pending_issues<C: Clone + Connect>(
+fn pending_issues<C: Clone + Connect>(
     github: &Github<C>, repo: Repository
 ) -> Box<dyn Stream<Item=IssuesItem, Error=Error>> {
     let github = github.clone();
@@ -82,3 +155,50 @@ pub fn pending_issues<C: Clone + Connect>(
             .take_while(|opt_ii| future::ok(opt_ii.is_some())).map(Option::unwrap)
     )
 }
+
+
+#[cfg(test)]
+mod tests {
+    use hyper;
+    use tokio_core::reactor::Core;
+    use model::Repository;
+    use testing::MockServer;
+    use super::Client;
+
+    #[test]
+    fn lookup_fork() {
+        let server = MockServer::start(vec![
+            ("/repos/someone/fork", r#"{
+                "name": "fork", "owner": {"login": "someone"}, "archived": false, "fork": true,
+                "parent": {"name": "repo", "owner": {"login": "owner"}, "archived": true}
+            }"#),
+        ]);
+        let mut core = Core::new().unwrap();
+        let client = Client::new(hyper::Client::new(&core.handle()), server.url(), None);
+
+        let repo = Repository::new("someone", "fork");
+        let item = core.run(client.lookup_repo(&repo)).unwrap().unwrap();
+        assert!(item.fork && !item.archived);
+        let parent = item.parent.unwrap();
+        assert_eq!("owner", parent.owner.login);
+        assert!(parent.archived);
+    }
+
+    #[test]
+    fn lookup_renamed_repo() {
+        let server = MockServer::start(vec![
+            ("/repos/old-owner/old-name", "redirect:{server_url}/repositories/123"),
+            ("/repositories/123", r#"{"name": "new-name", "owner": {"login": "new-owner"}}"#),
+        ]);
+        let mut core = Core::new().unwrap();
+        let client = Client::new(hyper::Client::new(&core.handle()), server.url(), None);
+
+        let repo = Repository::new("old-owner", "old-name");
+        let item = core.run(client.lookup_repo(&repo)).unwrap().unwrap();
+        assert_eq!("new-owner", item.owner.login);
+        assert_eq!("new-name", item.name);
+
+        let missing = Repository::new("nobody", "nothing");
+        assert!(core.run(client.lookup_repo(&missing)).unwrap().is_none());
+    }
+}
@@ -0,0 +1,203 @@
+//! Module for making GitLab API calls.
+//!
+//! This covers both gitlab.com and the self-hosted GitLab instances.
+
+use std::collections::HashSet;
+
+use futures::{Future, Stream, stream};
+use hyper;
+use hyper::client::Connect;
+use url::form_urlencoded;
+
+use model::{Issue, Repository};
+use super::forge_api::{fetch_json, ForgeClient, HostSettings, paginate};
+
+pub use super::forge_api::Error;
+
+
+/// Number of issues to request per page (which is also the maximum).
+const ISSUES_PER_PAGE: usize = 100;
+
+/// Header used to authenticate with a personal access token.
+const TOKEN_HEADER: &str = "PRIVATE-TOKEN";
+
+
+/// Issue as returned by the GitLab API.
+#[derive(Debug, Deserialize)]
+pub struct IssueItem {
+    /// Issue number within the project.
+    pub iid: u64,
+    pub title: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// URL to the HTML page of the issue.
+    pub web_url: String,
+    /// Number of comments on the issue.
+    #[serde(default)]
+    pub user_notes_count: usize,
+    #[serde(default)]
+    pub labels: Vec<String>,
+}
+
+impl IssueItem {
+    /// Convert to the `Issue` from given repository.
+    pub fn into_issue(self, repo: Repository) -> Issue {
+        Issue{
+            repo,
+            number: self.iid,
+            url: self.web_url,
+            title: self.title,
+            body: self.description.unwrap_or_else(String::new),
+            comment_count: self.user_notes_count,
+            labels: self.labels,
+            dependency: None,
+            dependent: None,
+            mentioned_crate: None,
+        }
+    }
+}
+
+
+/// Client for the GitLab API.
+#[derive(Clone, Debug)]
+pub struct Client<C: Clone> {
+    http: hyper::Client<C>,
+    /// Settings of particular GitLab hosts, whose API is usually at https://$HOST/api/v4.
+    hosts: HostSettings,
+}
+
+impl<C: Clone> Client<C> {
+    #[inline]
+    pub fn with_http(http: hyper::Client<C>) -> Self {
+        Client{http, hosts: HostSettings::default()}
+    }
+
+    fn api_root(&self, host: &str) -> String {
+        self.hosts.api_root(host).map(String::from)
+            .unwrap_or_else(|| format!("https://{}/api/v4", host))
+    }
+}
+
+impl<C: Clone> ForgeClient for Client<C> {
+    #[inline]
+    fn hosts_mut(&mut self) -> &mut HostSettings {
+        &mut self.hosts
+    }
+}
+
+impl<C: Clone + Connect> Client<C> {
+    /// Return a stream of the open & unassigned issues in given GitLab repository
+    /// which have any of given labels, most recently updated first (for each of the labels).
+    ///
+    /// The API only allows to ask for the issues having _all_ of the given labels,
+    /// so each label is queried separately, and issues with several of them are returned once.
+    pub fn pending_issues(
+        &self, repo: Repository, labels: &[&str]
+    ) -> Box<dyn Stream<Item=IssueItem, Error=Error>> {
+        debug!("Querying for issues in {:?}", repo);
+        let label_issues: Vec<_> = labels.iter().map(|&label| {
+            let (client, repo, label) = (self.clone(), repo.clone(), label.to_owned());
+            paginate(1, move |page| {
+                Box::new(client.issues_page(&repo, &label, page).map(move |issues| {
+                    let next_page =
+                        if issues.len() < ISSUES_PER_PAGE { None } else { Some(page + 1) };
+                    (issues, next_page)
+                }))
+            })
+        }).collect();
+        let mut seen = HashSet::new();
+        Box::new(stream::iter_ok::<_, Error>(label_issues).flatten()
+            .filter(move |issue: &IssueItem| seen.insert(issue.iid)))
+    }
+
+    /// Fetch given page of the open & unassigned issues with given label in a repository.
+    /// Pages are numbered from 1.
+    fn issues_page(
+        &self, repo: &Repository, label: &str, page: usize
+    ) -> Box<dyn Future<Item=Vec<IssueItem>, Error=Error>> {
+        // Projects are identified by their URL-encoded path, like "group%2Fsubgroup%2Fproject".
+        let project: String = form_urlencoded::byte_serialize(
+            format!("{}/{}", repo.owner, repo.name).as_bytes()).collect();
+        let label: String = form_urlencoded::byte_serialize(label.as_bytes()).collect();
+        let url = format!(
+            concat!("{}/projects/{}/issues?state=opened&assignee_id=None&labels={}",
+                "&order_by=updated_at&sort=desc&per_page={}&page={}"),
+            self.api_root(&repo.host), project, label, ISSUES_PER_PAGE, page);
+        trace!("Fetching GitLab issues from {}", url);
+
+        let auth = self.hosts.token(&repo.host).map(|token| (TOKEN_HEADER, token.to_owned()));
+        Box::new(fetch_json(&self.http, &url, auth, repo).map(Option::unwrap_or_default))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use futures::Stream;
+    use hyper;
+    use tokio_core::reactor::Core;
+    use model::{Forge, Repository};
+    use testing::MockServer;
+    use super::{Client, ForgeClient};
+
+    /// Path of the issues with given (URL-encoded) label in the group/subgroup/project repo.
+    fn issues_path(label: &str) -> String {
+        format!(concat!(
+            "/api/v4/projects/group%2Fsubgroup%2Fproject/issues",
+            "?state=opened&assignee_id=None&labels={}",
+            "&order_by=updated_at&sort=desc&per_page=100&page=1"), label)
+    }
+
+    #[test]
+    fn pending_issues() {
+        let server = MockServer::start(vec![
+            (issues_path("help+wanted"), r#"[
+                {
+                    "iid": 12, "title": "Add a builder", "description": "Like the one in foo",
+                    "web_url": "https://gitlab.example.com/group/subgroup/project/-/issues/12",
+                    "user_notes_count": 3, "labels": ["help wanted", "easy", "feature"]
+                }
+            ]"#),
+            (issues_path("easy"), r#"[
+                {
+                    "iid": 12, "title": "Add a builder", "description": "Like the one in foo",
+                    "web_url": "https://gitlab.example.com/group/subgroup/project/-/issues/12",
+                    "user_notes_count": 3, "labels": ["help wanted", "easy", "feature"]
+                },
+                {
+                    "iid": 7, "title": "Typo in README", "description": null,
+                    "web_url": "https://gitlab.example.com/group/subgroup/project/-/issues/7",
+                    "labels": ["easy"]
+                }
+            ]"#),
+        ]);
+        let mut core = Core::new().unwrap();
+        let mut client = Client::with_http(hyper::Client::new(&core.handle()));
+        client.configure_host(
+            "gitlab.example.com", Some(&format!("{}/api/v4/", server.url())), None);
+
+        let repo = Repository::with_host(
+            Forge::GitLab, "gitlab.example.com", "group/subgroup", "project");
+        let issues = client.pending_issues(repo.clone(), &["help wanted", "easy"]);
+        let issues = core.run(issues.collect()).unwrap();
+        assert_eq!(2, issues.len());
+        assert_eq!(vec!["help wanted", "easy", "feature"], issues[0].labels);
+
+        let issue = issues.into_iter().nth(1).unwrap().into_issue(repo.clone());
+        assert_eq!(7, issue.number);
+        assert_eq!("", issue.body);
+        assert_eq!(repo, issue.repo);
+    }
+
+    #[test]
+    fn missing_repository() {
+        let server = MockServer::start(Vec::<(&str, &str)>::new());
+        let mut core = Core::new().unwrap();
+        let mut client = Client::with_http(hyper::Client::new(&core.handle()));
+        client.configure_host("gitlab.com", Some(&format!("{}/api/v4", server.url())), None);
+
+        let repo = Repository::with_host(Forge::GitLab, "gitlab.com", "nobody", "nothing");
+        let issues = core.run(client.pending_issues(repo, &["easy"]).collect()).unwrap();
+        assert!(issues.is_empty());
+    }
+}
@@ -1,8 +1,24 @@
 //! Module for producing suggested issues for crate dependencies.
 
+mod bitbucket;
+mod cargo_config;
+mod cargo_lock;
+mod cargo_metadata;
 mod cargo_toml;
 mod crates_io;
+mod features;
+mod filter;
+mod forge_api;
+mod gitea;
 mod github;
+mod gitlab;
+mod patch;
 mod producer;
+mod registry_cache;
+mod sourcehut;
+mod target;
+mod vendor;
 
+pub use self::cargo_toml::{DependencyFilter, find_manifest, find_projects};
+pub use self::filter::NameFilter;
 pub use self::producer::{Error, SuggestedIssuesProducer};
@@ -0,0 +1,302 @@
+//! Module for reading the [patch] and [replace] sections of workspace manifests.
+//!
+//! Those allow to substitute a crate in the entire dependency graph with another one,
+//! typically a fork of it that carries some local changes.
+
+use std::path::Path;
+
+use semver::{Version, VersionReq};
+use serde::de::Error as SerdeDeError;
+use toml::{self, Value as Toml};
+
+use model::{CrateLocation, Dependency, GitUrl};
+use super::cargo_config::{self, CargoConfig};
+use super::cargo_toml::{self, Error};
+
+
+/// Name that [patch] uses to refer to crates.io.
+const CRATES_IO_SOURCE: &str = "crates-io";
+
+
+/// Source of crates that a patch applies to.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PatchSource {
+    /// crates.io, or an alternative registry with given name.
+    Registry(Option<String>),
+    /// Registry index or Git repository with given URL.
+    Url(String),
+}
+
+impl PatchSource {
+    fn from_key(key: &str) -> Self {
+        if key == CRATES_IO_SOURCE {
+            PatchSource::Registry(None)
+        } else if key.contains("://") {
+            PatchSource::Url(key.to_owned())
+        } else {
+            PatchSource::Registry(Some(key.to_owned()))
+        }
+    }
+
+    /// Resolve the URLs of registry indices to registries they correspond to.
+    fn resolve(&self, config: &CargoConfig) -> PatchSource {
+        match *self {
+            PatchSource::Url(ref url) if cargo_config::is_crates_io_index(url) =>
+                PatchSource::Registry(None),
+            PatchSource::Url(ref url) => match config.registry_for_index(url) {
+                Some(registry) => PatchSource::Registry(Some(registry.to_owned())),
+                None => self.clone(),
+            },
+            _ => self.clone(),
+        }
+    }
+
+    /// Whether crates from given location come from this source.
+    fn contains(&self, location: &CrateLocation, config: &CargoConfig) -> bool {
+        match (self.resolve(config), location) {
+            (PatchSource::Registry(ref r), &CrateLocation::Registry{ref registry, ..}) =>
+                r == registry,
+            (PatchSource::Url(ref u), &CrateLocation::Git{ref url, ..}) =>
+                GitUrl::parse(u).is_some() && GitUrl::parse(u) == GitUrl::parse(url),
+            _ => false,
+        }
+    }
+
+    /// Location of a crate from this source, with any version.
+    fn location(&self, config: &CargoConfig) -> CrateLocation {
+        match self.resolve(config) {
+            PatchSource::Registry(registry) =>
+                CrateLocation::Registry{registry, version: VersionReq::any()},
+            PatchSource::Url(url) => CrateLocation::Git{url, reference: None},
+        }
+    }
+}
+
+
+/// Single entry from [patch] or [replace] section of the manifest.
+#[derive(Clone, Debug)]
+pub struct Patch {
+    /// Source of the crate that's being patched.
+    pub source: PatchSource,
+    /// Version of the patched crate, if the patch only applies to that version
+    /// (which is only the case for [replace]).
+    pub version: Option<Version>,
+    /// Crate that replaces the patched one.
+    pub replacement: Dependency,
+}
+
+impl Patch {
+    /// Whether this patch replaces given dependency.
+    pub fn applies_to(&self, dep: &Dependency, config: &CargoConfig) -> bool {
+        if dep.name() != self.replacement.name() {
+            return false;
+        }
+        if !self.source.contains(dep.location(), config) {
+            return false;
+        }
+        match (self.version.as_ref(), dep.location()) {
+            (None, _) => true,
+            (Some(v), _) if dep.locked_version().is_some() => dep.locked_version() == Some(v),
+            (Some(v), &CrateLocation::Registry{ref version, ..}) => version.matches(v),
+            (Some(_), _) => true,
+        }
+    }
+
+    /// Whether given dependency is the replacement crate of this patch.
+    ///
+    /// This is the case when the dependency graph has been resolved by Cargo,
+    /// which substitutes the patched crates by itself.
+    pub fn is_replacement(&self, dep: &Dependency) -> bool {
+        if dep.name() != self.replacement.name() {
+            return false;
+        }
+        match (dep.location(), self.replacement.location()) {
+            (&CrateLocation::Git{url: ref a, ..}, &CrateLocation::Git{url: ref b, ..}) =>
+                GitUrl::parse(a).is_some() && GitUrl::parse(a) == GitUrl::parse(b),
+            (&CrateLocation::Filesystem{path: ref a}, &CrateLocation::Filesystem{path: ref b}) =>
+                a.canonicalize().ok().map_or(false, |a| b.canonicalize().ok() == Some(a)),
+            _ => false,
+        }
+    }
+
+    /// Apply the patch to given dependency, marking it as patched.
+    ///
+    /// If the dependency is the replacement crate itself,
+    /// it's turned into a dependency on the patched crate instead.
+    pub fn apply(&self, dep: Dependency, config: &CargoConfig) -> Dependency {
+        if self.is_replacement(&dep) {
+            let location = self.source.location(config);
+            return dep.clone().with_location(location).with_patch(dep);
+        }
+        let patch = self.replacement.clone().with_kind(dep.kind());
+        dep.with_patch(patch)
+    }
+}
+
+
+/// Read the [patch] and [replace] sections of given manifest.
+///
+/// Those are only respected by Cargo in the root manifest of the workspace.
+pub fn read_patches<P: AsRef<Path>>(manifest_path: P) -> Result<Vec<Patch>, Error> {
+    let path = manifest_path.as_ref();
+    let manifest = cargo_toml::read_manifest(path)?;
+    let manifest_dir = cargo_toml::manifest_dir(path);
+
+    let mut result = vec![];
+    if let Some(patch) = manifest.get("patch") {
+        let sources = patch.as_table().ok_or_else(|| Error::Toml(toml::de::Error::custom(
+            format!("[patch] must be a table, got {}", patch.type_str()))))?;
+        for (source, entries) in sources {
+            let entries = entries.as_table().ok_or_else(|| Error::Toml(toml::de::Error::custom(
+                format!("[patch.{}] must be a table, got {}", source, entries.type_str()))))?;
+            for (name, spec) in entries {
+                result.push(Patch{
+                    source: PatchSource::from_key(source),
+                    version: None,
+                    replacement: read_replacement(name, spec, manifest_dir)?,
+                });
+            }
+        }
+    }
+    if let Some(replace) = manifest.get("replace") {
+        let entries = replace.as_table().ok_or_else(|| Error::Toml(toml::de::Error::custom(
+            format!("[replace] must be a table, got {}", replace.type_str()))))?;
+        for (spec, replacement) in entries {
+            let (source, name, version) = parse_package_id_spec(spec)?;
+            result.push(Patch{
+                source,
+                version,
+                replacement: read_replacement(name, replacement, manifest_dir)?,
+            });
+        }
+    }
+
+    if !result.is_empty() {
+        debug!("{} patched crate(s) found in {}", result.len(), path.display());
+    }
+    Ok(result)
+}
+
+/// Read the crate that replaces the patched one.
+fn read_replacement(name: &str, spec: &Toml, manifest_dir: &Path) -> Result<Dependency, Error> {
+    let dep = Dependency::from_toml(name, spec).map_err(Error::Toml)?;
+    // Paths are relative to the manifest, but the patch applies to the whole workspace.
+    Ok(match *dep.location() {
+        CrateLocation::Filesystem{ref path} => {
+            let location = CrateLocation::Filesystem{path: manifest_dir.join(path)};
+            dep.clone().with_location(location)
+        }
+        _ => dep,
+    })
+}
+
+/// Parse the package ID specification used as a key of [replace],
+/// i.e. `[$SOURCE_URL#]$NAME[:$VERSION]`.
+fn parse_package_id_spec(spec: &str) -> Result<(PatchSource, &str, Option<Version>), Error> {
+    let (source, rest) = match spec.rfind('#') {
+        Some(idx) => (PatchSource::Url(spec[..idx].to_owned()), &spec[idx + 1..]),
+        None => (PatchSource::Registry(None), spec),
+    };
+    let mut parts = rest.splitn(2, ':');
+    let name = parts.next().unwrap_or(rest);
+    let version = match parts.next() {
+        Some(v) => Some(Version::parse(v).map_err(|e| Error::Toml(toml::de::Error::custom(
+            format!("invalid version in [replace] entry `{}`: {}", spec, e))))?),
+        None => None,
+    };
+    Ok((source, name, version))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use semver::Version;
+    use toml;
+    use model::{CrateLocation, Dependency};
+    use issues::cargo_config::CargoConfig;
+    use testing::create_temp_dir;
+    use super::read_patches;
+
+    /// Create a manifest with given content, returning its path.
+    fn create_manifest(content: &str) -> PathBuf {
+        create_temp_dir(&[("Cargo.toml", content)]).join("Cargo.toml")
+    }
+
+    #[test]
+    fn patched_registry_dependency() {
+        let manifest = create_manifest(concat!(
+            "[package]\nname = \"app\"\nversion = \"0.1.0\"\n",
+            "[patch.crates-io]\n",
+            "log = { git = \"https://github.com/ourfork/log\", branch = \"fix\" }\n",
+            "serde = { path = \"../serde\" }\n"));
+        let patches = read_patches(&manifest).unwrap();
+        assert_eq!(2, patches.len());
+        let config = CargoConfig::default();
+
+        let log = Dependency::with_version("log", "1.0");
+        let patch = patches.iter().find(|p| p.applies_to(&log, &config)).unwrap();
+        let log = patch.apply(log, &config);
+        assert!(log.is_patched());
+        assert!(log.location().is_registry());
+        assert!(log.patch().unwrap().location().is_git());
+
+        let serde = Dependency::with_version("serde", "0.3").with_registry("company");
+        assert!(!patches.iter().any(|p| p.applies_to(&serde, &config)));
+
+        match *patches[1].replacement.location() {
+            CrateLocation::Filesystem{ref path} =>
+                assert_eq!(manifest.parent().unwrap().join("../serde"), *path),
+            ref l => panic!("unexpected location: {:?}", l),
+        }
+    }
+
+    #[test]
+    fn patched_git_dependency() {
+        let manifest = create_manifest(concat!(
+            "[patch.\"https://github.com/rust-lang/log\"]\n",
+            "log = { git = \"https://github.com/ourfork/log\" }\n",
+            "[patch.\"https://crates.example.com/index\"]\n",
+            "serde = { git = \"https://github.com/ourfork/serde\" }\n"));
+        let patches = read_patches(&manifest).unwrap();
+        let config: CargoConfig = toml::from_str(concat!(
+            "[registries.company]\nindex = \"https://crates.example.com/index\"\n")).unwrap();
+
+        let log = Dependency::with_git_url("log", "https://github.com/rust-lang/log.git");
+        assert!(patches.iter().any(|p| p.applies_to(&log, &config)));
+        let serde = Dependency::with_version("serde", "1").with_registry("company");
+        assert!(patches.iter().any(|p| p.applies_to(&serde, &config)));
+    }
+
+    #[test]
+    fn resolved_replacement() {
+        let manifest = create_manifest(concat!(
+            "[patch.crates-io]\n",
+            "log = { git = \"https://github.com/ourfork/log\" }\n"));
+        let patches = read_patches(&manifest).unwrap();
+        let config = CargoConfig::default();
+
+        // Cargo has already substituted the crate with its replacement.
+        let log = Dependency::with_git_url("log", "git+https://github.com/ourfork/log#abcd");
+        assert!(patches[0].is_replacement(&log));
+        let log = patches[0].apply(log, &config);
+        assert!(log.location().is_registry());
+        assert!(log.patch().unwrap().location().is_git());
+    }
+
+    #[test]
+    fn replaced_version() {
+        let manifest = create_manifest(concat!(
+            "[replace]\n",
+            "\"log:1.2.3\" = { git = \"https://github.com/ourfork/log\" }\n"));
+        let patches = read_patches(&manifest).unwrap();
+        let config = CargoConfig::default();
+
+        let log = Dependency::with_version("log", "1.2");
+        assert!(patches[0].applies_to(&log, &config));
+        let log = log.with_locked_version(Version::parse("1.2.4").unwrap());
+        assert!(!patches[0].applies_to(&log, &config));
+        assert!(!patches[0].applies_to(&Dependency::with_version("log", "2"), &config));
+    }
+}
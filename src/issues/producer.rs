@@ -1,28 +1,43 @@
 //! Module implementing the suggested issues producer.
 
-use std::collections::HashSet;
+use std::cell::RefCell;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt;
+use std::io;
 use std::path::{Path, PathBuf};
+use std::rc::Rc;
 
-use dirs;
 use futures::{future, Future, stream, Stream as StdStream};
-use glob::glob;
-use hubcaps::{self, Credentials, Error as HubcapsError, Github};
-use hubcaps::search::IssuesItem;
+use hubcaps;
 use hyper::client::{Client as HyperClient, Connect};
 use itertools::Itertools;
 use log::LogLevel::*;
 use rand::{Rng, thread_rng};
-use regex::Regex;
+use regex::{self, Regex};
 use semver::{Version, VersionReq};
 use tokio_core::reactor::Handle;
 
-use ::USER_AGENT;
-use model::{CrateLocation, Dependency, Issue, Package, Repository};
+use ext::futures::FutureExt;
+use model::{
+    CrateLocation, Dependency, Forge, ForgeHosts, ForkPolicy, GitUrl, Issue, Package, Repository,
+};
 use util::{https_client, HttpsConnector};
-use super::cargo_toml;
-use super::crates_io::{self, Client as CratesIoClient};
-use super::github::pending_issues;
+use super::bitbucket::{self, Client as BitbucketClient};
+use super::cargo_config::{self, CargoConfig, CRATES_IO_INDEX, CRATES_IO_SPARSE_INDEX};
+use super::cargo_lock;
+use super::cargo_metadata;
+use super::cargo_toml::{self, DependencyFilter};
+use super::crates_io::{self, Client as CratesIoClient, Registries};
+use super::filter::NameFilter;
+use super::forge_api::ForgeClient;
+use super::gitea::{self, Client as GiteaClient};
+use super::github::Client as GitHubClient;
+use super::gitlab::{self, Client as GitLabClient};
+use super::patch::{self, Patch};
+use super::registry_cache::RegistryCache;
+use super::sourcehut::{self, Client as SourceHutClient};
+use super::vendor::VendoredSources;
 
 
 type Stream<T> = Box<dyn StdStream<Item=T, Error=Error>>;
@@ -35,7 +50,20 @@ pub type IssueStream = Stream<Issue>;
 /// for given crate manifest.
 pub struct SuggestedIssuesProducer {
     crates_io: CratesIoClient<HttpsConnector>,
-    github: Github<HttpsConnector>,
+    /// Registries of the projects' dependencies, with the clients of alternative ones
+    /// shared between all the projects.
+    registries: Registries<HttpsConnector>,
+    forges: Forges,
+    dependency_filter: DependencyFilter,
+    name_filter: NameFilter,
+    max_depth: Option<usize>,
+    use_cargo_metadata: bool,
+    repo_policy: RepoPolicy,
+    /// Cargo's cache of downloaded crates, if it can be found.
+    registry_cache: Option<RegistryCache>,
+    /// Repositories of crates as given by the user, overriding the crates' own metadata.
+    /// None means the crate should be ignored.
+    repo_overrides: HashMap<String, Option<Repository>>,
 }
 
 impl SuggestedIssuesProducer {
@@ -46,22 +74,38 @@ impl SuggestedIssuesProducer {
 
     #[inline]
     pub fn with_github_token(token: &str, handle: &Handle) -> Self {
-        let http = https_client(handle);
-        SuggestedIssuesProducer {
-            crates_io: CratesIoClient::with_http(http.clone()),
-            github: Github::custom(
-                GITHUB_API_ROOT, USER_AGENT.to_owned(),
-                Some(Credentials::Token(token.to_owned())), http.clone()),
-        }
+        Self::with_http_and_token(https_client(handle), Some(token))
     }
 
     #[inline]
-    #[cfg_attr(feature = "cargo-clippy", allow(needless_pass_by_value))]
     pub fn with_http(http: HyperClient<HttpsConnector>) -> Self {
+        Self::with_http_and_token(http, /* token */ None)
+    }
+
+    #[cfg_attr(feature = "cargo-clippy", allow(needless_pass_by_value))]
+    fn with_http_and_token(http: HyperClient<HttpsConnector>, token: Option<&str>) -> Self {
         SuggestedIssuesProducer {
             crates_io: CratesIoClient::with_http(http.clone()),
-            github: Github::custom(
-                GITHUB_API_ROOT, USER_AGENT.to_owned(), /* credentials */ None, http.clone()),
+            registries: Registries::new(CratesIoClient::with_http(http.clone())),
+            forges: Forges{
+                github: hashmap!{
+                    Forge::GitHub.default_host().to_owned() =>
+                        GitHubClient::new(http.clone(), GITHUB_API_ROOT, token),
+                },
+                gitlab: GitLabClient::with_http(http.clone()),
+                gitea: GiteaClient::with_http(http.clone()),
+                sourcehut: SourceHutClient::with_http(http.clone()),
+                bitbucket: BitbucketClient::with_http(http.clone()),
+                http: http.clone(),
+                hosts: ForgeHosts::new(),
+            },
+            dependency_filter: DependencyFilter::default(),
+            name_filter: NameFilter::default(),
+            max_depth: Some(1),
+            use_cargo_metadata: false,
+            repo_policy: RepoPolicy::default(),
+            registry_cache: RegistryCache::new(),
+            repo_overrides: HashMap::new(),
         }
     }
 
@@ -69,54 +113,403 @@ impl SuggestedIssuesProducer {
 }
 
 impl SuggestedIssuesProducer {
-    /// Suggest issues for a crate with given Cargo.toml manifest.
-    pub fn suggest_issues<P: AsRef<Path>>(&self, manifest_path: P) -> Result<IssueStream, Error> {
-        let manifest_path = manifest_path.as_ref();
-        debug!("Suggesting dependency issues for manifest path {}", manifest_path.display());
+    /// Set the criteria for which dependencies of the crate(s) are considered.
+    #[inline]
+    pub fn set_dependency_filter(&mut self, filter: DependencyFilter) {
+        self.dependency_filter = filter;
+    }
 
-        let mut deps = cargo_toml::list_dependencies(manifest_path)?;
-        thread_rng().shuffle(&mut deps);
-
-        // Determine the GitHub repositories corresponding to dependent crates.
-        // In most cases, this means read the package/repository entries
-        // from the manifests of those crates by looking at Cargo cache or talking to crates.io.
-        let mut repo_set = HashSet::new();
-        let repos = {
-            let manifest_path = manifest_path.to_owned();
-            let crates_io = self.crates_io.clone();
-            stream::iter_ok(deps)
-                .and_then(move |dep| {
-                    repo_for_dependency(&manifest_path, &crates_io, &dep).map_err(Error::CratesIo)
-                })
-                .filter_map(move |opt_repo| {
-                    if let Some(repo) = opt_repo {
-                        // Check if we've reported on this repo already.
-                        if repo_set.contains(&repo) { None }
-                        else {
-                            repo_set.insert(repo.clone()); Some(repo)
-                        }
-                    } else { None }
-                })
+    /// Set the patterns for crates and repositories that should (or shouldn't) be looked at.
+    #[inline]
+    pub fn set_name_filter(&mut self, filter: NameFilter) {
+        self.name_filter = filter;
+    }
+
+    /// Set the personal access token to use with gitlab.com.
+    #[inline]
+    pub fn set_gitlab_token(&mut self, token: &str) {
+        self.forges.gitlab.configure_host(Forge::GitLab.default_host(), None, Some(token));
+    }
+
+    /// Configure a self-hosted instance of given forge,
+    /// optionally with a non-standard URL of its API and/or an access token to use.
+    ///
+    /// Repositories on the host are only recognized once it's been configured.
+    pub fn configure_host(
+        &mut self, forge: Forge, host: &str, api_root: Option<&str>, token: Option<&str>,
+    ) {
+        self.forges.hosts.insert(host, forge);
+        let client: &mut dyn ForgeClient = match forge {
+            // Every GitHub host (i.e. a GitHub Enterprise Server instance) gets a client of its own.
+            Forge::GitHub => {
+                let api_root = api_root.map(String::from)
+                    .unwrap_or_else(|| format!("https://{}/api/v3", host));
+                let github = GitHubClient::new(self.forges.http.clone(), api_root, token);
+                self.forges.github.insert(host.to_owned(), github);
+                return;
+            }
+            Forge::GitLab => &mut self.forges.gitlab,
+            Forge::Gitea => &mut self.forges.gitea,
+            Forge::SourceHut => &mut self.forges.sourcehut,
+            Forge::Bitbucket => &mut self.forges.bitbucket,
         };
+        client.configure_host(host, api_root, token);
+    }
+
+    /// Set how deep into the dependency graph should we go.
+    ///
+    /// Depth of 1 means only the direct dependencies, while None means no limit.
+    /// Any depth greater than 1 requires a Cargo.lock to be present.
+    #[inline]
+    pub fn set_max_depth(&mut self, max_depth: Option<usize>) {
+        self.max_depth = max_depth;
+    }
+
+    /// Set whether the dependencies should be obtained from `cargo metadata`
+    /// rather than by reading Cargo.toml & Cargo.lock files directly.
+    #[inline]
+    pub fn set_use_cargo_metadata(&mut self, use_cargo_metadata: bool) {
+        self.use_cargo_metadata = use_cargo_metadata;
+    }
+
+    /// Set which repositories should be looked at for crates whose repository is a fork.
+    #[inline]
+    pub fn set_fork_policy(&mut self, policy: ForkPolicy) {
+        self.repo_policy.forks = policy;
+    }
+
+    /// Set whether the repositories which have been archived should be skipped.
+    #[inline]
+    pub fn set_skip_archived(&mut self, skip_archived: bool) {
+        self.repo_policy.skip_archived = skip_archived;
+    }
+
+    /// Set the repositories to use for given crates instead of those from their metadata,
+    /// as `forge:owner/repo` (or `none` to ignore the crate).
+    ///
+    /// Overrides from the projects' own manifests take precedence over these.
+    /// Any self-hosted forge instances they refer to need to be configured beforehand.
+    pub fn set_repository_overrides<'o, I>(&mut self, overrides: I) -> Result<(), String>
+        where I: IntoIterator<Item=(&'o String, &'o String)>
+    {
+        for (crate_, spec) in overrides {
+            let repo = parse_repo_override(crate_, spec, &self.forges.hosts)?;
+            self.repo_overrides.insert(crate_.clone(), repo);
+        }
+        Ok(())
+    }
+}
+
+impl SuggestedIssuesProducer {
+    /// Suggest issues for crates with given Cargo.toml manifests.
+    ///
+    /// If there are several projects, dependencies shared between them are only looked at once,
+    /// and those used by more of the projects are considered before the others.
+    /// Projects whose dependencies cannot be determined are skipped (unless there is just one).
+    pub fn suggest_issues<P: AsRef<Path>>(
+        &self, manifest_paths: &[P]
+    ) -> Result<IssueStream, Error> {
+        let usages = self.project_usages(manifest_paths)?;
+        Ok(suggest_usage_issues(
+            usages, self.forges.clone(), self.name_filter.clone(), self.repo_policy))
+    }
+
+    /// List the dependencies of crates with given Cargo.toml manifests
+    /// whose repositories cannot be determined (and haven't been overridden, either).
+    pub fn unresolved_dependencies<P: AsRef<Path>>(
+        &self, manifest_paths: &[P]
+    ) -> Result<Box<dyn Future<Item=Vec<Dependency>, Error=Error>>, Error> {
+        let usages = self.project_usages(manifest_paths)?;
+        let unresolved = stream::iter_ok(usages)
+            .and_then(|usage| {
+                let dep = usage.dependency.dependency.clone();
+                let overridden = usage.sources.overrides.contains_key(dep.name());
+                repos_for_project_dependency(&usage.dependency, &usage.sources)
+                    .map(move |repos| {
+                        if repos.is_empty() && !overridden { Some(dep) } else { None }
+                    })
+                    .map_err(Error::CratesIo)
+            })
+            .filter_map(|dep| dep)
+            .collect();
+        Ok(Box::new(unresolved))
+    }
+
+    /// Determine the usages of dependencies of crates with given Cargo.toml manifests.
+    fn project_usages<P: AsRef<Path>>(
+        &self, manifest_paths: &[P]
+    ) -> Result<Vec<DependencyUsage>, Error> {
+        let mut usages: Vec<DependencyUsage> = vec![];
+        let mut usage_indices = HashMap::new();
+        for (project, manifest_path) in manifest_paths.iter().enumerate() {
+            let manifest_path = manifest_path.as_ref();
+            let (deps, sources) = match self.list_dependencies(manifest_path) {
+                Ok(result) => result,
+                Err(ref e) if manifest_paths.len() > 1 => {
+                    warn!("Skipping project {}: {}", manifest_path.display(), e);
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            let sources = Rc::new(sources);
+            for pd in deps {
+                if !self.name_filter.accepts_crate(pd.dependency.name()) {
+                    debug!("Skipping dependency `{}` as filtered out", pd.dependency.name());
+                    continue;
+                }
+                let key = dependency_key(&pd);
+                if let Some(&idx) = usage_indices.get(&key) {
+                    let usage: &mut DependencyUsage = &mut usages[idx];
+                    usage.projects.insert(project);
+                    // Keep the occurrence that's the closest to any of the projects.
+                    if pd.depth < usage.dependency.depth {
+                        usage.dependency = pd;
+                        usage.sources = sources.clone();
+                    }
+                    continue;
+                }
+                usage_indices.insert(key, usages.len());
+                usages.push(DependencyUsage{
+                    dependency: pd,
+                    sources: sources.clone(),
+                    projects: Some(project).into_iter().collect(),
+                });
+            }
+        }
+        if manifest_paths.len() > 1 {
+            debug!("{} distinct dependencies found across {} projects",
+                usages.len(), manifest_paths.len());
+        }
+        Ok(usages)
+    }
+
+    /// Suggest issues for a crate from crates.io with given name & version requirement,
+    /// as well as its dependencies.
+    ///
+    /// The newest version that matches is looked up on crates.io, unless the requirement
+    /// is for an exact version whose manifest is already in Cargo's registry cache.
+    /// Without a cached manifest, only the direct dependencies of the crate are known.
+    pub fn suggest_crate_issues(
+        &self, name: &str, version: &VersionReq
+    ) -> Result<IssueStream, Error> {
+        debug!("Suggesting issues for crate {}={}", name, version);
+        let sources = self.crates_io_sources();
+
+        // Only an exact version is sure to be the one that crates.io would give us.
+        if let Some(version) = exact_version(version) {
+            let cached_deps = cached_crate_dependencies(
+                self.registry_cache.as_ref(), name, &version,
+                &self.dependency_filter, self.max_depth)?;
+            if let Some(deps) = cached_deps {
+                let usages = crate_usages(deps, sources, &self.name_filter);
+                return Ok(suggest_usage_issues(
+                    usages, self.forges.clone(), self.name_filter.clone(), self.repo_policy));
+            }
+        }
+
+        let (name, version) = (name.to_owned(), version.clone());
+        let crates_io = self.crates_io.clone();
+        let registry_cache = self.registry_cache.clone();
+        let (dependency_filter, max_depth) = (self.dependency_filter.clone(), self.max_depth);
+        let (forges, name_filter) = (self.forges.clone(), self.name_filter.clone());
+        let repo_policy = self.repo_policy;
+        let issues = self.crates_io.lookup_crate(name.clone())
+            .map_err(Error::CratesIo)
+            .and_then(move |crate_| {
+                // Take the newest version that matches, and its dependencies.
+                let crate_version = crate_.as_ref().and_then(|c| c.find_version(&version))
+                    .map(|v| (v.num.clone(), v.features.clone()));
+                let (num, features) = match crate_version {
+                    Some(cv) => cv,
+                    None => {
+                        warn!("No version of crate `{}` matches {}", name, version);
+                        return future::ok(vec![]).into_box();
+                    }
+                };
+                let cached_deps = match Version::parse(&num) {
+                    Ok(v) => cached_crate_dependencies(
+                        registry_cache.as_ref(), &name, &v, &dependency_filter, max_depth),
+                    Err(_) => Ok(None),
+                };
+                match cached_deps {
+                    Ok(Some(deps)) => future::ok(deps).into_box(),
+                    Ok(None) => {
+                        debug!("Crate {}={} not found locally, using crates.io", name, num);
+                        if max_depth != Some(1) {
+                            warn!("Only direct dependencies of crate {}={} are available \
+                                from crates.io", name, num);
+                        }
+                        crates_io.lookup_dependencies(name.clone(), num.clone())
+                            .map_err(Error::CratesIo)
+                            .map(move |deps| registry_crate_dependencies(
+                                &name, &num, &features, deps.unwrap_or_default(),
+                                &dependency_filter))
+                            .into_box()
+                    }
+                    Err(e) => future::err(e).into_box(),
+                }
+            })
+            .map(move |deps| {
+                let usages = crate_usages(deps, sources, &name_filter);
+                suggest_usage_issues(usages, forges, name_filter, repo_policy)
+            });
+        Ok(Box::new(issues.flatten_stream()))
+    }
+
+    /// Suggest issues for crates from crates.io which depend on the crate with given name.
+    ///
+    /// The dependent crates are looked at starting from the most popular ones.
+    /// Issues which mention our crate by name are flagged as especially relevant.
+    pub fn suggest_reverse_dependency_issues(&self, name: &str) -> IssueStream {
+        debug!("Suggesting issues for reverse dependencies of crate `{}`", name);
+        let sources = Rc::new(self.crates_io_sources());
+        let repo_set = Rc::new(RefCell::new(HashSet::new()));
 
-        // For each repo, search for suitable issues and stream them in a round-robin fashion
-        // (via this hideous amalgamation of fold() + flatten_stream()).
-        Ok(Box::new({
-            let github = self.github.clone();
-            repos.map(move |repo| suggest_repo_issues(&github, repo).map_err(Error::GitHub))
-                // Yes, each cast and each turbofish is necessary here -_-
-                .fold(Box::new(stream::empty()) as Stream<IssuesItem>,
-                    |acc, x| future::ok::<_, Error>(
-                        Box::new(acc.select(x)) as Stream<IssuesItem>,
-                    ))
-                .flatten_stream()
-                .map(|issue_item| {
-                    let issue = issue_item.into();
-                    trace!("Found issue: {}", issue);
-                    issue
+        // Each page of the dependent crates is a separate tier,
+        // so that we only fetch the next one when we run out of issues.
+        let crates_io = self.crates_io.clone();
+        let crate_name = name.to_owned();
+        let pages = stream::unfold(Some(1), move |page| {
+            let page = page?;
+            let crate_name = crate_name.clone();
+            Some(crates_io.lookup_reverse_dependencies(crate_name.clone(), page)
+                .map_err(Error::CratesIo)
+                .map(move |rev_deps| match rev_deps {
+                    Some(rd) => {
+                        if page == 1 {
+                            info!("Crate `{}` has {} reverse dependencies on crates.io",
+                                crate_name, rd.total);
+                        }
+                        (rd.crates, rd.next_page)
+                    }
+                    None => (vec![], None),
+                }))
+        });
+        let (forges, name_filter) = (self.forges.clone(), self.name_filter.clone());
+        let repo_policy = self.repo_policy;
+        let issues = pages.map(move |crates| {
+            let usages = crates.into_iter()
+                .filter(|c| {
+                    let accepted = name_filter.accepts_crate(c);
+                    if !accepted {
+                        debug!("Skipping reverse dependency `{}` as filtered out", c);
+                    }
+                    accepted
+                })
+                .map(|c| DependencyUsage{
+                    dependency: ProjectDependency{
+                        manifest_path: None,
+                        dependency: Dependency::with_version(c, "*"),
+                        depth: 1,
+                        package: None,
+                    },
+                    sources: sources.clone(),
+                    projects: HashSet::new(),
                 })
+                .collect();
+            suggest_dependency_issues(
+                usages, forges.clone(), name_filter.clone(), repo_policy, repo_set.clone())
+        }).flatten();
+
+        // The dependent crate has led us to the issue, but it's our crate that it's relevant to.
+        let crate_name = name.to_owned();
+        let crate_re = crate_mention_regex(name);
+        Box::new(issues.map(move |mut issue| {
+            issue.dependent = issue.dependency.take().map(|dep| dep.name().to_owned());
+            issue.dependency = Some(Dependency::with_version(crate_name.as_str(), "*"));
+            if mentions_crate(&issue, &crate_re) {
+                debug!("Issue {} mentions crate `{}`", issue, crate_name);
+                issue.mentioned_crate = Some(crate_name.clone());
+            }
+            issue
         }))
     }
+
+    /// Sources for the manifests of crates from crates.io that aren't part of any project.
+    fn crates_io_sources(&self) -> ProjectSources {
+        ProjectSources{
+            registries: Registries::new(self.crates_io.clone()),
+            local: LocalSources{
+                registry_cache: self.registry_cache.clone(), ..LocalSources::default()
+            },
+            overrides: self.repo_overrides.clone(),
+            hosts: self.forges.hosts.clone(),
+        }
+    }
+
+    /// List the dependencies of a project with given Cargo.toml manifest,
+    /// along with the sources that their own manifests can be found in.
+    fn list_dependencies(
+        &self, manifest_path: &Path
+    ) -> Result<(Vec<ProjectDependency>, ProjectSources), Error> {
+        debug!("Suggesting dependency issues for manifest path {}", manifest_path.display());
+
+        let cargo_config = CargoConfig::load(cargo_toml::manifest_dir(manifest_path))
+            .unwrap_or_else(|e| {
+                warn!("Error reading Cargo configuration: {}", e);
+                CargoConfig::default()
+            });
+        let metadata_deps = if self.use_cargo_metadata {
+            list_metadata_dependencies(
+                manifest_path, &self.dependency_filter, self.max_depth, &cargo_config)?
+        } else {
+            None
+        };
+        let deps = match metadata_deps {
+            Some(deps) => deps,
+            None => list_project_dependencies(
+                manifest_path, &self.dependency_filter, self.max_depth, &cargo_config)?,
+        };
+        // The workspace root is a canonical path, so the manifest's path must be too
+        // for us to tell whether it's the root itself.
+        let canonical_path = manifest_path.canonicalize()
+            .unwrap_or_else(|_| manifest_path.to_owned());
+        let root_manifest = workspace_root(manifest_path);
+        if root_manifest != canonical_path {
+            info!("Crate belongs to the workspace at {}", root_manifest.display());
+        }
+        let patches = patch::read_patches(&root_manifest)?;
+        let deps: Vec<_> = deps.into_iter()
+            .map(|pd| apply_patches(pd, &patches, &cargo_config))
+            .collect();
+
+        let mut registries = self.registries.clone();
+        for name in cargo_config.registries.keys() {
+            if let Some(index) = cargo_config.registry_index(name) {
+                registries.add(name, index);
+            }
+        }
+        let local = LocalSources::for_project(
+            manifest_path, &cargo_config, self.registry_cache.clone());
+        let overrides = self.project_repo_overrides(&canonical_path, &root_manifest);
+        let hosts = self.forges.hosts.clone();
+        Ok((deps, ProjectSources{registries, local, overrides, hosts}))
+    }
+
+    /// Determine the overrides of dependencies' repositories for given project,
+    /// with those from its manifest(s) taking precedence over the ones we've been given.
+    fn project_repo_overrides(
+        &self, manifest_path: &Path, root_manifest: &Path,
+    ) -> HashMap<String, Option<Repository>> {
+        let mut result = self.repo_overrides.clone();
+        let mut manifests = vec![root_manifest];
+        if manifest_path != root_manifest {
+            manifests.push(manifest_path);
+        }
+        for path in manifests {
+            let overrides = cargo_toml::read_repository_overrides(path).unwrap_or_else(|e| {
+                warn!("Error reading repository overrides from {}: {}", path.display(), e);
+                BTreeMap::new()
+            });
+            for (crate_, spec) in overrides {
+                match parse_repo_override(&crate_, &spec, &self.forges.hosts) {
+                    Ok(repo) => { result.insert(crate_, repo); }
+                    Err(e) => warn!("Ignoring override in {}: {}", path.display(), e),
+                }
+            }
+        }
+        result
+    }
 }
 
 impl fmt::Debug for SuggestedIssuesProducer {
@@ -127,60 +520,617 @@ impl fmt::Debug for SuggestedIssuesProducer {
 }
 
 
+/// A dependency of the project we're suggesting issues for.
+#[derive(Debug)]
+struct ProjectDependency {
+    /// Path to the manifest of the crate that depends on this one,
+    /// or the project's root manifest for transitive dependencies.
+    /// None if the dependency doesn't come from a manifest on disk (e.g. one from crates.io).
+    manifest_path: Option<PathBuf>,
+    dependency: Dependency,
+    /// Distance from the project in the dependency graph
+    /// (1 for direct dependencies, 2 for their dependencies, and so on).
+    depth: usize,
+    /// Manifest of the dependency itself, if it's already known.
+    package: Option<Package>,
+}
+
+/// Dependency used by one or more of the projects we're suggesting issues for.
+struct DependencyUsage {
+    /// The dependency as it occurs closest to the root of any project's dependency graph.
+    dependency: ProjectDependency,
+    /// Where to look for the dependency, as configured for the project it comes from.
+    sources: Rc<ProjectSources>,
+    /// Indices of the projects which use the dependency.
+    projects: HashSet<usize>,
+}
+
+/// Return a key identifying the crate of given dependency across projects.
+fn dependency_key(pd: &ProjectDependency) -> String {
+    let dep = &pd.dependency;
+    match *dep.location() {
+        CrateLocation::Registry{ref registry, ..} => {
+            let registry = registry.as_ref().map(|r| r.as_str()).unwrap_or("crates-io");
+            format!("{}@{}", dep.name(), registry)
+        }
+        CrateLocation::Filesystem{ref path} => {
+            let path = match pd.manifest_path {
+                Some(ref mp) => cargo_toml::manifest_dir(mp).join(path),
+                None => path.clone(),
+            };
+            format!("{}@{}", dep.name(), path.canonicalize().unwrap_or(path).display())
+        }
+        CrateLocation::Git{ref url, ..} => match GitUrl::parse(url) {
+            Some(git_url) => format!("{}@{}", dep.name(), git_url),
+            None => format!("{}@{}", dep.name(), url),
+        },
+    }
+}
+
+/// Turn the dependencies of a single crate into their usages, skipping the filtered out ones.
+fn crate_usages(
+    deps: Vec<ProjectDependency>, sources: ProjectSources, name_filter: &NameFilter,
+) -> Vec<DependencyUsage> {
+    let sources = Rc::new(sources);
+    deps.into_iter()
+        .filter(|pd| {
+            let accepted = name_filter.accepts_crate(pd.dependency.name());
+            if !accepted {
+                debug!("Skipping dependency `{}` as filtered out", pd.dependency.name());
+            }
+            accepted
+        })
+        .map(|pd| DependencyUsage{
+            dependency: pd,
+            sources: sources.clone(),
+            projects: Some(0).into_iter().collect(),
+        })
+        .collect()
+}
+
+/// Suggest issues for all given dependencies.
+fn suggest_usage_issues(
+    usages: Vec<DependencyUsage>, forges: Forges, name_filter: NameFilter,
+    repo_policy: RepoPolicy,
+) -> IssueStream {
+    // Crates used by more of the projects are more relevant, and so are those
+    // closer to the root of the dependency graph, so we look at all the dependencies
+    // of a given weight & depth before moving on to the others.
+    let mut tiers: Vec<Vec<_>> = usages.into_iter()
+        .sorted_by_key(|u| (Reverse(u.projects.len()), u.dependency.depth))
+        .group_by(|u| (Reverse(u.projects.len()), u.dependency.depth)).into_iter()
+        .map(|(_, tier)| tier.collect())
+        .collect();
+    for tier in &mut tiers {
+        thread_rng().shuffle(tier);
+    }
+
+    let repo_set = Rc::new(RefCell::new(HashSet::new()));
+    let tier_streams: Vec<_> = tiers.into_iter()
+        .map(|tier| suggest_dependency_issues(
+            tier, forges.clone(), name_filter.clone(), repo_policy, repo_set.clone()))
+        .collect();
+    Box::new(stream::iter_ok::<_, Error>(tier_streams).flatten())
+}
+
+/// Suggest issues for given dependencies,
+/// skipping the repositories that we've already suggested issues from.
+fn suggest_dependency_issues(
+    deps: Vec<DependencyUsage>, forges: Forges, name_filter: NameFilter,
+    repo_policy: RepoPolicy, repo_set: Rc<RefCell<HashSet<Repository>>>,
+) -> IssueStream {
+    // Determine the repositories corresponding to dependent crates.
+    // In most cases, this means read the package/repository entries
+    // from the manifests of those crates by looking at Cargo cache or talking to crates.io
+    // (or whichever registry the crate comes from).
+    let repos = stream::iter_ok(deps)
+        .and_then(move |usage| {
+            let dep = usage.dependency.dependency.clone();
+            let repos = repos_for_project_dependency(&usage.dependency, &usage.sources);
+            repos.map(move |repos| {
+                    if repos.is_empty() {
+                        debug!("Couldn't determine the repository of crate `{}`", dep.name());
+                    }
+                    let repos: Vec<_> = repos.into_iter().map(|r| (r, dep.clone())).collect();
+                    stream::iter_ok(repos)
+                })
+                .map_err(Error::CratesIo)
+        })
+        .flatten()
+        .filter({
+            let (name_filter, repo_set) = (name_filter.clone(), repo_set.clone());
+            move |&(ref repo, _)| is_new_repo(repo, &name_filter, &repo_set)
+        })
+        // Manifests may point to repositories which have since been renamed or transferred,
+        // so we need their current names to avoid looking at the same repository twice.
+        // We also skip the archived repositories, and look at the upstream ones of forks.
+        .and_then({
+            let forges = forges.clone();
+            move |(repo, dep)| {
+                let (name_filter, repo_set) = (name_filter.clone(), repo_set.clone());
+                resolve_repo(&forges, &repo, dep.name(), repo_policy).map(move |repos| {
+                    let repos: Vec<_> = repos.into_iter()
+                        .filter(|r| *r == repo || is_new_repo(r, &name_filter, &repo_set))
+                        .map(|r| (r, dep.clone()))
+                        .collect();
+                    stream::iter_ok(repos)
+                })
+            }
+        })
+        .flatten();
+
+    // For each repo, search for suitable issues and stream them in a round-robin fashion
+    // (via this hideous amalgamation of fold() + flatten_stream()).
+    Box::new({
+        repos.map(move |(repo, dep)| {
+                let issues: IssueStream = match repo.subdir.clone() {
+                    // Issues of the particular crate from a monorepo are the most relevant,
+                    // so they go before the other ones from the entire repository.
+                    Some(subdir) => {
+                        let crate_ = dep.name().to_owned();
+                        let crate_re = crate_mention_regex(&crate_);
+                        let issues = suggest_repo_issues(&forges, repo).collect()
+                            .map(move |issues| {
+                                let (crate_issues, other_issues): (Vec<_>, Vec<_>) =
+                                    issues.into_iter().partition(|issue| {
+                                        is_crate_issue(issue, &crate_, &crate_re, &subdir)
+                                    });
+                                debug!("{} of the issues concern crate `{}` in particular",
+                                    crate_issues.len(), crate_);
+                                stream::iter_ok(crate_issues.into_iter().chain(other_issues))
+                            });
+                        Box::new(issues.flatten_stream())
+                    }
+                    None => suggest_repo_issues(&forges, repo),
+                };
+                issues
+                    .map(move |mut issue| {
+                        issue.dependency = Some(dep.clone());
+                        trace!("Found issue: {}", issue);
+                        issue
+                    })
+            })
+            // Yes, each cast and each turbofish is necessary here -_-
+            .fold(Box::new(stream::empty()) as Stream<Issue>,
+                |acc, x| future::ok::<_, Error>(
+                    Box::new(acc.select(x)) as Stream<Issue>,
+                ))
+            .flatten_stream()
+    })
+}
+
+/// Whether we should look at given repository,
+/// i.e. it isn't filtered out and we haven't reported on it already.
+fn is_new_repo(
+    repo: &Repository, name_filter: &NameFilter, repo_set: &RefCell<HashSet<Repository>>,
+) -> bool {
+    // Skip the filtered out repos before asking their forges about them.
+    if !name_filter.accepts_repo(repo) {
+        debug!("Skipping repository {} as filtered out", repo);
+        return false;
+    }
+    repo_set.borrow_mut().insert(repo.clone())
+}
+
+/// List the dependencies of all crates in the project with given Cargo.toml.
+///
+/// If the manifest is a workspace root, dependencies of all its members are included.
+/// Dependencies shared between crates are only listed once,
+/// and path dependencies on other members of the workspace are omitted.
+///
+/// Only the direct dependencies matching given filter are included,
+/// together with their own dependencies up to `max_depth` (as read from Cargo.lock).
+fn list_project_dependencies(
+    manifest_path: &Path, filter: &DependencyFilter, max_depth: Option<usize>,
+    cargo_config: &CargoConfig,
+) -> Result<Vec<ProjectDependency>, Error> {
+    let members = cargo_toml::list_workspace_members(manifest_path)?;
+    let member_dirs: HashSet<_> = members.iter()
+        .filter_map(|m| cargo_toml::manifest_dir(m).canonicalize().ok())
+        .collect();
+
+    let mut member_deps = vec![];
+    for member in members {
+        for dep in cargo_toml::list_dependencies(&member, filter)? {
+            if let CrateLocation::Filesystem{ref path} = *dep.location() {
+                let dep_dir = cargo_toml::manifest_dir(&member).join(path).canonicalize().ok();
+                if dep_dir.map(|d| member_dirs.contains(&d)).unwrap_or(false) {
+                    trace!("Skipping dependency `{}` as it's a workspace member", dep.name());
+                    continue;
+                }
+            }
+            member_deps.push(ProjectDependency{
+                manifest_path: Some(member.clone()), dependency: dep, depth: 1, package: None,
+            });
+        }
+    }
+
+    let lockfile = cargo_lock::find_lockfile(manifest_path).and_then(|path| {
+        cargo_lock::read_lockfile(&path).map_err(|e| {
+            warn!("Error reading {}: {}", path.display(), e);
+        }).ok()
+    });
+    let lockfile = match lockfile {
+        Some(lf) => lf,
+        None => {
+            if max_depth != Some(1) {
+                warn!("Cannot find Cargo.lock for {}, only direct dependencies will be used",
+                    manifest_path.display());
+                info!("Run `cargo generate-lockfile` to make indirect dependencies available.");
+            }
+            let (result, _) = merge_member_dependencies(member_deps, member_dirs.len());
+            return Ok(result);
+        }
+    };
+
+    // Use the exact versions that Cargo has resolved the direct dependencies to.
+    for pd in &mut member_deps {
+        let locked_version = match *pd.dependency.location() {
+            CrateLocation::Registry{ref version, ..} =>
+                lockfile.locked_version(pd.dependency.name(), version),
+            _ => None,
+        };
+        if let Some(version) = locked_version {
+            trace!("Dependency `{}` is locked at version {}", pd.dependency.name(), version);
+            pd.dependency = pd.dependency.clone().with_locked_version(version);
+        }
+    }
+
+    let (mut result, mut seen_keys) = merge_member_dependencies(member_deps, member_dirs.len());
+
+    // Include the transitive dependencies if requested.
+    if max_depth == Some(1) {
+        return Ok(result);
+    }
+    let transitive: Vec<_> = {
+        let direct = result.iter()
+            .map(|pd| (pd.dependency.name(), pd.dependency.locked_version()));
+        lockfile.reachable_packages(direct, max_depth.map(|md| md - 1)).into_iter()
+            .filter_map(|(depth, package)| package.to_dependency(cargo_config).map(|dep| {
+                ProjectDependency{
+                    manifest_path: Some(manifest_path.to_owned()),
+                    dependency: dep,
+                    depth: depth + 1,
+                    package: None,
+                }
+            }))
+            .collect()
+    };
+    debug!("{} indirect dependencies found in Cargo.lock", transitive.len());
+    for pd in transitive {
+        if seen_keys.insert(member_dependency_key(&pd)) {
+            result.push(pd);
+        }
+    }
+    Ok(result)
+}
+
+/// Merge the dependencies of workspace members that refer to the same crate,
+/// returning them along with the keys (from `member_dependency_key`) of all of them.
+fn merge_member_dependencies(
+    deps: Vec<ProjectDependency>, member_count: usize,
+) -> (Vec<ProjectDependency>, HashSet<String>) {
+    let mut result: Vec<ProjectDependency> = vec![];
+    let mut indices = HashMap::new();
+    for pd in deps {
+        let key = member_dependency_key(&pd);
+        match indices.get(&key) {
+            Some(&i) => {
+                let merged = result[i].dependency.clone().merged_with(&pd.dependency);
+                result[i].dependency = merged;
+            }
+            None => {
+                indices.insert(key, result.len());
+                result.push(pd);
+            }
+        }
+    }
+    if member_count > 1 {
+        debug!("{} distinct dependencies found across {} workspace members",
+            result.len(), member_count);
+    }
+    let keys = indices.keys().cloned().collect();
+    (result, keys)
+}
+
+/// Return a key identifying the dependencies of workspace members which refer to the same crate,
+/// i.e. to the same version of it from the same source.
+fn member_dependency_key(pd: &ProjectDependency) -> String {
+    let key = dependency_key(pd);
+    match *pd.dependency.location() {
+        CrateLocation::Registry{ref version, ..} => match pd.dependency.locked_version() {
+            Some(locked) => format!("{}={}", key, locked),
+            None => format!("{} {}", key, version),
+        },
+        _ => key,
+    }
+}
+
+/// Mark the dependency as patched if any of the [patch] or [replace] entries apply to it.
+fn apply_patches(
+    pd: ProjectDependency, patches: &[Patch], cargo_config: &CargoConfig
+) -> ProjectDependency {
+    let patch = patches.iter().find(|p| {
+        p.applies_to(&pd.dependency, cargo_config) || p.is_replacement(&pd.dependency)
+    });
+    match patch {
+        Some(patch) => {
+            debug!("Dependency `{}` is patched with {}", pd.dependency.name(), patch.replacement);
+            ProjectDependency{
+                dependency: patch.apply(pd.dependency, cargo_config),
+                // We need to look up both the upstream crate and its replacement,
+                // so the manifest of just one of them isn't enough.
+                package: None,
+                ..pd
+            }
+        }
+        None => pd,
+    }
+}
+
+/// List the dependencies of the project with given Cargo.toml using `cargo metadata`.
+///
+/// Returns None if Cargo cannot be found, in which case the caller should fall back
+/// to `list_project_dependencies`.
+fn list_metadata_dependencies(
+    manifest_path: &Path, filter: &DependencyFilter, max_depth: Option<usize>,
+    cargo_config: &CargoConfig,
+) -> Result<Option<Vec<ProjectDependency>>, Error> {
+    let metadata = match cargo_metadata::read_metadata(manifest_path, &filter.features) {
+        Ok(m) => m,
+        Err(cargo_metadata::Error::Io(ref e)) if e.kind() == io::ErrorKind::NotFound => {
+            warn!("Cargo not found, reading the manifests directly instead");
+            return Ok(None);
+        }
+        Err(e) => return Err(Error::Metadata(e)),
+    };
+    let deps = metadata.list_dependencies(manifest_path, filter, max_depth, cargo_config);
+    debug!("{} dependencies found with `cargo metadata`", deps.len());
+    Ok(Some(deps.into_iter().map(|rd| ProjectDependency{
+        manifest_path: Some(rd.manifest_path),
+        dependency: rd.dependency,
+        depth: rd.depth,
+        package: Some(rd.package),
+    }).collect()))
+}
+
+/// Return the version that given requirement is for, if it only matches a single one.
+fn exact_version(req: &VersionReq) -> Option<Version> {
+    // VersionReq doesn't expose its predicates, but it displays an exact one as "= 1.2.3".
+    let req = req.to_string();
+    if !req.starts_with('=') {
+        return None;
+    }
+    Version::parse(req[1..].trim()).ok()
+}
+
+/// List the crate of given exact version from a registry, together with its dependencies,
+/// provided its manifest can be found in Cargo's registry cache.
+fn cached_crate_dependencies(
+    registry_cache: Option<&RegistryCache>, name: &str, version: &Version,
+    filter: &DependencyFilter, max_depth: Option<usize>,
+) -> Result<Option<Vec<ProjectDependency>>, Error> {
+    let index_hosts: Vec<_> = vec![CRATES_IO_INDEX, CRATES_IO_SPARSE_INDEX].into_iter()
+        .filter_map(cargo_config::index_host).collect();
+    let manifest_path = match registry_cache
+            .and_then(|rc| rc.find_manifest(name, &VersionReq::exact(version), &index_hosts)) {
+        Some(path) => path,
+        None => return Ok(None),
+    };
+    info!("Using cached crate manifest {}", manifest_path.display());
+    let package = cargo_toml::read_package(&manifest_path)?;
+    let mut deps = list_project_dependencies(
+        &manifest_path, filter, max_depth, &CargoConfig::default())?;
+    deps.insert(0, ProjectDependency{
+        manifest_path: Some(manifest_path.clone()),
+        dependency: Dependency::with_version(name, format!("={}", version)),
+        depth: 0,
+        package: Some(package),
+    });
+    Ok(Some(deps))
+}
+
+/// List the crate of given version from a registry, together with its direct dependencies
+/// as given by the registry's API.
+fn registry_crate_dependencies(
+    name: &str, version: &str, features: &BTreeMap<String, Vec<String>>,
+    deps: Vec<crates_io::CrateDependency>, filter: &DependencyFilter,
+) -> Vec<ProjectDependency> {
+    debug!("Crate {}={} has {} dependencies on crates.io", name, version, deps.len());
+    let deps: Vec<_> = deps.iter()
+        .filter_map(|d| d.to_dependency())
+        .filter(|d| filter.accepts(d.kind(), d.target()))
+        .collect();
+    let deps = cargo_toml::filter_optional_dependencies(name, features, deps, filter);
+    // There is no manifest of the crate on disk, so none of the dependencies has one either.
+    Some(Dependency::with_version(name, format!("={}", version)))
+        .into_iter().chain(deps)
+        .map(|dep| ProjectDependency{
+            manifest_path: None,
+            depth: if dep.name() == name { 0 } else { 1 },
+            dependency: dep,
+            package: None,
+        })
+        .collect()
+}
+
+
 /// Error that can occur while producing suggested issues.
 #[derive(Debug, Error)]
 pub enum Error {
     #[error(msg = "error reading crate manifest")]
     Manifest(cargo_toml::Error),
+    #[error(msg = "error running `cargo metadata`")]
+    Metadata(cargo_metadata::Error),
     #[error(msg = "error contacting crates.io")]
     CratesIo(crates_io::Error),
-    #[error(msg = "error contacting github.com")]
+    #[error(msg = "error contacting GitHub")]
     GitHub(hubcaps::Error),
+    #[error(msg = "error contacting GitLab", no_from)]
+    GitLab(gitlab::Error),
+    #[error(msg = "error contacting Gitea", no_from)]
+    Gitea(gitea::Error),
+    #[error(msg = "error contacting SourceHut", no_from)]
+    SourceHut(sourcehut::Error),
+    #[error(msg = "error contacting Bitbucket", no_from)]
+    Bitbucket(bitbucket::Error),
 }
 
 
 // Finding repositories of crate dependencies
 
-lazy_static! {
-    static ref GITHUB_GIT_HTTPS_URL_RE: Regex = Regex::new(
-        r#"https?://(www\.)?github\.com/(?P<owner>\w+)/(?P<name>[^.]+)(\.git)?"#
-    ).unwrap();
-    static ref GITHUB_GIT_SSH_URL_RE: Regex = Regex::new(
-        r#"git@github\.com:(?P<owner>\w+)/(?P<name>[^.]+)\.git"#
-    ).unwrap();
+/// Places where the manifests of a project's dependencies can be found.
+struct ProjectSources {
+    /// Registries configured for the project.
+    registries: Registries<HttpsConnector>,
+    /// Local replacements and caches of registries.
+    local: LocalSources,
+    /// Repositories of crates as given by the user (None meaning the crate should be ignored),
+    /// which take precedence over any of the above.
+    overrides: HashMap<String, Option<Repository>>,
+    /// Forge hosts that the repositories of crates are recognized on.
+    hosts: ForgeHosts,
+}
+
+/// Places where manifests of dependencies can be found without going online.
+#[derive(Clone, Debug, Default)]
+struct LocalSources {
+    /// Vendored crates and local registries that replace the actual crate sources.
+    vendored: VendoredSources,
+    /// Cargo's cache of downloaded crates.
+    registry_cache: Option<RegistryCache>,
+}
+
+impl LocalSources {
+    fn for_project(
+        manifest_path: &Path, cargo_config: &CargoConfig, registry_cache: Option<RegistryCache>,
+    ) -> Self {
+        // Crates are vendored for the entire workspace, not its individual members.
+        let root_manifest = workspace_root(manifest_path);
+        let vendored = VendoredSources::for_project(
+            cargo_toml::manifest_dir(&root_manifest), cargo_config);
+        if !vendored.is_empty() {
+            debug!("Using vendored sources: {:?}", vendored);
+        }
+
+        if registry_cache.is_none() {
+            warn!("Cannot find Cargo's registry cache directory.");
+        }
+        LocalSources{vendored, registry_cache}
+    }
+
+    /// Find the [package] manifest section of given crate from a registry.
+    fn find_package(
+        &self, crate_: &str, version: &VersionReq, locked_version: Option<&Version>,
+        index_hosts: &[String],
+    ) -> Option<Package> {
+        // Vendored crates are the ones that Cargo actually uses, so they take precedence.
+        self.vendored.find_package(crate_, version, locked_version)
+            .or_else(|| self.registry_cache.as_ref()?.find_package(
+                crate_, version, locked_version, index_hosts))
+    }
+}
+
+/// Value of a repository override which makes us ignore the crate instead.
+const NO_REPOSITORY: &str = "none";
+
+/// Parse the repository override of given crate, which is None if the crate should be ignored.
+fn parse_repo_override(
+    crate_: &str, spec: &str, hosts: &ForgeHosts,
+) -> Result<Option<Repository>, String> {
+    if spec.trim().eq_ignore_ascii_case(NO_REPOSITORY) {
+        return Ok(None);
+    }
+    Repository::parse(spec, hosts).map(Some)
+        .map_err(|e| format!("invalid repository `{}` of crate `{}`: {}", spec, crate_, e))
 }
 
-lazy_static! {
-    // TODO: verify this is where Cargo keeps the registry on Windows
-    static ref CARGO_REGISTRY_CACHE_DIR: Option<PathBuf> = dirs::home_dir()
-        .map(|home| home.join(".cargo/registry/src"));
+/// Determine the repositories relevant to a dependency of the project with given sources.
+fn repos_for_project_dependency(
+    pd: &ProjectDependency, sources: &ProjectSources,
+) -> Box<dyn Future<Item=Vec<Repository>, Error=crates_io::Error>> {
+    let dep = &pd.dependency;
+    // Overrides take precedence over whatever the crate itself says.
+    if let Some(repo) = sources.overrides.get(dep.name()) {
+        match *repo {
+            Some(ref r) => debug!("Repository of crate `{}` overridden as {}", dep.name(), r),
+            None => debug!("Skipping crate `{}` as its repository is overridden with `{}`",
+                dep.name(), NO_REPOSITORY),
+        }
+        return Box::new(future::ok(repo.iter().cloned().collect()));
+    }
+    // With `cargo metadata`, we already have the dependency's manifest.
+    match pd.package {
+        Some(ref p) =>
+            Box::new(future::ok(repo_for_package(p, &sources.hosts).into_iter().collect())),
+        None => repos_for_dependency(
+            pd.manifest_path.as_ref().map(|mp| mp.as_path()),
+            &sources.registries, &sources.local, &sources.hosts, dep),
+    }
+}
+
+/// Determine the repositories relevant to given dependency.
+///
+/// For patched dependencies, this includes the repository of the crate that replaces it
+/// in addition to the upstream one.
+fn repos_for_dependency<C: Clone + Connect>(
+    manifest_path: Option<&Path>, registries: &Registries<C>, local_sources: &LocalSources,
+    hosts: &ForgeHosts, dep: &Dependency,
+) -> Box<dyn Future<Item=Vec<Repository>, Error=crates_io::Error>> {
+    let upstream = repo_for_dependency(manifest_path, registries, local_sources, hosts, dep);
+    match dep.patch() {
+        Some(patch) => {
+            // Paths of patches are already absolute, so the manifest path doesn't matter here.
+            let fork = repo_for_dependency(manifest_path, registries, local_sources, hosts, patch);
+            Box::new(upstream.join(fork).map(|(upstream, fork)| {
+                upstream.into_iter().chain(fork).unique().collect()
+            }))
+        }
+        None => Box::new(upstream.map(|repo| repo.into_iter().collect())),
+    }
 }
 
-fn repo_for_dependency<P: AsRef<Path>, C: Clone + Connect>(
-    manifest_path: P, crates_io: &CratesIoClient<C>, dep: &Dependency
+/// Determine the repository of given dependency of the crate with given manifest (if any).
+fn repo_for_dependency<C: Clone + Connect>(
+    manifest_path: Option<&Path>, registries: &Registries<C>, local_sources: &LocalSources,
+    hosts: &ForgeHosts, dep: &Dependency,
 ) -> Box<dyn Future<Item=Option<Repository>, Error=crates_io::Error>> {
     match *dep.location() {
-        CrateLocation::Registry{ref version} => {
-            // Check the local Cargo cache first for the dependent crate's manifest.
-            // Otherwise, fall back to querying crates.io.
-            if let Some(package) = read_cached_manifest(dep.name(), version) {
+        CrateLocation::Registry{ref registry, ref version} => {
+            let registry = registry.as_ref().map(|r| r.as_str());
+
+            // Check the vendored sources and local Cargo cache first
+            // for the dependent crate's manifest.
+            // Otherwise, fall back to querying the registry.
+            let index_hosts: Vec<_> = match registry {
+                None => vec![CRATES_IO_INDEX, CRATES_IO_SPARSE_INDEX],
+                Some(_) => registries.index(registry).into_iter().collect(),
+            }.into_iter().filter_map(cargo_config::index_host).collect();
+            let local_package = local_sources.find_package(
+                dep.name(), version, dep.locked_version(), &index_hosts);
+            if let Some(package) = local_package {
                 return Box::new(future::ok(
-                    package.repository.as_ref().and_then(Repository::from_http_url)
+                    package.repository.as_ref().and_then(|url| Repository::from_url(url, hosts))
                 ));
             }
-            debug!("Dependency {}={} not found in local Cargo cache", dep.name(), version);
+            debug!("Dependency {}={} not found locally", dep.name(), version);
+            let hosts = hosts.clone();
             Box::new(
-                crates_io.lookup_crate(dep.name().to_owned()).map(|opt_c| {
+                registries.lookup_crate(registry, dep.name().to_owned()).map(move |opt_c| {
                     // Some crates list their GitHub URLs only as "homepage" in the manifest,
                     // so we'll try that in addition to the more appropriate "repository".
                     let crate_ = opt_c?;
-                    crate_.metadata.repo_url.as_ref().and_then(Repository::from_http_url)
-                        .or_else(|| Repository::from_http_url(crate_.metadata.homepage_url.as_ref()?))
+                    let metadata = &crate_.metadata;
+                    metadata.repo_url.as_ref().and_then(|url| Repository::from_url(url, &hosts))
+                        .or_else(|| Repository::from_url(metadata.homepage_url.as_ref()?, &hosts))
                 })
             )
         }
         CrateLocation::Filesystem{ref path} => Box::new(future::ok({
-            manifest_path.as_ref().parent()
+            if manifest_path.is_none() {
+                warn!("Cannot resolve path=... dependency `{}` without a manifest", dep.name());
+            }
+            manifest_path.and_then(|mp| mp.parent())
                 .and_then(|manifest_dir| manifest_dir.join(path).canonicalize().map_err(|e| {
                     warn!("Error resolving path=... dependency `{}`: {}", dep.name(), e); e
                 }).ok())
@@ -192,83 +1142,37 @@ fn repo_for_dependency<P: AsRef<Path>, C: Clone + Connect>(
                                 dep.name(), e); e
                         }).ok()
                 })
-                .and_then(|p| {
-                    // Like above, try `repository` followed by `homepage`.
-                    p.repository.as_ref().and_then(Repository::from_http_url)
-                        .or_else(|| p.homepage.as_ref().and_then(Repository::from_http_url))
-                })
+                .and_then(|p| repo_for_package(&p, hosts))
         })),
-        CrateLocation::Git{ref url} => {
-            trace!("Trying to parse Git URL as GitHub one: {}", url);
+        CrateLocation::Git{ref url, ..} => {
+            trace!("Trying to parse Git URL as a forge repository: {}", url);
             Box::new(future::ok(
-                GITHUB_GIT_HTTPS_URL_RE.captures(url)
-                    .or_else(|| GITHUB_GIT_SSH_URL_RE.captures(url))
-                    .map(|caps| {
-                        let repo = Repository::new(&caps["owner"], &caps["name"]);
-                        debug!("Git URL {} recognized as repo: {}", url, repo);
-                        repo
-                    })
+                Repository::from_url(url, hosts).map(|repo| {
+                    debug!("Git URL {} recognized as repo: {}", url, repo);
+                    repo
+                })
             ))
         },
     }
 }
 
-fn read_cached_manifest<N>(crate_: N, version: &VersionReq) -> Option<Package>
-    where N: AsRef<str>
-{
-    let crate_ = crate_.as_ref();
-    trace!("Trying to find cached manifest of crate {}={}", crate_, version);
-
-    let cache_root = match CARGO_REGISTRY_CACHE_DIR.as_ref() {
-        Some(cr) => cr,
-        None => {
-            warn!("Cannot find Cargo's registry cache directory.");
-            return None;
-        }
-    };
-
-    // Find all cached versions of the crate and pick the newest matching one.
-    let pattern = format!("{}/*/{}-*", cache_root.display(), crate_);
-    trace!("Globbing with pattern: {}", pattern);
-    let manifest_path = glob(&pattern).unwrap()
-        .filter_map(|res| {
-            if let Err(ref e) = res { trace!("Error while globbing: {}", e); }
-            res.ok()
-        })
-        .filter_map(|dir| {
-            // Extract the cached crate version and match it with the dependency requirement.
-            let version_suffix = dir.file_name().unwrap().to_str().unwrap()
-                .rsplit('-').next().unwrap();
-            let cached_version = Version::parse(version_suffix).unwrap_or_else(|e| {
-                panic!("Failed to parse crate version `{}` from cached path {}: {}",
-                    version_suffix, dir.display(), e);
-            });
-            if version.matches(&cached_version) {
-                Some((cached_version, dir.to_owned()))
-            } else {
-                None
-            }
-        })
-        .sorted_by_key(|&(ref v, _)| v.clone()).map(|(_, d)| d)
-        .next().or_else(|| {
-            debug!("Crate {}-{} not found in Cargo cache", crate_, version); None
-        })?
-        .join("Cargo.toml");
-
-    if manifest_path.exists() {
-        debug!("Cached manifest found at {}", manifest_path.display());
-    } else {
-        warn!("Found cached crate {}={} but it's missing its manifest", crate_, version);
-        return None;
-    }
+/// Return the root manifest of the workspace that the crate with given manifest belongs to,
+/// or the manifest itself if it's not part of a workspace.
+fn workspace_root(manifest_path: &Path) -> PathBuf {
+    cargo_toml::find_workspace_root(manifest_path).ok()
+        .and_then(|root| root)
+        .unwrap_or_else(|| manifest_path.to_owned())
+}
 
-    cargo_toml::read_package(manifest_path).map_err(|e| {
-        warn!("Error while reading cached manifest of {}={}: {}", crate_, version, e);
-    }).ok()
+/// Determine the repository of a crate from its manifest.
+fn repo_for_package(package: &Package, hosts: &ForgeHosts) -> Option<Repository> {
+    // Like with crates.io, try `repository` followed by `homepage`.
+    package.repository.as_ref().and_then(|url| Repository::from_url(url, hosts))
+        .or_else(|| package.homepage.as_ref().and_then(|url| Repository::from_url(url, hosts)))
 }
 
 
-// Searching suitable issues on GitHub
+// Searching suitable issues on GitHub & other forges
 
 const GITHUB_API_ROOT: &str = "https://api.github.com";
 
@@ -281,24 +1185,179 @@ const ISSUE_LABELS: &[&str] = &[
     "beginner",
 ];
 
-/// Provide suggested issues specifically from given GitHub repo.
-fn suggest_repo_issues<C: Clone + Connect>(
-    github: &Github<C>, repo: Repository
-) -> Box<dyn StdStream<Item=IssuesItem, Error=HubcapsError>> {
-    let result = Box::new(
-        // Filter pending issues to match one of the labels we're looking for.
-        pending_issues(github, repo).filter(|ii| ii.labels.iter().any(|l| {
-            let label = canonicalize_label(&l.name);
-            ISSUE_LABELS.contains(&label.as_str())
-        }))
-    );
+/// What to do with the repositories that forges say have been archived or forked.
+#[derive(Clone, Copy, Debug)]
+struct RepoPolicy {
+    forks: ForkPolicy,
+    skip_archived: bool,
+}
+
+impl Default for RepoPolicy {
+    fn default() -> Self {
+        RepoPolicy{forks: ForkPolicy::default(), skip_archived: true}
+    }
+}
+
+/// Clients for the forges that we're looking for issues on.
+#[derive(Clone)]
+struct Forges {
+    /// GitHub clients for github.com and any GitHub Enterprise hosts, keyed by the host.
+    github: HashMap<String, GitHubClient<HttpsConnector>>,
+    gitlab: GitLabClient<HttpsConnector>,
+    gitea: GiteaClient<HttpsConnector>,
+    sourcehut: SourceHutClient<HttpsConnector>,
+    bitbucket: BitbucketClient<HttpsConnector>,
+    /// HTTP client to create the clients for any additional hosts with.
+    http: HyperClient<HttpsConnector>,
+    /// Forge instances whose repositories we recognize, including the self-hosted ones.
+    hosts: ForgeHosts,
+}
+
+/// Determine the repositories to look at in place of given one,
+/// based on what its forge has to say about it.
+///
+/// This means following repositories which have been renamed or transferred to their current
+/// names, skipping the archived ones, and looking at the upstream repositories of forks.
+/// Only GitHub repositories are checked.
+fn resolve_repo(
+    forges: &Forges, repo: &Repository, crate_: &str, policy: RepoPolicy,
+) -> Box<dyn Future<Item=Vec<Repository>, Error=Error>> {
+    let github = match forges.github.get(&repo.host) {
+        Some(github) if repo.forge == Forge::GitHub => github,
+        _ => return future::ok(vec![repo.clone()]).into_box(),
+    };
+    let (repo, crate_) = (repo.clone(), crate_.to_owned());
+    github.lookup_repo(&repo).then(move |result| Ok(match result {
+        Ok(Some(item)) => {
+            let current = Repository{
+                owner: item.owner.login.clone(), name: item.name.clone(), ..repo.clone()
+            };
+            // GitHub names are case-insensitive, so it's only worth pointing out actual changes.
+            if !current.to_string().eq_ignore_ascii_case(&repo.to_string()) {
+                info!(concat!("Repository {} has moved to {}, ",
+                    "so crate `{}` has a stale `repository` URL in its manifest"),
+                    repo, current, crate_);
+            }
+            if item.archived && policy.skip_archived {
+                warn!("Skipping repository {} of crate `{}` as it's been archived",
+                    current, crate_);
+                return Ok(vec![]);
+            }
+            let upstream = match item.parent {
+                Some(ref parent) if item.fork => {
+                    let upstream = Repository{
+                        owner: parent.owner.login.clone(), name: parent.name.clone(),
+                        ..repo.clone()
+                    };
+                    debug!("Repository {} of crate `{}` is a fork of {}",
+                        current, crate_, upstream);
+                    if parent.archived && policy.skip_archived {
+                        warn!("Skipping repository {} (upstream of {}) as it's been archived",
+                            upstream, current);
+                        None
+                    } else {
+                        Some(upstream)
+                    }
+                }
+                _ => None,
+            };
+            match (upstream, policy.forks) {
+                (Some(upstream), ForkPolicy::Upstream) => vec![upstream],
+                (Some(upstream), ForkPolicy::Both) => vec![current, upstream],
+                _ => vec![current],
+            }
+        }
+        Ok(None) => vec![repo],
+        Err(e) => {
+            warn!("Failed to look up repository {}: {}", repo, e);
+            vec![repo]
+        }
+    })).into_box()
+}
+
+/// Provide suggested issues specifically from given repo.
+fn suggest_repo_issues(forges: &Forges, repo: Repository) -> IssueStream {
+    let pending: IssueStream = match repo.forge {
+        Forge::GitHub => match forges.github.get(&repo.host) {
+            Some(github) => Box::new(
+                github.pending_issues(repo).map(Issue::from).map_err(Error::GitHub)
+            ),
+            None => {
+                warn!("No GitHub API configured for host {}, skipping {}", repo.host, repo);
+                Box::new(stream::empty())
+            }
+        },
+        Forge::GitLab => Box::new(
+            forges.gitlab.pending_issues(repo.clone(), ISSUE_LABELS)
+                .map(move |ii| ii.into_issue(repo.clone()))
+                .map_err(Error::GitLab)
+        ),
+        Forge::Gitea => Box::new(
+            forges.gitea.pending_issues(repo.clone(), ISSUE_LABELS)
+                .map(move |ii| ii.into_issue(repo.clone()))
+                .map_err(Error::Gitea)
+        ),
+        Forge::SourceHut => Box::new(
+            forges.sourcehut.pending_issues(repo.clone())
+                .map(move |ti| ti.into_issue(repo.clone()))
+                .map_err(Error::SourceHut)
+        ),
+        Forge::Bitbucket => Box::new(
+            forges.bitbucket.pending_issues(repo.clone())
+                .map(move |ii| ii.into_issue(repo.clone()))
+                .map_err(Error::Bitbucket)
+        ),
+    };
     if log_enabled!(Trace) {
         trace!("Accepted issue labels: {}", ISSUE_LABELS.iter().format(", "));
     }
-    result
+    // Filter pending issues to match one of the labels we're looking for.
+    Box::new(pending.filter(|issue| has_issue_label(issue.labels.iter().map(|l| l.as_str()))))
+}
+
+/// Whether any of given issue labels is one of those we're looking for.
+fn has_issue_label<'l, I: IntoIterator<Item=&'l str>>(labels: I) -> bool {
+    labels.into_iter().any(|l| ISSUE_LABELS.contains(&canonicalize_label(l).as_str()))
+}
+
+/// Prefixes of the labels that monorepos use to mark issues concerning a particular crate.
+const CRATE_LABEL_PREFIXES: &[&str] = &["a-", "crate:", "crate-", "crate/"];
+
+/// Whether given issue from a monorepo concerns the crate with given name
+/// that lives in given subdirectory of the repository.
+///
+/// This is the case if the issue has a label for the crate (like "A-foo" or "crate:foo")
+/// or mentions it by name, as matched by the crate's `crate_mention_regex`.
+fn is_crate_issue(issue: &Issue, crate_: &str, crate_re: &Regex, subdir: &str) -> bool {
+    // Crate labels sometimes use the name of the crate's directory rather than the crate itself.
+    let dir_name = subdir.rsplit('/').next().unwrap_or(subdir);
+    issue.labels.iter().any(|l| is_crate_label(l, crate_) || is_crate_label(l, dir_name))
+        || mentions_crate(issue, crate_re)
+}
+
+/// Whether given issue label refers to the crate with given name.
+fn is_crate_label(label: &str, crate_: &str) -> bool {
+    let label = label.trim().to_lowercase().replace('_', "-");
+    let name = CRATE_LABEL_PREFIXES.iter()
+        .find(|&&p| label.starts_with(p))
+        .map(|p| label[p.len()..].trim())
+        .unwrap_or(&label);
+    name == crate_.to_lowercase().replace('_', "-")
 }
 
-/// Convert a GitHub label to its "canonical" form for comparison purposes.
+/// Create the regex matching the mentions of crate with given name in text.
+fn crate_mention_regex(crate_: &str) -> Regex {
+    // Hyphens and underscores in crate names are often used interchangeably.
+    let name = crate_.split(|c| c == '-' || c == '_').map(regex::escape).join("[-_]");
+    Regex::new(&format!(r"(?i)(^|[^\w-]){}([^\w-]|$)", name)).unwrap()
+}
+
+/// Whether given issue mentions a crate, as matched by its `crate_mention_regex`.
+fn mentions_crate(issue: &Issue, crate_re: &Regex) -> bool {
+    crate_re.is_match(&issue.title) || crate_re.is_match(&issue.body)
+}
+
+/// Convert an issue label to its "canonical" form for comparison purposes.
 fn canonicalize_label(label: &str) -> String {
     // Strip punctuation, sanitize whitespace, and remove freestanding capital letters
     // (which are often used in labels to keep them sorted).
@@ -312,10 +1371,61 @@ fn canonicalize_label(label: &str) -> String {
 
 #[cfg(test)]
 mod tests {
+    use futures::Stream;
+    use semver::{Version, VersionReq};
     use tokio_core::reactor::Core;
-    use issues::crates_io::Client as CratesIoClient;
-    use model::{Dependency, Repository};
-    use super::{canonicalize_label, ISSUE_LABELS, repo_for_dependency};
+    use issues::cargo_toml::DependencyFilter;
+    use issues::crates_io::{Client as CratesIoClient, Registries};
+    use issues::registry_cache::RegistryCache;
+    use model::{
+        CrateLocation, Dependency, DependencyKind, Forge, ForgeHosts, Issue, Repository,
+    };
+    use testing::{create_temp_dir, MockServer};
+    use util::https_client;
+    use super::{canonicalize_label, crate_mention_regex, exact_version, is_crate_label,
+        ISSUE_LABELS, list_project_dependencies, LocalSources, parse_repo_override,
+        repo_for_dependency, SuggestedIssuesProducer};
+
+    /// Create a producer talking to given mock server as both crates.io
+    /// and a Gitea instance at git.example.org, with given registry cache.
+    fn mock_producer(
+        core: &Core, server: &MockServer, cache_root: &str,
+    ) -> SuggestedIssuesProducer {
+        let mut producer = SuggestedIssuesProducer::new(&core.handle());
+        producer.crates_io = CratesIoClient::with_api_root(
+            https_client(&core.handle()), format!("{}/api/v1/", server.url()));
+        producer.registry_cache = Some(RegistryCache::with_root(cache_root));
+        let api_root = format!("{}/api/v1", server.url());
+        producer.configure_host(Forge::Gitea, "git.example.org", Some(&api_root), None);
+        producer
+    }
+
+    /// Gitea issues of given repository at git.example.org, with one issue that needs help.
+    fn mock_issues(repo: &str) -> (String, String) {
+        let path = format!(concat!(
+            "/api/v1/repos/owner/{}/issues?state=open&type=issues",
+            "&labels=help+wanted%2Cgood+first+issue%2Ceasy%2Cbeginner",
+            "&sort=recentupdate&limit=50&page=1"), repo);
+        let body = format!(r#"[{{
+            "number": 1, "title": "Fix the docs", "body": "",
+            "html_url": "https://git.example.org/owner/{}/issues/1", "comments": 0,
+            "labels": [{{"name": "help wanted"}}], "assignees": null
+        }}]"#, repo);
+        (path, body)
+    }
+
+    fn issue_versions(issues: &[Issue]) -> Vec<(String, VersionReq)> {
+        let mut result: Vec<_> = issues.iter().map(|issue| {
+            let dep = issue.dependency.as_ref().unwrap();
+            match *dep.location() {
+                CrateLocation::Registry{ref version, ..} =>
+                    (issue.repo.name.clone(), version.clone()),
+                _ => panic!("unexpected dependency location of {}", issue),
+            }
+        }).collect();
+        result.sort_by(|a, b| a.0.cmp(&b.0));
+        result
+    }
 
     #[test]
     fn issue_labels_are_canonical() {
@@ -324,10 +1434,39 @@ mod tests {
         }
     }
 
+    #[test]
+    fn crate_labels() {
+        assert!(is_crate_label("A-tokio-util", "tokio-util"));
+        assert!(is_crate_label("crate: tokio_util", "tokio-util"));
+        assert!(is_crate_label("tokio-util", "tokio-util"));
+        assert!(!is_crate_label("A-tokio", "tokio-util"));
+        assert!(!is_crate_label("help wanted", "tokio-util"));
+    }
+
+    #[test]
+    fn repo_overrides() {
+        let hosts = ForgeHosts::new();
+        assert_eq!(Ok(None), parse_repo_override("internal", "none", &hosts));
+        assert_eq!(Ok(Some(Repository::new("someone", "foo"))),
+            parse_repo_override("foo", "github:someone/foo", &hosts));
+        assert!(parse_repo_override("foo", "foo", &hosts).is_err());
+    }
+
+    #[test]
+    fn crate_mentions() {
+        let serde_json = crate_mention_regex("serde_json");
+        assert!(serde_json.is_match("Update to serde_json 1.0"));
+        assert!(serde_json.is_match("Switch to `serde-json`"));
+        let log = crate_mention_regex("log");
+        assert!(log.is_match("LOG: add a macro"));
+        assert!(!log.is_match("Improve logging"));
+        assert!(!crate_mention_regex("serde").is_match("Implement Serialize with serde_derive"));
+    }
+
     #[test]
     fn repo_for_github_http_git_dependency() {
         let mut core = Core::new().unwrap();
-        let crates_io = CratesIoClient::new(&core.handle());
+        let registries = Registries::new(CratesIoClient::new(&core.handle()));
 
         const REPO_URLS: &[&str] = &[
             "https://github.com/Xion/gisht.git",
@@ -339,10 +1478,12 @@ mod tests {
             "http://www.github.com/Xion/gisht",
             "https://www.github.com/Xion/gisht",
         ];
-        let expected_repo = Repository{owner: "Xion".into(), name: "gisht".into()};
+        let expected_repo = Repository::new("Xion", "gisht");
         for &repo_url in REPO_URLS {
             let dep = Dependency::with_git_url("unused", repo_url);
-            let repo = core.run(repo_for_dependency("unused", &crates_io, &dep)).unwrap();
+            let repo = core.run(repo_for_dependency(
+                None, &registries, &LocalSources::default(), &ForgeHosts::new(), &dep,
+            )).unwrap();
             assert_eq!(Some(&expected_repo), repo.as_ref());
         }
     }
@@ -350,10 +1491,152 @@ mod tests {
     #[test]
     fn repo_for_github_ssh_git_dependency() {
         let mut core = Core::new().unwrap();
-        let crates_io = CratesIoClient::new(&core.handle());
+        let registries = Registries::new(CratesIoClient::new(&core.handle()));
+
+        const REPO_URLS: &[&str] = &[
+            "git@github.com:Xion/gisht.git",
+            "ssh://git@github.com/Xion/gisht.git",
+            "git://github.com/Xion/gisht.git",
+        ];
+        let expected_repo = Repository::new("Xion", "gisht");
+        for &repo_url in REPO_URLS {
+            let dep = Dependency::with_git_url("unused", repo_url);
+            let repo = core.run(repo_for_dependency(
+                None, &registries, &LocalSources::default(), &ForgeHosts::new(), &dep,
+            )).unwrap();
+            assert_eq!(Some(&expected_repo), repo.as_ref());
+        }
+    }
+
+    #[test]
+    fn repo_for_hyphenated_github_owner() {
+        let mut core = Core::new().unwrap();
+        let registries = Registries::new(CratesIoClient::new(&core.handle()));
+
+        let dep = Dependency::with_git_url("unused", "https://github.com/rust-lang/rust-clippy");
+        let repo = core.run(repo_for_dependency(
+            None, &registries, &LocalSources::default(), &ForgeHosts::new(), &dep,
+        )).unwrap();
+        assert_eq!(Some(Repository::new("rust-lang", "rust-clippy")), repo);
+    }
+
+    #[test]
+    fn dependencies_of_workspace_members() {
+        let root = create_temp_dir(&[
+            ("Cargo.toml", "[workspace]\nmembers = [\"a\", \"b\"]\n"),
+            ("a/Cargo.toml", concat!(
+                "[package]\nname = \"a\"\nversion = \"0.1.0\"\n",
+                "[dependencies]\nfutures = \"0.1\"\nlog = \"0.4\"\n",
+                "serde = { version = \"1\", optional = true }\n",
+                "[dev-dependencies]\nrand = \"0.7\"\n")),
+            ("b/Cargo.toml", concat!(
+                "[package]\nname = \"b\"\nversion = \"0.1.0\"\n",
+                "[dependencies]\nfutures = \"0.3\"\nlog = { path = \"../log\" }\n",
+                "rand = \"0.7\"\nserde = \"1\"\n")),
+            ("log/Cargo.toml", "[package]\nname = \"log\"\nversion = \"0.4.99\"\n"),
+        ]);
+        let filter = DependencyFilter{
+            kinds: DependencyKind::all().to_vec(), ..DependencyFilter::default()
+        };
+        let deps = list_project_dependencies(
+            &root.join("Cargo.toml"), &filter, Some(1), &Default::default()).unwrap();
+        let mut deps: Vec<_> = deps.iter().map(|pd| &pd.dependency).collect();
+        deps.sort_by_key(|d| d.name().to_owned());
+
+        // Different versions and sources of the same crate are all kept.
+        let names: Vec<_> = deps.iter().map(|d| d.name()).collect();
+        assert_eq!(vec!["futures", "futures", "log", "log", "rand", "serde"], names);
+        // Attributes of the same dependency are merged across members.
+        assert_eq!(DependencyKind::Normal, deps[4].kind());
+        assert!(!deps[5].is_optional());
+    }
+
+    #[test]
+    fn exact_versions() {
+        let version = Version::parse("1.2.3-beta.1").unwrap();
+        assert_eq!(Some(version.clone()), exact_version(&VersionReq::exact(&version)));
+        let req = VersionReq::parse("=1.2.3-beta.1").unwrap();
+        assert_eq!(Some(version.clone()), exact_version(&req));
+        assert_eq!(None, exact_version(&VersionReq::parse("=1.2").unwrap()));
+        assert_eq!(None, exact_version(&VersionReq::parse("1.2.3").unwrap()));
+        assert_eq!(None, exact_version(&VersionReq::any()));
+    }
+
+    #[test]
+    fn crate_issues() {
+        let mut routes = vec![
+            ("/api/v1/crates/foo".to_owned(), r#"{
+                "crate": {
+                    "id": "foo", "name": "foo", "description": "",
+                    "created_at": "2018-01-01T00:00:00Z", "updated_at": "2018-01-02T00:00:00Z",
+                    "repository": "https://git.example.org/owner/foo"
+                },
+                "versions": [
+                    {"num": "1.5.0", "yanked": false},
+                    {"num": "1.0.0", "yanked": false}
+                ]
+            }"#.to_owned()),
+            ("/api/v1/crates/foo/1.5.0/dependencies".to_owned(), r#"{"dependencies": [
+                {"crate_id": "bar", "req": "^0.1", "kind": "normal", "optional": false}
+            ]}"#.to_owned()),
+            ("/api/v1/crates/bar".to_owned(), r#"{"crate": {
+                "id": "bar", "name": "bar", "description": "",
+                "created_at": "2018-01-01T00:00:00Z", "updated_at": "2018-01-02T00:00:00Z",
+                "repository": "https://git.example.org/owner/bar"
+            }}"#.to_owned()),
+        ];
+        routes.extend(vec!["foo", "bar", "stale"].into_iter().map(mock_issues));
+        let server = MockServer::start(routes);
+        // Only an older version of the crate is in the cache, pointing at another repository.
+        let cache_root = create_temp_dir(&[(
+            "src/index.crates.io-1949cf8c6b5b557f/foo-1.0.0/Cargo.toml",
+            concat!("[package]\nname = \"foo\"\nversion = \"1.0.0\"\n",
+                "repository = \"https://git.example.org/owner/stale\"\n"),
+        )]);
+        let mut core = Core::new().unwrap();
+        let producer = mock_producer(&core, &server, cache_root.to_str().unwrap());
+
+        // The newest version is taken from crates.io, rather than the one we have cached.
+        let issues = producer.suggest_crate_issues("foo", &VersionReq::any()).unwrap();
+        let issues = core.run(issues.collect()).unwrap();
+        assert_eq!(vec![
+            ("bar".to_owned(), VersionReq::parse("^0.1").unwrap()),
+            ("foo".to_owned(), VersionReq::exact(&Version::parse("1.5.0").unwrap())),
+        ], issue_versions(&issues));
+
+        // An exact version can be read from the cache.
+        let version = VersionReq::exact(&Version::parse("1.0.0").unwrap());
+        let issues = producer.suggest_crate_issues("foo", &version).unwrap();
+        let issues = core.run(issues.collect()).unwrap();
+        assert_eq!(vec![("stale".to_owned(), version)], issue_versions(&issues));
+    }
+
+    #[test]
+    fn reverse_dependency_issues() {
+        let mut routes = vec![
+            ("/api/v1/crates/foo/reverse_dependencies?page=1&per_page=100".to_owned(), r#"{
+                "dependencies": [
+                    {"crate_id": "foo", "req": "^1", "kind": "normal", "optional": false}
+                ],
+                "versions": [{"crate": "bar", "num": "0.3.0"}],
+                "meta": {"total": 1}
+            }"#.to_owned()),
+            ("/api/v1/crates/bar".to_owned(), r#"{"crate": {
+                "id": "bar", "name": "bar", "description": "",
+                "created_at": "2018-01-01T00:00:00Z", "updated_at": "2018-01-02T00:00:00Z",
+                "repository": "https://git.example.org/owner/bar"
+            }}"#.to_owned()),
+        ];
+        routes.push(mock_issues("bar"));
+        let server = MockServer::start(routes);
+        let mut core = Core::new().unwrap();
+        let producer = mock_producer(&core, &server, create_temp_dir(&[]).to_str().unwrap());
 
-        let dep = Dependency::with_git_url("unused", "git@github.com:Xion/gisht.git");
-        let repo = core.run(repo_for_dependency("unused", &crates_io, &dep)).unwrap();
-        assert_eq!(Some(Repository{owner: "Xion".into(), name: "gisht".into()}), repo);
+        let issues = core.run(producer.suggest_reverse_dependency_issues("foo").collect()).unwrap();
+        assert_eq!(1, issues.len());
+        assert_eq!("bar", issues[0].repo.name);
+        assert_eq!(Some("foo"), issues[0].dependency.as_ref().map(|d| d.name()));
+        assert_eq!(Some("bar"), issues[0].dependent.as_ref().map(|c| c.as_str()));
+        assert!(issues[0].mentioned_crate.is_none());
     }
 }
@@ -0,0 +1,301 @@
+//! Module for looking up crates in Cargo's local registry cache.
+//!
+//! Cargo keeps the crates it has downloaded under $CARGO_HOME/registry,
+//! separately for every registry index:
+//!
+//! * registry/cache/$INDEX/$NAME-$VERSION.crate -- the downloaded .crate tarballs
+//! * registry/src/$INDEX/$NAME-$VERSION/ -- their extracted sources
+//!
+//! where $INDEX is the index hostname followed by a hash,
+//! e.g. "index.crates.io-1949cf8c6b5b557f" or "github.com-1ecc6299db9ec823".
+
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use flate2::read::GzDecoder;
+use semver::{Version, VersionReq};
+use tar::Archive;
+
+use model::Package;
+use super::cargo_config::cargo_home;
+use super::cargo_toml;
+
+
+/// Extension of the crate tarballs.
+const CRATE_FILE_EXT: &str = "crate";
+
+
+/// Cargo's local cache of crates downloaded from registries.
+#[derive(Clone, Debug)]
+pub struct RegistryCache {
+    /// Path to the registry directory, usually $CARGO_HOME/registry.
+    root: PathBuf,
+}
+
+impl RegistryCache {
+    /// Create the cache for the registry directory of current $CARGO_HOME.
+    /// Returns None if it cannot be determined.
+    pub fn new() -> Option<Self> {
+        cargo_home().map(|home| RegistryCache::with_root(home.join("registry")))
+    }
+
+    #[inline]
+    pub fn with_root<P: AsRef<Path>>(root: P) -> Self {
+        RegistryCache{root: root.as_ref().to_owned()}
+    }
+}
+
+impl RegistryCache {
+    /// Find the [package] manifest section of given crate.
+    ///
+    /// If the locked version is given, only that exact version is considered.
+    /// Otherwise, the newest cached version that matches the requirement is used.
+    /// Only the parts of the cache that correspond to registry indices
+    /// with given hostnames are looked at.
+    pub fn find_package(
+        &self, crate_: &str, version: &VersionReq, locked_version: Option<&Version>,
+        index_hosts: &[String],
+    ) -> Option<Package> {
+        trace!("Trying to find cached manifest of crate {}={}", crate_, version);
+        let is_match = |v: &Version| match locked_version {
+            Some(locked) => v == locked,
+            None => version.matches(v),
+        };
+
+        // Prefer the extracted sources, and only fall back to the tarballs if there are none.
+        if let Some(manifest_path) = self.find_extracted_manifest(&is_match, crate_, index_hosts) {
+            debug!("Cached manifest found at {}", manifest_path.display());
+            return cargo_toml::read_package(&manifest_path).map_err(|e| {
+                warn!("Error while reading cached manifest of {}={}: {}", crate_, version, e);
+            }).ok();
+        }
+
+        let tarball = self.list_crate_entries("cache", crate_, index_hosts, Some(CRATE_FILE_EXT))
+            .into_iter().filter(|&(ref v, _)| is_match(v))
+            .max_by(|&(ref a, _), &(ref b, _)| a.cmp(b));
+        match tarball {
+            Some((cached_version, path)) => {
+                debug!("Reading manifest from cached crate tarball {}", path.display());
+                read_tarball_package(&path, crate_, &cached_version).map_err(|e| {
+                    warn!("Error while reading manifest from {}: {}", path.display(), e);
+                }).ok()
+            }
+            None => {
+                debug!("Crate {}-{} not found in Cargo cache", crate_, version);
+                None
+            }
+        }
+    }
+
+    /// Find the Cargo.toml of the newest extracted version of given crate
+    /// that matches the requirement.
+    ///
+    /// Unlike `find_package`, this doesn't look into crate tarballs.
+    pub fn find_manifest(
+        &self, crate_: &str, version: &VersionReq, index_hosts: &[String],
+    ) -> Option<PathBuf> {
+        trace!("Trying to find extracted sources of crate {}={}", crate_, version);
+        self.find_extracted_manifest(&|v| version.matches(v), crate_, index_hosts)
+    }
+
+    fn find_extracted_manifest(
+        &self, is_match: &dyn Fn(&Version) -> bool, crate_: &str, index_hosts: &[String],
+    ) -> Option<PathBuf> {
+        let (version, dir) = self.list_crate_entries("src", crate_, index_hosts, None)
+            .into_iter().filter(|&(ref v, _)| is_match(v))
+            .max_by(|&(ref a, _), &(ref b, _)| a.cmp(b))?;
+        let manifest_path = dir.join("Cargo.toml");
+        if !manifest_path.is_file() {
+            warn!("Found cached crate {}={} but it's missing its manifest", crate_, version);
+            return None;
+        }
+        Some(manifest_path)
+    }
+
+    /// List the entries for given crate from a particular part of the cache (src/ or cache/),
+    /// along with the crate versions they correspond to.
+    fn list_crate_entries(
+        &self, subdir: &str, crate_: &str, index_hosts: &[String], ext: Option<&str>
+    ) -> Vec<(Version, PathBuf)> {
+        let mut result = vec![];
+        for index_dir in self.index_dirs(subdir, index_hosts) {
+            let entries = match fs::read_dir(&index_dir) {
+                Ok(e) => e,
+                Err(e) => {
+                    trace!("Cannot list {}: {}", index_dir.display(), e);
+                    continue;
+                }
+            };
+            for path in entries.filter_map(Result::ok).map(|e| e.path()) {
+                let stem = match ext {
+                    Some(ext) if path.extension().and_then(|e| e.to_str()) != Some(ext) => continue,
+                    Some(_) => path.file_stem(),
+                    None => path.file_name(),
+                };
+                if let Some(version) = stem.and_then(|s| s.to_str())
+                        .and_then(|s| parse_crate_version(crate_, s)) {
+                    result.push((version, path));
+                }
+            }
+        }
+        result
+    }
+
+    /// List the per-index directories within given part of the cache
+    /// which correspond to indices with given hostnames.
+    fn index_dirs(&self, subdir: &str, index_hosts: &[String]) -> Vec<PathBuf> {
+        let dir = self.root.join(subdir);
+        let entries = match fs::read_dir(&dir) {
+            Ok(e) => e,
+            Err(e) => {
+                debug!("Cannot read Cargo registry cache at {}: {}", dir.display(), e);
+                return vec![];
+            }
+        };
+        entries.filter_map(Result::ok)
+            .map(|e| e.path())
+            .filter(|p| p.is_dir())
+            .filter(|p| {
+                let name = p.file_name().and_then(|n| n.to_str()).unwrap_or("");
+                index_hosts.iter().any(|h| {
+                    name.len() > h.len() && name.starts_with(h.as_str())
+                        && name[h.len()..].starts_with('-')
+                })
+            })
+            .collect()
+    }
+}
+
+
+/// Parse the crate version out of a "$NAME-$VERSION" directory or file name.
+/// Returns None if the name doesn't belong to given crate.
+pub fn parse_crate_version(crate_: &str, name: &str) -> Option<Version> {
+    // Both crate names and versions can contain hyphens (e.g. foo-bar-1.0.0-beta.1),
+    // so we cannot just split on the last one.
+    if !(name.len() > crate_.len() && name.starts_with(crate_)) {
+        return None;
+    }
+    let suffix = &name[crate_.len()..];
+    if !suffix.starts_with('-') {
+        return None;
+    }
+    // This fails for names of other crates that have ours as a prefix, like foo-bar for foo.
+    Version::parse(&suffix[1..]).ok()
+}
+
+/// Read the [package] manifest section from a .crate tarball.
+pub fn read_tarball_package(path: &Path, crate_: &str, version: &Version) -> io::Result<Package> {
+    let manifest_path = Path::new(&format!("{}-{}", crate_, version)).join("Cargo.toml");
+    let mut archive = Archive::new(GzDecoder::new(File::open(path)?));
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if entry.path()? != manifest_path {
+            continue;
+        }
+        let mut content = String::new();
+        entry.read_to_string(&mut content)?;
+        return cargo_toml::parse_package(&content, path.join(&manifest_path))
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e));
+    }
+    Err(io::Error::new(io::ErrorKind::NotFound,
+        format!("{} not found in the crate tarball", manifest_path.display())))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, File};
+
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+    use semver::{Version, VersionReq};
+    use tar::{Builder, Header};
+    use testing::create_temp_dir;
+    use super::{parse_crate_version, RegistryCache};
+
+    const CRATES_IO_HOST: &str = "index.crates.io";
+
+    fn manifest(name: &str, version: &str) -> String {
+        format!(concat!(
+            "[package]\nname = \"{}\"\nversion = \"{}\"\n",
+            "repository = \"https://github.com/foo/{}\"\n"), name, version, name)
+    }
+
+    /// Create a registry cache with given extracted crates and crate tarballs.
+    fn create_cache(extracted: &[(&str, &str)], tarballs: &[(&str, &str)]) -> RegistryCache {
+        let root = create_temp_dir(&[]);
+        let index_dir = format!("{}-1949cf8c6b5b557f", CRATES_IO_HOST);
+
+        for &(name, version) in extracted {
+            let dir = root.join("src").join(&index_dir).join(format!("{}-{}", name, version));
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("Cargo.toml"), manifest(name, version)).unwrap();
+        }
+        let cache_dir = root.join("cache").join(&index_dir);
+        fs::create_dir_all(&cache_dir).unwrap();
+        for &(name, version) in tarballs {
+            let file = File::create(cache_dir.join(format!("{}-{}.crate", name, version))).unwrap();
+            let mut builder = Builder::new(GzEncoder::new(file, Compression::default()));
+            let content = manifest(name, version);
+            let mut header = Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_cksum();
+            builder.append_data(&mut header, format!("{}-{}/Cargo.toml", name, version),
+                content.as_bytes()).unwrap();
+            builder.into_inner().unwrap().finish().unwrap();
+        }
+        RegistryCache::with_root(root)
+    }
+
+    fn find(cache: &RegistryCache, name: &str, req: &str, locked: Option<&str>) -> Option<String> {
+        let locked = locked.map(|l| Version::parse(l).unwrap());
+        cache.find_package(name, &VersionReq::parse(req).unwrap(), locked.as_ref(),
+            &[CRATES_IO_HOST.to_owned()]).map(|p| p.version)
+    }
+
+    #[test]
+    fn crate_version_from_name() {
+        assert_eq!(Some(Version::parse("1.0.0").unwrap()), parse_crate_version("foo", "foo-1.0.0"));
+        assert_eq!(Some(Version::parse("1.0.0-beta.1").unwrap()),
+            parse_crate_version("foo-bar", "foo-bar-1.0.0-beta.1"));
+        assert_eq!(None, parse_crate_version("foo", "foo-bar-1.0.0"));
+        assert_eq!(None, parse_crate_version("foo", "foobar-1.0.0"));
+    }
+
+    #[test]
+    fn extracted_crates() {
+        let cache = create_cache(&[
+            ("foo", "1.0.0"), ("foo", "1.2.0"), ("foo", "2.0.0-rc.1"), ("foo-bar", "1.5.0"),
+        ], &[]);
+        assert_eq!(Some("1.2.0".into()), find(&cache, "foo", "1", None));
+        assert_eq!(Some("1.0.0".into()), find(&cache, "foo", "1", Some("1.0.0")));
+        assert_eq!(Some("1.5.0".into()), find(&cache, "foo-bar", "1", None));
+        assert_eq!(None, find(&cache, "foo", "3", None));
+    }
+
+    #[test]
+    fn crate_tarballs() {
+        let cache = create_cache(&[("foo", "1.0.0")], &[("foo", "1.1.0"), ("baz", "0.3.1")]);
+        assert_eq!(Some("1.0.0".into()), find(&cache, "foo", "1", None));
+        assert_eq!(Some("1.1.0".into()), find(&cache, "foo", "1", Some("1.1.0")));
+        assert_eq!(Some("0.3.1".into()), find(&cache, "baz", "0.3", None));
+    }
+
+    #[test]
+    fn extracted_manifest() {
+        let cache = create_cache(&[("foo", "1.0.0"), ("foo", "1.2.0")], &[("foo", "1.3.0")]);
+        let hosts = &[CRATES_IO_HOST.to_owned()];
+        let manifest = cache.find_manifest("foo", &VersionReq::any(), hosts).unwrap();
+        assert!(manifest.ends_with("foo-1.2.0/Cargo.toml"));
+        assert!(cache.find_manifest("foo", &VersionReq::parse("=1.3.0").unwrap(), hosts).is_none());
+    }
+
+    #[test]
+    fn other_registries_are_ignored() {
+        let cache = create_cache(&[("foo", "1.0.0")], &[]);
+        let package = cache.find_package("foo", &VersionReq::any(), None,
+            &["crates.example.com".to_owned()]);
+        assert!(package.is_none());
+    }
+}
@@ -0,0 +1,250 @@
+//! Module for making calls to the API of SourceHut's issue trackers (todo.sr.ht).
+//!
+//! Unlike on other forges, trackers aren't a part of the repository
+//! but live on a separate todo.* host. We assume the tracker is named the same as the repo,
+//! which is by far the most common setup (and is pointed out in `--help`).
+
+use futures::{Future, Stream};
+use hyper;
+use hyper::client::Connect;
+
+use model::{Issue, Repository};
+use super::forge_api::{fetch_json, ForgeClient, HostSettings, paginate};
+
+pub use super::forge_api::Error;
+
+
+/// Status of tickets which have been dealt with.
+const RESOLVED_STATUS: &str = "resolved";
+
+
+/// Page of tickets as returned by the todo.sr.ht API.
+#[derive(Debug, Deserialize)]
+struct TicketsPage {
+    /// Cursor for the next page, if there is one.
+    #[serde(default)]
+    next: Option<TicketsCursor>,
+    results: Vec<TicketItem>,
+}
+
+/// The API returns cursors as either strings or numbers, depending on its version.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum TicketsCursor {
+    Number(u64),
+    String(String),
+}
+
+impl TicketsCursor {
+    fn into_string(self) -> String {
+        match self {
+            TicketsCursor::Number(n) => n.to_string(),
+            TicketsCursor::String(s) => s,
+        }
+    }
+}
+
+/// Ticket as returned by the todo.sr.ht API.
+#[derive(Debug, Deserialize)]
+pub struct TicketItem {
+    /// Ticket number within the tracker.
+    pub id: u64,
+    /// Title of the ticket.
+    pub subject: String,
+    #[serde(default)]
+    pub body: Option<String>,
+    /// Status of the ticket, like "reported", "confirmed", or "resolved".
+    pub status: String,
+    #[serde(default)]
+    pub labels: Vec<Label>,
+    #[serde(default)]
+    pub assignees: Vec<User>,
+}
+
+/// Ticket label, which older versions of the API return as a plain string.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Label {
+    Object{name: String},
+    Name(String),
+}
+
+impl Label {
+    #[inline]
+    pub fn name(&self) -> &str {
+        match *self {
+            Label::Object{ref name} | Label::Name(ref name) => name,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct User {
+    #[serde(default)]
+    pub canonical_name: Option<String>,
+}
+
+impl TicketItem {
+    /// Whether the ticket is still open.
+    #[inline]
+    pub fn is_open(&self) -> bool {
+        !self.status.eq_ignore_ascii_case(RESOLVED_STATUS)
+    }
+
+    /// Whether the ticket is assigned to anyone.
+    #[inline]
+    pub fn is_assigned(&self) -> bool {
+        !self.assignees.is_empty()
+    }
+
+    /// Convert to the `Issue` from given repository.
+    pub fn into_issue(self, repo: Repository) -> Issue {
+        let url = format!("https://{}/{}/{}/{}",
+            tracker_host(&repo.host), repo.owner, repo.name, self.id);
+        let labels = self.labels.iter().map(|l| l.name().to_owned()).collect();
+        Issue{
+            repo,
+            number: self.id,
+            url,
+            title: self.subject,
+            body: self.body.unwrap_or_else(String::new),
+            // Comments aren't included in the ticket listing.
+            comment_count: 0,
+            labels,
+            dependency: None,
+            dependent: None,
+            mentioned_crate: None,
+        }
+    }
+}
+
+
+/// Client for the todo.sr.ht API.
+#[derive(Clone, Debug)]
+pub struct Client<C: Clone> {
+    http: hyper::Client<C>,
+    /// Settings of particular SourceHut (Git or Mercurial) hosts,
+    /// whose tracker API is usually at https://todo.$DOMAIN/api.
+    hosts: HostSettings,
+}
+
+impl<C: Clone> Client<C> {
+    #[inline]
+    pub fn with_http(http: hyper::Client<C>) -> Self {
+        Client{http, hosts: HostSettings::default()}
+    }
+
+    fn api_root(&self, host: &str) -> String {
+        self.hosts.api_root(host).map(String::from)
+            .unwrap_or_else(|| format!("https://{}/api", tracker_host(host)))
+    }
+}
+
+impl<C: Clone> ForgeClient for Client<C> {
+    #[inline]
+    fn hosts_mut(&mut self) -> &mut HostSettings {
+        &mut self.hosts
+    }
+}
+
+impl<C: Clone + Connect> Client<C> {
+    /// Return a stream of all open & unassigned tickets in the tracker of given repository,
+    /// in the order returned by the API.
+    pub fn pending_issues(
+        &self, repo: Repository
+    ) -> Box<dyn Stream<Item=TicketItem, Error=Error>> {
+        debug!("Querying for tickets of {:?}", repo);
+        let tickets_url = format!("{}/user/{}/trackers/{}/tickets",
+            self.api_root(&repo.host), repo.owner, repo.name);
+
+        let client = self.clone();
+        let tickets = paginate(tickets_url.clone(), move |url| {
+            let tickets_url = tickets_url.clone();
+            Box::new(client.tickets_page(&repo, &url).map(move |page| {
+                let next = page.next.map(|c| format!("{}?start={}", tickets_url, c.into_string()));
+                (page.results, next)
+            }))
+        });
+        // The API doesn't allow to filter tickets at all, so we have to do it here.
+        Box::new(tickets.filter(|ti| ti.is_open() && !ti.is_assigned()))
+    }
+
+    /// Fetch the page of tickets from given URL.
+    fn tickets_page(
+        &self, repo: &Repository, url: &str
+    ) -> Box<dyn Future<Item=TicketsPage, Error=Error>> {
+        trace!("Fetching SourceHut tickets from {}", url);
+        let auth = self.hosts.token(&repo.host)
+            .map(|token| ("Authorization", format!("token {}", token)));
+        Box::new(fetch_json(&self.http, url, auth, repo).map(|page| {
+            page.unwrap_or_else(|| TicketsPage{next: None, results: vec![]})
+        }))
+    }
+}
+
+
+/// Host of the issue trackers that go with repositories on given host,
+/// e.g. todo.sr.ht for git.sr.ht.
+fn tracker_host(host: &str) -> String {
+    let domain = host.splitn(2, '.').nth(1).unwrap_or(host);
+    format!("todo.{}", domain)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use futures::Stream;
+    use hyper;
+    use tokio_core::reactor::Core;
+    use model::{Forge, Repository};
+    use testing::MockServer;
+    use super::{Client, ForgeClient, tracker_host};
+
+    #[test]
+    fn pending_issues() {
+        let server = MockServer::start(vec![
+            ("/api/user/~someone/trackers/project/tickets", r#"{
+                "next": 40, "results_per_page": 2, "total": 3,
+                "results": [
+                    {
+                        "id": 42, "subject": "Port to BSD", "body": "Should be easy",
+                        "status": "reported", "labels": ["good first issue"], "assignees": []
+                    },
+                    {
+                        "id": 41, "subject": "Crash on start", "body": null,
+                        "status": "resolved", "labels": [], "assignees": []
+                    }
+                ]
+            }"#),
+            ("/api/user/~someone/trackers/project/tickets?start=40", r#"{
+                "next": null, "results_per_page": 2, "total": 3,
+                "results": [
+                    {
+                        "id": 40, "subject": "Add a manpage", "status": "confirmed",
+                        "labels": [{"name": "help wanted"}],
+                        "assignees": [{"canonical_name": "~someone"}]
+                    }
+                ]
+            }"#),
+        ]);
+        let mut core = Core::new().unwrap();
+        let mut client = Client::with_http(hyper::Client::new(&core.handle()));
+        client.configure_host("git.sr.ht", Some(&format!("{}/api", server.url())), None);
+
+        let repo = Repository::with_host(Forge::SourceHut, "git.sr.ht", "~someone", "project");
+        let tickets = core.run(client.pending_issues(repo.clone()).collect()).unwrap();
+        assert_eq!(1, tickets.len());
+        assert_eq!("good first issue", tickets[0].labels[0].name());
+
+        let issue = tickets.into_iter().next().unwrap().into_issue(repo);
+        assert_eq!(42, issue.number);
+        assert_eq!("https://todo.sr.ht/~someone/project/42", issue.url);
+    }
+
+    #[test]
+    fn tracker_hosts() {
+        assert_eq!("todo.sr.ht", tracker_host("git.sr.ht"));
+        assert_eq!("todo.sr.ht", tracker_host("hg.sr.ht"));
+        assert_eq!("todo.example.com", tracker_host("git.example.com"));
+    }
+}
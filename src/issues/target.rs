@@ -0,0 +1,269 @@
+//! Module for matching platform-specific dependencies against a target triple.
+//!
+//! Cargo allows to specify dependencies under [target.$SPEC.dependencies],
+//! where $SPEC is either a full target triple or a `cfg(...)` expression.
+
+use std::iter::Peekable;
+use std::str::Chars;
+
+
+/// Check whether given target specification from Cargo.toml
+/// applies to the target with given triple (e.g. "x86_64-unknown-linux-gnu").
+pub fn matches(spec: &str, triple: &str) -> bool {
+    let spec = spec.trim();
+    if !spec.starts_with("cfg(") {
+        return spec == triple;
+    }
+    match parse_cfg(spec) {
+        Some(cfg) => {
+            let result = cfg.eval(&TargetInfo::from_triple(triple));
+            trace!("Target spec `{}` {} target {}",
+                spec, if result { "matches" } else { "doesn't match" }, triple);
+            result
+        }
+        None => {
+            warn!("Invalid target specification `{}`, ignoring", spec);
+            false
+        }
+    }
+}
+
+
+/// Configuration values (as understood by `cfg(...)`) of a particular target.
+#[derive(Debug)]
+struct TargetInfo {
+    arch: String,
+    vendor: String,
+    os: String,
+    env: String,
+    family: Option<&'static str>,
+    pointer_width: &'static str,
+    endian: &'static str,
+}
+
+impl TargetInfo {
+    /// Derive the target configuration from a target triple.
+    ///
+    /// This is a best-effort approximation of what rustc knows about its targets.
+    fn from_triple(triple: &str) -> Self {
+        let parts: Vec<_> = triple.split('-').collect();
+        let arch = parts.get(0).cloned().unwrap_or("");
+        let (vendor, os, env) = match parts.len() {
+            0 | 1 => ("unknown", "none", ""),
+            2 => (parts[1], "none", ""),
+            // Three-part triples are either $ARCH-$VENDOR-$OS (e.g. x86_64-apple-darwin)
+            // or $ARCH-$OS-$ENV (e.g. aarch64-linux-android, thumbv7em-none-eabihf).
+            3 if parts[1] == "linux" || parts[1] == "none" => ("unknown", parts[1], parts[2]),
+            3 => (parts[1], parts[2], ""),
+            _ => (parts[1], parts[2], parts[3]),
+        };
+        let (os, env) = match (os, env) {
+            ("darwin", env) => ("macos", env),
+            ("linux", "android") | ("linux", "androideabi") => ("android", ""),
+            (os, env) => (os, env),
+        };
+
+        let family = match os {
+            "windows" => Some("windows"),
+            "linux" | "android" | "macos" | "ios" | "freebsd" | "netbsd" | "openbsd"
+                | "dragonfly" | "solaris" | "illumos" | "haiku" | "fuchsia" => Some("unix"),
+            _ if arch.starts_with("wasm") => Some("wasm"),
+            _ => None,
+        };
+        let canonical_arch = match arch {
+            a if a.starts_with('i') && a.ends_with("86") => "x86",
+            a if a.starts_with("arm") || a.starts_with("thumb") => "arm",
+            a if a.starts_with("mips64") => "mips64",
+            a if a.starts_with("mips") => "mips",
+            a if a.starts_with("riscv64") => "riscv64",
+            a if a.starts_with("riscv32") => "riscv32",
+            "powerpc64le" => "powerpc64",
+            a => a,
+        };
+        let pointer_width = match canonical_arch {
+            "x86_64" | "aarch64" | "mips64" | "powerpc64" | "riscv64" | "s390x" | "sparc64"
+                | "wasm64" => "64",
+            "msp430" | "avr" => "16",
+            _ => "32",
+        };
+        let endian = match arch {
+            "powerpc" | "powerpc64" | "s390x" | "sparc64" | "mips" | "mips64" => "big",
+            _ => "little",
+        };
+
+        TargetInfo{
+            arch: canonical_arch.to_owned(),
+            vendor: vendor.to_owned(),
+            os: os.to_owned(),
+            env: env.to_owned(),
+            family, pointer_width, endian,
+        }
+    }
+
+    /// Check whether the target has a `cfg` key with given optional value.
+    fn has(&self, key: &str, value: Option<&str>) -> bool {
+        match (key, value) {
+            ("unix", None) | ("windows", None) => self.family == Some(key),
+            ("target_family", Some(v)) => self.family == Some(v),
+            ("target_arch", Some(v)) => self.arch == v,
+            ("target_vendor", Some(v)) => self.vendor == v,
+            ("target_os", Some(v)) => self.os == v,
+            ("target_env", Some(v)) => self.env == v,
+            ("target_pointer_width", Some(v)) => self.pointer_width == v,
+            ("target_endian", Some(v)) => self.endian == v,
+            // Other keys (e.g. `feature`, `test`, or `debug_assertions`)
+            // cannot be determined from the target alone.
+            _ => false,
+        }
+    }
+}
+
+
+/// Parsed `cfg(...)` expression.
+#[derive(Debug, PartialEq)]
+enum Cfg {
+    /// A name, like `unix`.
+    Name(String),
+    /// A key-value pair, like `target_os = "linux"`.
+    KeyValue(String, String),
+    All(Vec<Cfg>),
+    Any(Vec<Cfg>),
+    Not(Box<Cfg>),
+}
+
+impl Cfg {
+    fn eval(&self, target: &TargetInfo) -> bool {
+        match *self {
+            Cfg::Name(ref n) => target.has(n, None),
+            Cfg::KeyValue(ref k, ref v) => target.has(k, Some(v)),
+            Cfg::All(ref cfgs) => cfgs.iter().all(|c| c.eval(target)),
+            Cfg::Any(ref cfgs) => cfgs.iter().any(|c| c.eval(target)),
+            Cfg::Not(ref cfg) => !cfg.eval(target),
+        }
+    }
+}
+
+/// Parse the entire `cfg(...)` target specification.
+fn parse_cfg(spec: &str) -> Option<Cfg> {
+    // The top-level `cfg(...)` is like a call to an `all(...)` with exactly one argument.
+    let mut chars = spec.chars().peekable();
+    if parse_ident(&mut chars)? != "cfg" { return None; }
+    let mut args = parse_args(&mut chars)?;
+    skip_whitespace(&mut chars);
+    if chars.peek().is_some() || args.len() != 1 { return None; }
+    args.pop()
+}
+
+/// Parse a single predicate within a `cfg(...)` expression.
+fn parse_predicate(chars: &mut Peekable<Chars>) -> Option<Cfg> {
+    let ident = parse_ident(chars)?;
+    skip_whitespace(chars);
+    match chars.peek().cloned() {
+        Some('(') => {
+            let args = parse_args(chars)?;
+            match ident.as_str() {
+                "all" => Some(Cfg::All(args)),
+                "any" => Some(Cfg::Any(args)),
+                "not" if args.len() == 1 => args.into_iter().next().map(|a| Cfg::Not(Box::new(a))),
+                _ => None,
+            }
+        }
+        Some('=') => {
+            chars.next();
+            skip_whitespace(chars);
+            if chars.next()? != '"' { return None; }
+            let value: String = chars.by_ref().take_while(|&c| c != '"').collect();
+            Some(Cfg::KeyValue(ident, value))
+        }
+        _ => Some(Cfg::Name(ident)),
+    }
+}
+
+/// Parse a parenthesized, comma-separated list of predicates.
+fn parse_args(chars: &mut Peekable<Chars>) -> Option<Vec<Cfg>> {
+    skip_whitespace(chars);
+    if chars.next()? != '(' { return None; }
+    let mut args = vec![];
+    loop {
+        skip_whitespace(chars);
+        match chars.peek().cloned()? {
+            ')' => { chars.next(); return Some(args); }
+            ',' => { chars.next(); }
+            _ => args.push(parse_predicate(chars)?),
+        }
+    }
+}
+
+fn parse_ident(chars: &mut Peekable<Chars>) -> Option<String> {
+    skip_whitespace(chars);
+    let mut ident = String::new();
+    while let Some(&c) = chars.peek() {
+        if c.is_alphanumeric() || c == '_' { ident.push(c); chars.next(); } else { break; }
+    }
+    if ident.is_empty() { None } else { Some(ident) }
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.peek().map(|c| c.is_whitespace()).unwrap_or(false) {
+        chars.next();
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{Cfg, matches, parse_cfg};
+
+    const LINUX: &str = "x86_64-unknown-linux-gnu";
+    const WINDOWS: &str = "x86_64-pc-windows-msvc";
+    const MACOS: &str = "x86_64-apple-darwin";
+    const ANDROID: &str = "aarch64-linux-android";
+    const WASM: &str = "wasm32-unknown-unknown";
+
+    #[test]
+    fn parse_nested_cfg() {
+        assert_eq!(
+            Some(Cfg::All(vec![
+                Cfg::Name("unix".into()),
+                Cfg::Not(Box::new(Cfg::KeyValue("target_os".into(), "macos".into()))),
+            ])),
+            parse_cfg(r#"cfg(all(unix, not(target_os = "macos")))"#));
+        assert_eq!(None, parse_cfg("cfg(unix"));
+        assert_eq!(None, parse_cfg("cfg(unix, windows)"));
+    }
+
+    #[test]
+    fn triple_spec() {
+        assert!(matches(LINUX, LINUX));
+        assert!(!matches(WINDOWS, LINUX));
+    }
+
+    #[test]
+    fn family_cfg() {
+        assert!(matches("cfg(unix)", LINUX));
+        assert!(matches("cfg(unix)", MACOS));
+        assert!(matches("cfg(unix)", ANDROID));
+        assert!(!matches("cfg(unix)", WINDOWS));
+        assert!(matches("cfg(windows)", WINDOWS));
+        assert!(matches(r#"cfg(target_family = "wasm")"#, WASM));
+    }
+
+    #[test]
+    fn key_value_cfg() {
+        assert!(matches(r#"cfg(target_os = "macos")"#, MACOS));
+        assert!(matches(r#"cfg(target_os = "android")"#, ANDROID));
+        assert!(matches(r#"cfg(target_env = "msvc")"#, WINDOWS));
+        assert!(matches(r#"cfg(target_arch = "wasm32")"#, WASM));
+        assert!(matches(r#"cfg(target_pointer_width = "64")"#, LINUX));
+        assert!(!matches(r#"cfg(target_pointer_width = "64")"#, WASM));
+    }
+
+    #[test]
+    fn compound_cfg() {
+        let spec = r#"cfg(any(windows, all(unix, not(target_os = "macos"))))"#;
+        assert!(matches(spec, LINUX));
+        assert!(matches(spec, WINDOWS));
+        assert!(!matches(spec, MACOS));
+        assert!(!matches(spec, WASM));
+    }
+}
@@ -0,0 +1,200 @@
+//! Module for finding crates among the local replacements of their sources:
+//! directories of vendored crates (as created by `cargo vendor`) and local registries.
+//!
+//! This allows to read the manifests of dependencies without any network access.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use semver::{Version, VersionReq};
+
+use model::Package;
+use super::cargo_config::CargoConfig;
+use super::cargo_toml;
+use super::registry_cache::{parse_crate_version, read_tarball_package};
+
+
+/// Name of the source that corresponds to crates.io.
+const CRATES_IO_SOURCE: &str = "crates-io";
+
+/// Name of the directory that `cargo vendor` puts the crates in by default.
+const DEFAULT_VENDOR_DIR: &str = "vendor";
+
+
+/// Local replacements of crate sources.
+#[derive(Clone, Debug, Default)]
+pub struct VendoredSources {
+    /// Directories with vendored crates, each in its own subdirectory.
+    directories: Vec<PathBuf>,
+    /// Directories of local registries, with .crate files of all the crates.
+    local_registries: Vec<PathBuf>,
+}
+
+impl VendoredSources {
+    /// Determine the vendored sources for a project in given directory.
+    ///
+    /// Those include the replacements of crates.io and alternative registries
+    /// from Cargo configuration, as well as the project's vendor/ directory, if any.
+    pub fn for_project<P: AsRef<Path>>(project_dir: P, config: &CargoConfig) -> Self {
+        let mut result = VendoredSources::default();
+        let source_names = Some(CRATES_IO_SOURCE).into_iter()
+            .chain(config.registries.keys().map(|r| r.as_str()));
+        for name in source_names {
+            if let Some(source) = config.replaced_source(name) {
+                debug!("Source `{}` is replaced with {:?}", name, source);
+                result.add_directory(source.directory.as_ref());
+                result.add_local_registry(source.local_registry.as_ref());
+            }
+        }
+        let vendor_dir = project_dir.as_ref().join(DEFAULT_VENDOR_DIR);
+        if vendor_dir.is_dir() {
+            result.add_directory(Some(&vendor_dir));
+        }
+        result
+    }
+
+    fn add_directory(&mut self, dir: Option<&PathBuf>) {
+        if let Some(dir) = dir {
+            if !self.directories.contains(dir) {
+                self.directories.push(dir.clone());
+            }
+        }
+    }
+
+    fn add_local_registry(&mut self, dir: Option<&PathBuf>) {
+        if let Some(dir) = dir {
+            if !self.local_registries.contains(dir) {
+                self.local_registries.push(dir.clone());
+            }
+        }
+    }
+}
+
+impl VendoredSources {
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.directories.is_empty() && self.local_registries.is_empty()
+    }
+
+    /// Find the [package] manifest section of given crate among the vendored sources.
+    ///
+    /// If the locked version is given, only that exact version is considered.
+    /// Otherwise, the newest vendored version that matches the requirement is used.
+    pub fn find_package(
+        &self, crate_: &str, version: &VersionReq, locked_version: Option<&Version>
+    ) -> Option<Package> {
+        let is_match = |v: &Version| match locked_version {
+            Some(locked) => v == locked,
+            None => version.matches(v),
+        };
+
+        // `cargo vendor` puts crates in directories named either $NAME or $NAME-$VERSION
+        // (the latter when multiple versions of the crate are vendored),
+        // so we have to read the manifests to know their versions.
+        let vendored = self.directories.iter()
+            .flat_map(|dir| list_dir(dir))
+            .filter(|path| {
+                let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+                name == crate_ || parse_crate_version(crate_, name).is_some()
+            })
+            .filter_map(|path| {
+                let package = cargo_toml::read_package(path.join("Cargo.toml")).map_err(|e| {
+                    trace!("Error reading vendored manifest in {}: {}", path.display(), e);
+                }).ok()?;
+                let package_version = Version::parse(&package.version).ok()?;
+                if package.name == crate_ && is_match(&package_version) {
+                    Some((package_version, package))
+                } else {
+                    None
+                }
+            })
+            .max_by(|&(ref a, _), &(ref b, _)| a.cmp(b));
+        if let Some((v, package)) = vendored {
+            debug!("Vendored crate {}={} found", crate_, v);
+            return Some(package);
+        }
+
+        let tarball = self.local_registries.iter()
+            .flat_map(|dir| list_dir(dir))
+            .filter(|path| path.extension().and_then(|e| e.to_str()) == Some("crate"))
+            .filter_map(|path| {
+                let v = path.file_stem().and_then(|s| s.to_str())
+                    .and_then(|s| parse_crate_version(crate_, s))?;
+                if is_match(&v) { Some((v, path)) } else { None }
+            })
+            .max_by(|&(ref a, _), &(ref b, _)| a.cmp(b));
+        match tarball {
+            Some((v, path)) => {
+                debug!("Crate {}={} found in local registry: {}", crate_, v, path.display());
+                read_tarball_package(&path, crate_, &v).map_err(|e| {
+                    warn!("Error while reading manifest from {}: {}", path.display(), e);
+                }).ok()
+            }
+            None => {
+                trace!("Crate {}={} not found among vendored sources", crate_, version);
+                None
+            }
+        }
+    }
+}
+
+/// List the entries of given directory, ignoring any errors.
+fn list_dir(dir: &Path) -> Vec<PathBuf> {
+    match fs::read_dir(dir) {
+        Ok(entries) => entries.filter_map(Result::ok).map(|e| e.path()).collect(),
+        Err(e) => {
+            warn!("Cannot read directory {}: {}", dir.display(), e);
+            vec![]
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use semver::{Version, VersionReq};
+    use toml;
+    use testing::create_temp_dir;
+    use super::VendoredSources;
+
+    fn manifest(name: &str, version: &str) -> String {
+        format!("[package]\nname = \"{}\"\nversion = \"{}\"\n", name, version)
+    }
+
+    #[test]
+    fn vendored_crates() {
+        let root = create_temp_dir(&[
+            ("third-party/foo/Cargo.toml", &manifest("foo", "1.2.0")),
+            ("third-party/foo-0.9.1/Cargo.toml", &manifest("foo", "0.9.1")),
+            ("third-party/foo-bar/Cargo.toml", &manifest("foo-bar", "1.0.0")),
+        ]);
+        let config = toml::from_str(&format!(
+            concat!(
+                "[source.crates-io]\nreplace-with = \"vendored\"\n",
+                "[source.vendored]\ndirectory = \"{}\"\n",
+            ),
+            root.join("third-party").display(),
+        )).unwrap();
+        let sources = VendoredSources::for_project(&root, &config);
+
+        let find = |req: &str, locked: Option<&str>| {
+            let locked = locked.map(|l| Version::parse(l).unwrap());
+            sources.find_package("foo", &VersionReq::parse(req).unwrap(), locked.as_ref())
+                .map(|p| p.version)
+        };
+        assert_eq!(Some("1.2.0".into()), find("*", None));
+        assert_eq!(Some("0.9.1".into()), find("0.9", None));
+        assert_eq!(Some("0.9.1".into()), find("*", Some("0.9.1")));
+        assert_eq!(None, find("2", None));
+    }
+
+    #[test]
+    fn default_vendor_dir() {
+        let root = create_temp_dir(&[("vendor/foo/Cargo.toml", &manifest("foo", "1.0.0"))]);
+        let sources = VendoredSources::for_project(&root, &Default::default());
+        assert!(sources.find_package("foo", &VersionReq::any(), None).is_some());
+
+        let sources = VendoredSources::for_project(root.join("vendor"), &Default::default());
+        assert!(sources.is_empty());
+    }
+}
@@ -10,6 +10,7 @@
              extern crate dirs;
 #[macro_use] extern crate enum_derive;
              extern crate exitcode;
+             extern crate flate2;
              extern crate futures;
              extern crate glob;
              extern crate hubcaps;
@@ -30,6 +31,7 @@
              extern crate slog_envlogger;
              extern crate slog_stdlog;
              extern crate slog_stream;
+             extern crate tar;
              extern crate tokio_core;
              extern crate toml;
              extern crate url;
@@ -42,17 +44,21 @@
 
 
 mod args;
+mod config;
 mod display;
 mod ext;
 mod issues;
 mod logging;
 mod model;
+#[cfg(test)] mod testing;
 mod util;
 
 
 use std::borrow::Cow;
+use std::collections::HashSet;
+use std::env;
 use std::error::Error;
-use std::path::Path;
+use std::path::PathBuf;
 use std::process::exit;
 
 use futures::Stream;
@@ -60,8 +66,9 @@ use log::LogLevel::*;
 use tokio_core::reactor::Core;
 
 use args::{ArgsError, Options};
-use issues::SuggestedIssuesProducer;
-use model::Issue;
+use config::Config;
+use issues::{DependencyFilter, find_manifest, find_projects, NameFilter, SuggestedIssuesProducer};
+use model::{DependencyKind, Forge, ForkPolicy, Issue};
 
 
 lazy_static! {
@@ -130,24 +137,48 @@ fn log_signature() {
 /// Suggest issues to contribute to based on given command line options,
 /// and print them to stdout.
 fn suggest_contributions(core: &mut Core, opts: &Options) -> ! {
-    let manifest_path = opts.manifest_path.as_ref()
-        .map(|p| p as &Path).unwrap_or_else(|| Path::new("./Cargo.toml"));
-    if !manifest_path.is_file() {
-        error!("Couldn't find crate manifest {}.",
-            match opts.manifest_path {
-                Some(ref path) => format!("under {}", path.display()),
-                None => "; make sure you're in the crate root directory.".into(),
-            });
-        exit(exitcode::NOINPUT);
-    }
-
     // TODO: consider doing the OAuth flow via a browser and saving the access token+secret
     // as another mode of authentication
-    let producer = match opts.github_token {
+    let mut producer = match opts.github_token {
         Some(ref t) => SuggestedIssuesProducer::with_github_token(t, &core.handle()),
         None => SuggestedIssuesProducer::new(&core.handle()),
     };
-    let mut issues = producer.suggest_issues(manifest_path).unwrap_or_else(|e| {
+    if let Some(ref token) = opts.gitlab_token {
+        producer.set_gitlab_token(token);
+    }
+    producer.set_dependency_filter(DependencyFilter{
+        kinds: opts.kinds.clone(),
+        target: opts.target.clone(),
+        features: opts.features.clone(),
+        only_enabled: opts.only_enabled,
+    });
+    let config = Config::load().unwrap_or_else(|e| {
+        error!("Failed to read the config file: {}", e);
+        exit(exitcode::CONFIG);
+    });
+    configure_hosts(&mut producer, &config);
+    producer.set_repository_overrides(&config.repositories).unwrap_or_else(|e| {
+        error!("Invalid entry in the `repositories` table of the config file: {}", e);
+        exit(exitcode::CONFIG);
+    });
+    producer.set_name_filter(create_name_filter(opts, &config));
+    producer.set_fork_policy(fork_policy(opts, &config));
+    producer.set_skip_archived(!(opts.include_archived || config.include_archived));
+    producer.set_max_depth(opts.depth);
+    producer.set_use_cargo_metadata(opts.use_metadata);
+    if opts.list_unresolved {
+        list_unresolved_dependencies(core, &producer, opts);
+    }
+    let issues = match (&opts.crate_, &opts.reverse_deps) {
+        (&Some((ref name, ref version)), _) => producer.suggest_crate_issues(name, version),
+        (_, &Some(ref name)) => Ok(producer.suggest_reverse_dependency_issues(name)),
+        _ => {
+            let manifest_paths = find_manifests(opts);
+            log_manifests(&manifest_paths);
+            producer.suggest_issues(&manifest_paths)
+        }
+    };
+    let mut issues = issues.unwrap_or_else(|e| {
         error!("Failed to suggest issues: {}", e);
         exit(exitcode::IOERR);
     });
@@ -172,11 +203,154 @@ fn suggest_contributions(core: &mut Core, opts: &Options) -> ! {
     exit(exitcode::OK)
 }
 
+/// Print the dependencies whose repositories cannot be determined, rather than any issues.
+fn list_unresolved_dependencies(
+    core: &mut Core, producer: &SuggestedIssuesProducer, opts: &Options,
+) -> ! {
+    let manifest_paths = find_manifests(opts);
+    log_manifests(&manifest_paths);
+    let deps = producer.unresolved_dependencies(&manifest_paths)
+        .and_then(|deps| core.run(deps))
+        .unwrap_or_else(|e| {
+            error!("Failed to determine the dependencies' repositories: {}", e);
+            exit(exitcode::IOERR);
+        });
+    if deps.is_empty() {
+        info!("Repositories of all the dependencies are known");
+    }
+    for dep in deps {
+        println!("{}", dep);
+    }
+    exit(exitcode::OK)
+}
+
+#[inline]
+fn log_manifests(manifest_paths: &[PathBuf]) {
+    for path in manifest_paths {
+        info!("Using crate manifest {}", path.display());
+    }
+}
+
+/// Create the filter for crate & repository names,
+/// combining the patterns from command line with those from the config file.
+fn create_name_filter(opts: &Options, config: &Config) -> NameFilter {
+    let include = config.include.iter().chain(&opts.include);
+    let exclude = config.exclude.iter().chain(&opts.exclude);
+    let filter = NameFilter::new(include, exclude).unwrap_or_else(|e| {
+        error!("Invalid pattern in the config file: {}", e);
+        exit(exitcode::CONFIG);
+    });
+    if !filter.is_empty() {
+        debug!("Using crate/repository filter: {:?}", filter);
+    }
+    filter
+}
+
+/// Determine which repositories to look at for crates hosted in forks,
+/// with the command line taking precedence over the config file.
+fn fork_policy(opts: &Options, config: &Config) -> ForkPolicy {
+    if let Some(policy) = opts.forks {
+        return policy;
+    }
+    config.forks.as_ref().map(|f| f.parse().unwrap_or_else(|e| {
+        error!("Invalid `forks` setting in the config file: {}", e);
+        exit(exitcode::CONFIG);
+    })).unwrap_or_default()
+}
+
+/// Make the self-hosted forge instances from the config file known to the producer.
+fn configure_hosts(producer: &mut SuggestedIssuesProducer, config: &Config) {
+    for hc in &config.hosts {
+        let forge: Forge = hc.forge.parse().unwrap_or_else(|e| {
+            error!("Invalid forge of host {} in the config file: {}", hc.host, e);
+            exit(exitcode::CONFIG);
+        });
+        debug!("Using {} host {}", forge, hc.host);
+        producer.configure_host(forge, &hc.host,
+            hc.api_root.as_ref().map(|r| r.as_str()), hc.token.as_ref().map(|t| t.as_str()));
+    }
+}
+
+/// Determine the manifests of projects to suggest issues for, based on command line options.
+fn find_manifests(opts: &Options) -> Vec<PathBuf> {
+    // The same manifest can be given in different ways (e.g. with a relative path),
+    // so we compare their canonical paths to only look at each of them once.
+    let mut result = vec![];
+    let mut seen = HashSet::new();
+    let mut is_new = |path: &PathBuf| {
+        seen.insert(path.canonicalize().unwrap_or_else(|_| path.clone()))
+    };
+    for path in &opts.manifest_paths {
+        if !path.is_file() {
+            error!("Couldn't find crate manifest under {}.", path.display());
+            exit(exitcode::NOINPUT);
+        }
+        if is_new(path) {
+            result.push(path.clone());
+        }
+    }
+    for dir in &opts.recursive_dirs {
+        let projects = find_projects(dir);
+        if projects.is_empty() {
+            warn!("No Rust projects found under {}", dir.display());
+        }
+        result.extend(projects.into_iter().filter(|p| is_new(p)));
+    }
+    if !result.is_empty() {
+        return result;
+    }
+    if !opts.recursive_dirs.is_empty() {
+        error!("Couldn't find any crate manifests to look through.");
+        exit(exitcode::NOINPUT);
+    }
+
+    // Like Cargo, look for the manifest in the current directory and all of its parents.
+    let cwd = env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+    let manifest_path = find_manifest(&cwd).unwrap_or_else(|| {
+        error!("Couldn't find Cargo.toml in {} or any of its parent directories; {}",
+            cwd.display(), "make sure you're inside a crate directory.");
+        exit(exitcode::NOINPUT);
+    });
+    vec![manifest_path]
+}
+
 /// Print a single issue to standard output.
 fn print_issue(fmt: Option<&str>, issue: &Issue) -> Result<(), Box<dyn Error>> {
     match fmt {
         Some(f) => println!("{}", display::format_issue(f, issue)?),
-        None => println!("{} -- {}", issue, issue.url),
+        None => {
+            // Point out the issues that come from dependencies other than the regular ones,
+            // optional ones (along with the feature that enables them),
+            // those pinned to a particular Git branch/tag/revision,
+            // and those of crates that we've patched (either upstream's or our fork's).
+            // Also point out the issues which mention our crate (when looking at its dependents).
+            let mut notes = vec![];
+            if let Some(ref crate_) = issue.mentioned_crate {
+                notes.push(format!("mentions {}", crate_));
+            }
+            if let Some(ref dep) = issue.dependency {
+                if dep.kind() != DependencyKind::Normal {
+                    notes.push(dep.kind().to_string());
+                }
+                if dep.is_optional() {
+                    notes.push(match dep.feature() {
+                        Some(f) => format!("feature {}", f),
+                        None => "optional".to_owned(),
+                    });
+                }
+                if dep.is_patched() {
+                    notes.push("patched".to_owned());
+                }
+                if let Some(r) = dep.git_reference() {
+                    notes.push(format!("{} {}", r.key(), r.value()));
+                }
+            }
+            if notes.is_empty() {
+                println!("{} -- {}", issue, issue.url);
+            } else {
+                println!("{} -- {} ({})", issue, issue.url, notes.join(", "));
+            }
+        }
     }
     Ok(())
 }
@@ -0,0 +1,569 @@
+//! Module with the data types related to code hosting sites (forges), like GitHub.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+use hubcaps::search::IssuesItem;
+use url::Url;
+
+use super::{Dependency, GitUrl};
+
+
+const GITHUB_HOST: &str = "github.com";
+const GITLAB_HOST: &str = "gitlab.com";
+const CODEBERG_HOST: &str = "codeberg.org";
+const SOURCEHUT_HOST: &str = "git.sr.ht";
+const BITBUCKET_HOST: &str = "bitbucket.org";
+
+/// Public instances of the forges, along with the short names they can be referred to by.
+const PUBLIC_HOSTS: &[(&str, &str, Forge)] = &[
+    ("github", GITHUB_HOST, Forge::GitHub),
+    ("gitlab", GITLAB_HOST, Forge::GitLab),
+    ("codeberg", CODEBERG_HOST, Forge::Gitea),
+    ("gitea", "gitea.com", Forge::Gitea),
+    ("sourcehut", SOURCEHUT_HOST, Forge::SourceHut),
+    ("sourcehut", "hg.sr.ht", Forge::SourceHut),
+    ("bitbucket", BITBUCKET_HOST, Forge::Bitbucket),
+];
+
+/// Prefix of hostnames that are assumed to be self-hosted GitLab instances.
+const GITLAB_HOST_PREFIX: &str = "gitlab.";
+
+
+/// Kind of a code hosting site (forge) that a repository can be found on.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Forge {
+    GitHub,
+    GitLab,
+    /// Gitea, or any of its forks with a compatible API, like Forgejo (which runs Codeberg).
+    Gitea,
+    /// SourceHut (sr.ht), whose issue trackers are on a separate todo.* host.
+    SourceHut,
+    /// Bitbucket Cloud.
+    Bitbucket,
+}
+
+impl Forge {
+    /// Name of the forge software.
+    pub fn name(self) -> &'static str {
+        match self {
+            Forge::GitHub => "github",
+            Forge::GitLab => "gitlab",
+            Forge::Gitea => "gitea",
+            Forge::SourceHut => "sourcehut",
+            Forge::Bitbucket => "bitbucket",
+        }
+    }
+
+    /// Hostname of the forge's main public instance.
+    pub fn default_host(self) -> &'static str {
+        match self {
+            Forge::GitHub => GITHUB_HOST,
+            Forge::GitLab => GITLAB_HOST,
+            Forge::Gitea => CODEBERG_HOST,
+            Forge::SourceHut => SOURCEHUT_HOST,
+            Forge::Bitbucket => BITBUCKET_HOST,
+        }
+    }
+
+    /// Determine the forge running on given public host (without the "www." prefix).
+    ///
+    /// Self-hosted instances are only recognized if they are GitLab's with a "gitlab." hostname;
+    /// use `ForgeHosts` for the others.
+    pub fn from_host(host: &str) -> Option<Self> {
+        if let Some(&(_, _, forge)) = PUBLIC_HOSTS.iter().find(|&&(_, h, _)| h == host) {
+            return Some(forge);
+        }
+        if host.starts_with(GITLAB_HOST_PREFIX) {
+            return Some(Forge::GitLab);
+        }
+        None
+    }
+}
+
+impl FromStr for Forge {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "github" => Ok(Forge::GitHub),
+            "gitlab" => Ok(Forge::GitLab),
+            "gitea" | "forgejo" => Ok(Forge::Gitea),
+            "sourcehut" | "srht" => Ok(Forge::SourceHut),
+            "bitbucket" => Ok(Forge::Bitbucket),
+            _ => Err(format!("unknown forge `{}`", s)),
+        }
+    }
+}
+
+impl fmt::Display for Forge {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}", self.name())
+    }
+}
+
+
+/// Forge instances known by their hostnames,
+/// which includes the self-hosted ones configured by the user on top of the public ones.
+#[derive(Clone, Debug, Default)]
+pub struct ForgeHosts {
+    custom: HashMap<String, Forge>,
+}
+
+impl ForgeHosts {
+    /// Create the mapping of just the public forge instances.
+    #[inline]
+    pub fn new() -> Self {
+        ForgeHosts::default()
+    }
+
+    /// Add a self-hosted instance of given forge, so that its repositories are recognized.
+    pub fn insert<H: ToString>(&mut self, host: H, forge: Forge) {
+        let host = host.to_string();
+        trace!("Registering {} as a {} host", host, forge);
+        self.custom.insert(host, forge);
+    }
+
+    /// Determine the forge running on given host (without the "www." prefix), if it's known.
+    pub fn forge(&self, host: &str) -> Option<Forge> {
+        self.custom.get(host).cloned().or_else(|| Forge::from_host(host))
+    }
+}
+
+
+/// Which repositories to look at when the repository of a crate is a fork of another one.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ForkPolicy {
+    /// Only the fork itself.
+    Fork,
+    /// Only the repository it has been forked from.
+    Upstream,
+    /// Both the fork and its upstream repository.
+    Both,
+}
+
+impl ForkPolicy {
+    /// Short name of the policy, as accepted by `from_str`.
+    #[inline]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ForkPolicy::Fork => "fork",
+            ForkPolicy::Upstream => "upstream",
+            ForkPolicy::Both => "both",
+        }
+    }
+}
+
+impl Default for ForkPolicy {
+    fn default() -> Self { ForkPolicy::Both }
+}
+
+impl FromStr for ForkPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "fork" => Ok(ForkPolicy::Fork),
+            "upstream" | "parent" => Ok(ForkPolicy::Upstream),
+            "both" => Ok(ForkPolicy::Both),
+            _ => Err(format!("unknown policy for forks `{}`", s)),
+        }
+    }
+}
+
+impl fmt::Display for ForkPolicy {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}", self.as_str())
+    }
+}
+
+
+/// Represents a repository on one of the supported forges.
+#[derive(Clone, Debug)]
+pub struct Repository {
+    pub forge: Forge,
+    /// Hostname of the forge instance.
+    pub host: String,
+    /// Owner of the repository.
+    /// On GitLab, this can also be a group path with subgroups, like "group/subgroup",
+    /// while on SourceHut, it's the username prefixed with a tilde, like "~user".
+    pub owner: String,
+    pub name: String,
+    /// Subdirectory of the repository where the crate lives, if it's a monorepo
+    /// (and its URL pointed to that subdirectory).
+    ///
+    /// This isn't a part of the repository's identity, so that a monorepo is the same repository
+    /// regardless of which of its crates has led us to it.
+    pub subdir: Option<String>,
+}
+
+impl PartialEq for Repository {
+    fn eq(&self, other: &Self) -> bool {
+        self.forge == other.forge && self.host == other.host
+            && self.owner == other.owner && self.name == other.name
+    }
+}
+
+impl Eq for Repository {}
+
+impl Hash for Repository {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.forge.hash(state);
+        self.host.hash(state);
+        self.owner.hash(state);
+        self.name.hash(state);
+    }
+}
+
+impl Repository {
+    /// Create a repository on github.com.
+    #[inline]
+    #[cfg_attr(feature = "cargo-clippy", allow(needless_pass_by_value))]
+    pub fn new<O: ToString, N: ToString>(owner: O, name: N) -> Self {
+        Repository::with_host(Forge::GitHub, GITHUB_HOST, owner, name)
+    }
+
+    /// Create a repository on given forge instance.
+    #[inline]
+    #[cfg_attr(feature = "cargo-clippy", allow(needless_pass_by_value))]
+    pub fn with_host<H: ToString, O: ToString, N: ToString>(
+        forge: Forge, host: H, owner: O, name: N
+    ) -> Self {
+        Repository {
+            forge,
+            host: host.to_string(),
+            owner: owner.to_string(),
+            name: name.to_string(),
+            subdir: None,
+        }
+    }
+
+    /// Determine the repository from given URL.
+    ///
+    /// This can be either the URL of the project's homepage,
+    /// or a URL of the Git repository (in any of the forms accepted by Cargo).
+    ///
+    /// URLs pointing to a subdirectory of the repository (as used by crates in monorepos,
+    /// e.g. github.com/$OWNER/$NAME/tree/$REF/$SUBDIR) are accepted as well.
+    ///
+    /// Only the repositories on given forge hosts are recognized.
+    pub fn from_url<U: AsRef<str>>(repo_url: U, hosts: &ForgeHosts) -> Option<Self> {
+        let repo_url = repo_url.as_ref();
+        let parsed = GitUrl::parse(repo_url)?;
+        let host = parsed.host.trim_start_matches("www.");
+        let forge = hosts.forge(host)?;
+        let segs = parsed.path_segments();
+        let (mut repo, page) = match forge {
+            // github.com/$OWNER/$NAME (project homepage)
+            // or github.com/$OWNER/$NAME.git (direct Git repo URL)
+            // or github.com/$OWNER/$NAME/tree/$REF[/$SUBDIR] (browsing the code)
+            Forge::GitHub if segs.len() == 2 || (segs.len() > 3 && segs[2] == "tree") =>
+                (Repository::with_host(forge, host, segs[0], segs[1]), &segs[2..]),
+            Forge::GitHub => return None,
+            // codeberg.org/$OWNER/$NAME or bitbucket.org/$WORKSPACE/$NAME,
+            // possibly followed by the path of a particular page
+            Forge::Gitea | Forge::Bitbucket if segs.len() >= 2 =>
+                (Repository::with_host(forge, host, segs[0], segs[1]), &segs[2..]),
+            // git.sr.ht/~$USER/$NAME
+            Forge::SourceHut if segs.len() >= 2 && segs[0].starts_with('~') =>
+                (Repository::with_host(forge, host, segs[0], segs[1]), &segs[2..]),
+            Forge::Gitea | Forge::Bitbucket | Forge::SourceHut => return None,
+            // gitlab.com/$GROUP[/$SUBGROUP...]/$NAME, possibly followed by /-/$PAGE
+            Forge::GitLab => {
+                let repo_len = segs.iter().take_while(|&&s| s != "-").count();
+                if repo_len < 2 {
+                    return None;
+                }
+                let (name, owner) = segs[..repo_len].split_last()?;
+                let page = if repo_len < segs.len() { &segs[repo_len + 1..] } else { &[] };
+                (Repository::with_host(forge, host, owner.join("/"), name), page)
+            }
+        };
+        repo.subdir = tree_subdir(forge, page);
+        match repo.subdir {
+            Some(ref subdir) =>
+                trace!("URL {} identified as {} repo {} (in {})", repo_url, forge, repo, subdir),
+            None => trace!("URL {} identified as {} repo {}", repo_url, forge, repo),
+        }
+        Some(repo)
+    }
+
+    /// Parse the repository from its name in the same form it is displayed in,
+    /// i.e. `owner/repo` for github.com, `$SHORT_NAME:owner/repo` for other public instances
+    /// (where the short name can also be the name of the forge, like "gitlab"),
+    /// and `$HOST:owner/repo` for any of given forge hosts. Repository URLs are accepted as well.
+    pub fn parse(s: &str, hosts: &ForgeHosts) -> Result<Self, String> {
+        let s = s.trim();
+        if s.contains("://") {
+            return Repository::from_url(s, hosts)
+                .ok_or_else(|| format!("unrecognized repository URL `{}`", s));
+        }
+        let (prefix, path) = match s.find(':') {
+            Some(idx) => (&s[..idx], &s[idx + 1..]),
+            None => (GITHUB_HOST, s),
+        };
+        let public = PUBLIC_HOSTS.iter().find(|&&(short_name, _, _)| short_name == prefix);
+        let (forge, host) = match (public, prefix.parse::<Forge>()) {
+            (Some(&(_, host, forge)), _) => (forge, host),
+            (None, Ok(forge)) => (forge, forge.default_host()),
+            (None, Err(_)) => {
+                let forge = hosts.forge(prefix)
+                    .ok_or_else(|| format!("unknown host `{}`", prefix))?;
+                (forge, prefix)
+            }
+        };
+        // Only GitLab allows for nested groups, so the repository name is the last segment.
+        let path = path.trim_matches('/');
+        match path.rfind('/') {
+            Some(idx) if idx > 0 && idx < path.len() - 1 =>
+                Ok(Repository::with_host(forge, host, &path[..idx], &path[idx + 1..])),
+            _ => Err(format!("repository must be given as `owner/name`, got `{}`", path)),
+        }
+    }
+}
+
+/// Determine the subdirectory of a repository from the path of the page browsing its code,
+/// i.e. the URL path segments that follow the repository itself.
+fn tree_subdir(forge: Forge, page: &[&str]) -> Option<String> {
+    let first = page.first().cloned();
+    let prefix_len = match forge {
+        // github.com/$OWNER/$NAME/tree/$REF/$SUBDIR
+        // gitlab.com/$GROUP/$NAME/-/tree/$REF/$SUBDIR
+        Forge::GitHub | Forge::GitLab if first == Some("tree") => 2,
+        // codeberg.org/$OWNER/$NAME/src/branch/$REF/$SUBDIR
+        Forge::Gitea if first == Some("src") => 3,
+        // bitbucket.org/$WORKSPACE/$NAME/src/$REF/$SUBDIR
+        Forge::Bitbucket if first == Some("src") => 2,
+        // git.sr.ht/~$USER/$NAME/tree/$REF/item/$SUBDIR
+        Forge::SourceHut if first == Some("tree") && page.get(2) == Some(&"item") => 3,
+        _ => return None,
+    };
+    if page.len() > prefix_len {
+        Some(page[prefix_len..].join("/"))
+    } else {
+        None
+    }
+}
+
+impl fmt::Display for Repository {
+    /// Format the repository as `$OWNER/$NAME` for github.com,
+    /// `$SHORT_NAME:$OWNER/$NAME` for other public instances (e.g. `gitlab:owner/repo`),
+    /// and `$HOST:$OWNER/$NAME` otherwise.
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let public = PUBLIC_HOSTS.iter().find(|&&(_, h, f)| h == self.host && f == self.forge);
+        match public {
+            Some(&(_, GITHUB_HOST, _)) => {}
+            Some(&(short_name, _, _)) => write!(fmt, "{}:", short_name)?,
+            None => write!(fmt, "{}:", self.host)?,
+        }
+        write!(fmt, "{}/{}", self.owner, self.name)
+    }
+}
+
+
+#[derive(Debug)]
+pub struct Issue {
+    /// Repository where this issue comes from.
+    pub repo: Repository,
+    /// Issue number.
+    pub number: u64,
+    /// URL to the HTML page of the issue.
+    pub url: String,
+    /// Issue title.
+    pub title: String,
+    /// Issue text (body of the first comment).
+    pub body: String,
+    /// Number of comments on the issue.
+    pub comment_count: usize,
+    /// Names of the issue's labels.
+    pub labels: Vec<String>,
+    /// Dependency which has led us to the issue's repository, if known.
+    /// When looking at the dependents of our crate, this is our crate.
+    pub dependency: Option<Dependency>,
+    /// Crate depending on ours whose repository the issue comes from
+    /// (when looking at the dependents of our crate).
+    pub dependent: Option<String>,
+    /// Crate of ours that the issue mentions, which makes it especially relevant.
+    pub mentioned_crate: Option<String>,
+}
+
+impl From<IssuesItem> for Issue {
+    fn from(input: IssuesItem) -> Self {
+        let (owner, project) = repo_tuple(&input);
+        // The issue's page is on the same host as the repository, be it github.com
+        // or a GitHub Enterprise server.
+        let host = Url::parse(&input.html_url).ok()
+            .and_then(|url| url.host_str().map(String::from))
+            .unwrap_or_else(|| GITHUB_HOST.to_owned());
+        Issue{
+            repo: Repository::with_host(Forge::GitHub, host, owner, project),
+            number: input.number,
+            url: input.html_url,
+            title: input.title,
+            body: input.body.unwrap_or_else(String::new),
+            comment_count: input.comments as usize,
+            labels: input.labels.into_iter().map(|l| l.name).collect(),
+            dependency: None,
+            dependent: None,
+            mentioned_crate: None,
+        }
+    }
+}
+/// A fixed version of hubcaps::IssuesItem::repo_tuple,
+/// because the original in 0.5.0 doesn't handle URLs GitHub returns
+/// (i.e. "https://api.github.com/repos/$OWNER/$REPO").
+fn repo_tuple(issues_item: &IssuesItem) -> (String, String) {
+    let url = Url::parse(&issues_item.repository_url).unwrap();
+    let segs = url.path_segments().map(|ps| ps.collect()).unwrap_or_else(Vec::new);
+    let owner = if segs.len() > 1 { segs[segs.len() - 2] } else { "" };
+    let project = if segs.len() > 0 { segs[segs.len() - 1] } else { "" };
+    (owner.to_owned(), project.to_owned())
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "[{}] #{}: {}", self.repo, self.number, self.title)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{Forge, ForgeHosts, Repository};
+
+    fn repo_from_url(url: &str) -> Option<Repository> {
+        Repository::from_url(url, &ForgeHosts::new())
+    }
+
+    #[test]
+    fn repository_from_project_url() {
+        let repo = repo_from_url("https://github.com/Xion/gisht").unwrap();
+        assert_eq!("Xion", repo.owner);
+        assert_eq!("gisht", repo.name);
+    }
+
+    #[test]
+    fn repository_from_git_url() {
+        let repo = repo_from_url("https://github.com/Xion/callee.git").unwrap();
+        assert_eq!("Xion", repo.owner);
+        assert_eq!("callee", repo.name);
+    }
+
+    #[test]
+    fn repository_from_ssh_url() {
+        let repo = repo_from_url("ssh://git@github.com/rust-lang/rust-clippy.git").unwrap();
+        assert_eq!("rust-lang", repo.owner);
+        assert_eq!("rust-clippy", repo.name);
+    }
+
+    #[test]
+    fn repository_from_gitlab_url() {
+        let repo = repo_from_url("https://gitlab.com/Xion/gisht").unwrap();
+        assert_eq!(Forge::GitLab, repo.forge);
+        assert_eq!("gitlab:Xion/gisht", repo.to_string());
+
+        let repo = repo_from_url(
+            "https://gitlab.example.com/group/subgroup/project/-/tree/master").unwrap();
+        assert_eq!("gitlab.example.com", repo.host);
+        assert_eq!("group/subgroup", repo.owner);
+        assert_eq!("project", repo.name);
+        assert_eq!("gitlab.example.com:group/subgroup/project", repo.to_string());
+
+        assert_eq!(None, repo_from_url("https://gitlab.com/Xion"));
+    }
+
+    #[test]
+    fn repository_from_gitea_url() {
+        let repo = repo_from_url("https://codeberg.org/forgejo/forgejo/src/branch/forgejo")
+            .unwrap();
+        assert_eq!(Forge::Gitea, repo.forge);
+        assert_eq!("codeberg:forgejo/forgejo", repo.to_string());
+
+        assert_eq!(None, repo_from_url("https://git.example.org/owner/repo"));
+        let mut hosts = ForgeHosts::new();
+        hosts.insert("git.example.org", Forge::Gitea);
+        let repo = Repository::from_url("https://git.example.org/owner/repo.git", &hosts)
+            .unwrap();
+        assert_eq!(Forge::Gitea, repo.forge);
+        assert_eq!("git.example.org:owner/repo", repo.to_string());
+    }
+
+    #[test]
+    fn repository_from_github_enterprise_url() {
+        assert_eq!(None, repo_from_url("https://github.corp.example.com/team/repo"));
+        let mut hosts = ForgeHosts::new();
+        hosts.insert("github.corp.example.com", Forge::GitHub);
+        let repo = Repository::from_url("https://github.corp.example.com/team/repo", &hosts)
+            .unwrap();
+        assert_eq!(Forge::GitHub, repo.forge);
+        assert_eq!("github.corp.example.com", repo.host);
+        assert_eq!("github.corp.example.com:team/repo", repo.to_string());
+    }
+
+    #[test]
+    fn repository_from_sourcehut_url() {
+        let repo = repo_from_url("https://git.sr.ht/~sircmpwn/scdoc").unwrap();
+        assert_eq!(Forge::SourceHut, repo.forge);
+        assert_eq!("~sircmpwn", repo.owner);
+        assert_eq!("sourcehut:~sircmpwn/scdoc", repo.to_string());
+        assert!(repo_from_url("git@git.sr.ht:~sircmpwn/scdoc").is_some());
+        assert_eq!(None, repo_from_url("https://git.sr.ht/sircmpwn/scdoc"));
+    }
+
+    #[test]
+    fn repository_from_bitbucket_url() {
+        let repo = repo_from_url("https://bitbucket.org/owner/repo/src/master/").unwrap();
+        assert_eq!(Forge::Bitbucket, repo.forge);
+        assert_eq!("bitbucket:owner/repo", repo.to_string());
+    }
+
+    #[test]
+    fn repository_from_monorepo_url() {
+        let repo = repo_from_url(
+            "https://github.com/tokio-rs/tokio/tree/master/tokio-util").unwrap();
+        assert_eq!(Repository::new("tokio-rs", "tokio"), repo);
+        assert_eq!(Some("tokio-util"), repo.subdir.as_ref().map(|s| s.as_str()));
+        let other_crate_repo = repo_from_url(
+            "https://github.com/tokio-rs/tokio/tree/master/tokio-stream").unwrap();
+        assert_eq!(repo, other_crate_repo);
+
+        let repo = repo_from_url(
+            "https://gitlab.com/group/project/-/tree/main/crates/foo").unwrap();
+        assert_eq!("group/project", format!("{}/{}", repo.owner, repo.name));
+        assert_eq!(Some("crates/foo".into()), repo.subdir);
+
+        let repo = repo_from_url(
+            "https://codeberg.org/owner/repo/src/branch/main/crates/foo").unwrap();
+        assert_eq!(Some("crates/foo".into()), repo.subdir);
+
+        let repo = repo_from_url("https://github.com/owner/repo/tree/main").unwrap();
+        assert_eq!(None, repo.subdir);
+        assert_eq!(None, repo_from_url("https://github.com/owner/repo/issues"));
+    }
+
+    #[test]
+    fn repository_parse() {
+        let hosts = ForgeHosts::new();
+        assert_eq!(Ok(Repository::new("Xion", "gisht")), Repository::parse("Xion/gisht", &hosts));
+        assert_eq!(Ok(Repository::with_host(Forge::GitLab, "gitlab.com", "group/sub", "project")),
+            Repository::parse("gitlab:group/sub/project", &hosts));
+        assert_eq!(Ok(Repository::with_host(Forge::Gitea, "codeberg.org", "owner", "repo")),
+            Repository::parse("codeberg:owner/repo", &hosts));
+        assert_eq!(Ok(Repository::new("Xion", "gisht")),
+            Repository::parse("https://github.com/Xion/gisht", &hosts));
+        assert!(Repository::parse("unknown.example.com:owner/repo", &hosts).is_err());
+        assert!(Repository::parse("github:gisht", &hosts).is_err());
+
+        let mut hosts = ForgeHosts::new();
+        hosts.insert("unknown.example.com", Forge::Gitea);
+        assert_eq!(Ok(Repository::with_host(Forge::Gitea, "unknown.example.com", "owner", "repo")),
+            Repository::parse("unknown.example.com:owner/repo", &hosts));
+    }
+
+    #[test]
+    fn repository_from_other_host() {
+        assert_eq!(None, repo_from_url("https://example.com/Xion/gisht"));
+    }
+}
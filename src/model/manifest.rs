@@ -3,11 +3,14 @@
 use std::collections::BTreeMap;
 use std::fmt;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
-use semver::VersionReq;
+use semver::{Version, VersionReq};
 use serde::de::Error;
 use toml::{self, Value as Toml};
 
+use super::GitReference;
+
 
 /// Represents the [package] section of Cargo.toml.
 #[derive(Debug, Deserialize)]
@@ -20,6 +23,7 @@ pub struct Package {
     #[serde(default)]
     pub description: String,
     /// Author(s) of the crate.
+    #[serde(default)]
     pub authors: Vec<String>,
     /// Crate license.
     #[serde(default)]
@@ -43,11 +47,31 @@ pub struct Package {
 
 
 /// A dependent crate read from Cargo.toml manifest.
+#[derive(Clone)]
 pub struct Dependency {
-    /// Name of the crate.
+    /// Name of the crate (its actual package name).
     name: String,
+    /// Name that the crate is referred to in the dependent crate's manifest,
+    /// if it's been renamed there with `package = "..."`.
+    alias: Option<String>,
     /// Location of crate's sources.
     location: CrateLocation,
+    /// Kind of the dependency, i.e. which section of the manifest it comes from.
+    kind: DependencyKind,
+    /// Platform the dependency is specific to, if any.
+    /// This is either a target triple or a `cfg(...)` expression.
+    target: Option<String>,
+    /// Whether the dependency is optional, i.e. only used when some feature is enabled.
+    optional: bool,
+    /// Feature of the dependent crate that enables this optional dependency,
+    /// if it's enabled at all.
+    feature: Option<String>,
+    /// Exact version of the crate that has been resolved for this dependency
+    /// (as recorded in Cargo.lock), if known.
+    locked_version: Option<Version>,
+    /// Crate that replaces this one in the dependency graph
+    /// via [patch] or [replace] section of the workspace manifest, if any.
+    patch: Option<Box<Dependency>>,
 }
 
 #[cfg_attr(feature = "cargo-clippy", allow(needless_pass_by_value))]
@@ -59,7 +83,9 @@ impl Dependency {
         let version = version.as_ref();
         Dependency{
             name: name.to_string(),
+            alias: None,
             location: CrateLocation::Registry{
+                registry: None,
                 version: if version == "*" {
                     VersionReq::any()
                 } else {
@@ -67,6 +93,12 @@ impl Dependency {
                     VersionReq::parse(version).unwrap()
                 },
             },
+            kind: DependencyKind::default(),
+            target: None,
+            optional: false,
+            feature: None,
+            locked_version: None,
+            patch: None,
         }
     }
 
@@ -76,7 +108,14 @@ impl Dependency {
     {
         Dependency{
             name: name.to_string(),
+            alias: None,
             location: CrateLocation::Filesystem{path: path.as_ref().to_owned()},
+            kind: DependencyKind::default(),
+            target: None,
+            optional: false,
+            feature: None,
+            locked_version: None,
+            patch: None,
         }
     }
 
@@ -86,10 +125,126 @@ impl Dependency {
     {
         Dependency{
             name: name.to_string(),
-            location: CrateLocation::Git{url: url.to_string()},
+            alias: None,
+            location: CrateLocation::Git{url: url.to_string(), reference: None},
+            kind: DependencyKind::default(),
+            target: None,
+            optional: false,
+            feature: None,
+            locked_version: None,
+            patch: None,
         }
     }
 
+    /// Mark the dependency as renamed, i.e. referring to a crate with given package name
+    /// under what used to be its name (which becomes an alias).
+    #[inline]
+    pub fn with_package_name<P: ToString>(self, package: P) -> Self {
+        let package = package.to_string();
+        if package == self.name {
+            return self;
+        }
+        Dependency{alias: Some(self.name), name: package, ..self}
+    }
+
+    /// Mark the dependency as referred to under given alias in the dependent crate's manifest.
+    #[inline]
+    pub fn with_alias<A: ToString>(self, alias: A) -> Self {
+        let alias = alias.to_string();
+        if alias == self.name {
+            return self;
+        }
+        Dependency{alias: Some(alias), ..self}
+    }
+
+    /// Mark the dependency as coming from the alternative registry with given name.
+    ///
+    /// Has no effect on dependencies that aren't hosted in a registry.
+    #[inline]
+    pub fn with_registry<R: ToString>(self, registry: R) -> Self {
+        match self.location {
+            CrateLocation::Registry{version, ..} => Dependency{
+                location: CrateLocation::Registry{registry: Some(registry.to_string()), version},
+                ..self
+            },
+            _ => self,
+        }
+    }
+
+    /// Pin the Git dependency to given branch, tag, or revision.
+    ///
+    /// Has no effect on dependencies that aren't hosted in a Git repository.
+    #[inline]
+    pub fn with_git_reference(self, reference: GitReference) -> Self {
+        match self.location {
+            CrateLocation::Git{url, ..} => Dependency{
+                location: CrateLocation::Git{url, reference: Some(reference)},
+                ..self
+            },
+            _ => self,
+        }
+    }
+
+    /// Change where the dependency is located, keeping its other attributes.
+    #[inline]
+    pub fn with_location(self, location: CrateLocation) -> Self {
+        Dependency{location, ..self}
+    }
+
+    /// Mark the dependency as being of given kind.
+    #[inline]
+    pub fn with_kind(self, kind: DependencyKind) -> Self {
+        Dependency{kind, ..self}
+    }
+
+    /// Mark the dependency as specific to given target platform.
+    #[inline]
+    pub fn with_target<T: ToString>(self, target: T) -> Self {
+        Dependency{target: Some(target.to_string()), ..self}
+    }
+
+    /// Mark the dependency as optional (or not).
+    #[inline]
+    pub fn with_optional(self, optional: bool) -> Self {
+        Dependency{optional, ..self}
+    }
+
+    /// Record the feature of the dependent crate that enables this optional dependency.
+    #[inline]
+    pub fn with_feature<F: ToString>(self, feature: F) -> Self {
+        Dependency{feature: Some(feature.to_string()), ..self}
+    }
+
+    /// Record the exact version that the dependency has been resolved to.
+    #[inline]
+    pub fn with_locked_version(self, version: Version) -> Self {
+        Dependency{locked_version: Some(version), ..self}
+    }
+
+    /// Mark the dependency as patched, i.e. replaced with given crate
+    /// in the dependency graph of the project.
+    #[inline]
+    pub fn with_patch(self, patch: Dependency) -> Self {
+        Dependency{patch: Some(Box::new(patch)), ..self}
+    }
+
+    /// Combine with the same dependency as listed by another crate (e.g. a workspace member).
+    ///
+    /// The strongest attributes of the two are kept: a normal dependency wins over a dev-
+    /// or build one, a required one over an optional one, and one that's enabled
+    /// by a feature over one that isn't.
+    pub fn merged_with(self, other: &Dependency) -> Self {
+        let kind = if other.kind == DependencyKind::Normal { other.kind } else { self.kind };
+        let target = if other.target.is_none() { None } else { self.target };
+        let optional = self.optional && other.optional;
+        let feature = if optional {
+            self.feature.or_else(|| other.feature.clone())
+        } else {
+            None
+        };
+        Dependency{kind, target, optional, feature, ..self}
+    }
+
     // TODO: consider implementing custom Deserialize instead
     /// Create a `Dependency` struct by interpreting a TOML value from Cargo.toml.
     pub fn from_toml<N: ToString>(name: N, toml: &Toml) -> Result<Self, toml::de::Error> {
@@ -107,61 +262,229 @@ impl Dependency {
                     v.type_str())));
             }
         }
-        match (attrs.get("version"), attrs.get("path"), attrs.get("git")) {
-            (Some(v), None,    None) => Ok(Dependency::with_version(name, v)),
-            (_,       Some(p), None) => Ok(Dependency::with_path(name, p)),
-            (None,    None,    Some(u)) => Ok(Dependency::with_git_url(name, u)),
-            _ => Err(toml::de::Error::custom(
+        let dep = match (attrs.get("version"), attrs.get("path"), attrs.get("git")) {
+            (Some(v), None,    None) => Dependency::with_version(name, v),
+            (_,       Some(p), None) => Dependency::with_path(name, p),
+            (None,    None,    Some(u)) => Dependency::with_git_url(name, u),
+            _ => return Err(toml::de::Error::custom(
                 "dependency must specify `version`, `path`, or `git`")),
-        }
+        };
+        let optional = match *toml {
+            Toml::Table(ref t) => match t.get("optional") {
+                None => false,
+                Some(&Toml::Boolean(b)) => b,
+                Some(v) => return Err(toml::de::Error::custom(format!(
+                    "`optional` must be a boolean, got {}", v.type_str()))),
+            },
+            _ => false,
+        };
+        let dep = dep.with_optional(optional);
+        let dep = match attrs.get("registry") {
+            Some(registry) => dep.with_registry(registry),
+            None => dep,
+        };
+        let git_ref = ["branch", "tag", "rev"].iter()
+            .filter_map(|&k| GitReference::from_key(k, attrs.get(k)?))
+            .next();
+        let dep = match git_ref {
+            Some(r) => dep.with_git_reference(r),
+            None => dep,
+        };
+        Ok(match attrs.get("package") {
+            Some(package) => dep.with_package_name(package),
+            None => dep,
+        })
     }
 }
 
 impl Dependency {
     #[inline]
     pub fn name(&self) -> &str { &self.name }
+    /// Name the dependency is referred to in the manifest.
+    /// This is the same as `name()` unless the dependency has been renamed.
+    #[inline]
+    pub fn alias(&self) -> &str { self.alias.as_ref().unwrap_or(&self.name) }
+    #[inline]
+    pub fn is_renamed(&self) -> bool { self.alias.is_some() }
     #[inline]
     pub fn location(&self) -> &CrateLocation { &self.location }
+    #[inline]
+    pub fn kind(&self) -> DependencyKind { self.kind }
+    #[inline]
+    pub fn target(&self) -> Option<&str> { self.target.as_ref().map(|t| t.as_str()) }
+    #[inline]
+    pub fn is_optional(&self) -> bool { self.optional }
+    /// Feature that enables this optional dependency.
+    /// None if the dependency isn't optional, or it's not enabled by any feature we know of.
+    #[inline]
+    pub fn feature(&self) -> Option<&str> { self.feature.as_ref().map(|f| f.as_str()) }
+    #[inline]
+    pub fn locked_version(&self) -> Option<&Version> { self.locked_version.as_ref() }
+    /// Crate that the dependency has been patched with, if any.
+    #[inline]
+    pub fn patch(&self) -> Option<&Dependency> { self.patch.as_ref().map(|p| &**p) }
+    #[inline]
+    pub fn is_patched(&self) -> bool { self.patch.is_some() }
+    /// Git branch, tag, or revision that the dependency is pinned to, if any.
+    #[inline]
+    pub fn git_reference(&self) -> Option<&GitReference> {
+        match self.location {
+            CrateLocation::Git{ref reference, ..} => reference.as_ref(),
+            _ => None,
+        }
+    }
 }
 
 impl fmt::Debug for Dependency {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         let mut ds = fmt.debug_struct("Dependency");
         ds.field("name", &self.name);
+        if let Some(ref alias) = self.alias {
+            ds.field("alias", alias);
+        }
         match self.location {
-            CrateLocation::Registry{ref version} =>
-                ds.field("version", version),
+            CrateLocation::Registry{ref registry, ref version} => {
+                if let Some(ref registry) = *registry {
+                    ds.field("registry", registry);
+                }
+                ds.field("version", version)
+            }
             CrateLocation::Filesystem{ref path} =>
                 ds.field("path", &path.display()),
-            CrateLocation::Git{ref url} => ds.field("git", url),
+            CrateLocation::Git{ref url, ref reference} => {
+                if let Some(ref r) = *reference {
+                    ds.field(r.key(), &r.value());
+                }
+                ds.field("git", url)
+            }
         };
+        ds.field("kind", &self.kind);
+        if let Some(ref target) = self.target {
+            ds.field("target", target);
+        }
+        if self.optional {
+            ds.field("optional", &self.optional);
+        }
+        if let Some(ref feature) = self.feature {
+            ds.field("feature", feature);
+        }
+        if let Some(ref version) = self.locked_version {
+            ds.field("locked_version", version);
+        }
+        if let Some(ref patch) = self.patch {
+            ds.field("patch", patch);
+        }
         ds.finish()
     }
 }
 
 impl fmt::Display for Dependency {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ref alias) = self.alias {
+            write!(fmt, "{} = {{ package = \"{}\", ", alias, self.name)?;
+            return match self.location {
+                CrateLocation::Registry{registry: Some(ref r), ref version} =>
+                    write!(fmt, "version = \"{}\", registry = \"{}\" }}", version, r),
+                CrateLocation::Registry{registry: None, ref version} =>
+                    write!(fmt, "version = \"{}\" }}", version),
+                CrateLocation::Filesystem{ref path} =>
+                    write!(fmt, "path = \"{}\" }}", path.display()),
+                CrateLocation::Git{ref url, reference: Some(ref r)} =>
+                    write!(fmt, "git = \"{}\", {} }}", url, r),
+                CrateLocation::Git{ref url, reference: None} =>
+                    write!(fmt, "git = \"{}\" }}", url),
+            };
+        }
         match self.location {
-            CrateLocation::Registry{ref version} =>
+            CrateLocation::Registry{registry: Some(ref r), ref version} =>
+                write!(fmt, "{} = {{ version = \"{}\", registry = \"{}\" }}", self.name, version, r),
+            CrateLocation::Registry{registry: None, ref version} =>
                 write!(fmt, "{} = \"{}\"", self.name, version),
             CrateLocation::Filesystem{ref path} =>
                 write!(fmt, "{} = {{ path = \"{}\" }}", self.name, path.display()),
-            CrateLocation::Git{ref url} =>
+            CrateLocation::Git{ref url, reference: Some(ref r)} =>
+                write!(fmt, "{} = {{ git = \"{}\", {} }}", self.name, url, r),
+            CrateLocation::Git{ref url, reference: None} =>
                 write!(fmt, "{} = {{ git = \"{}\" }}", self.name, url),
         }
     }
 }
 
 
+/// Kind of a dependency, corresponding to the manifest section it's listed in.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum DependencyKind {
+    /// Regular dependency, from [dependencies].
+    Normal,
+    /// Dependency used only for tests, examples, and benchmarks,
+    /// from [dev-dependencies].
+    Development,
+    /// Dependency of the build script, from [build-dependencies].
+    Build,
+}
+
+impl DependencyKind {
+    /// All the dependency kinds, in the order they're typically listed in the manifest.
+    pub fn all() -> &'static [DependencyKind] {
+        &[DependencyKind::Normal, DependencyKind::Development, DependencyKind::Build]
+    }
+
+    /// Short name of the dependency kind, as accepted by `from_str`.
+    #[inline]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DependencyKind::Normal => "normal",
+            DependencyKind::Development => "dev",
+            DependencyKind::Build => "build",
+        }
+    }
+
+    /// Name of the manifest section that lists dependencies of this kind.
+    #[inline]
+    pub fn manifest_section(self) -> &'static str {
+        match self {
+            DependencyKind::Normal => "dependencies",
+            DependencyKind::Development => "dev-dependencies",
+            DependencyKind::Build => "build-dependencies",
+        }
+    }
+}
+
+impl Default for DependencyKind {
+    fn default() -> Self { DependencyKind::Normal }
+}
+
+impl FromStr for DependencyKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "normal" => Ok(DependencyKind::Normal),
+            "dev" | "development" => Ok(DependencyKind::Development),
+            "build" => Ok(DependencyKind::Build),
+            _ => Err(format!("unknown dependency kind `{}`", s)),
+        }
+    }
+}
+
+impl fmt::Display for DependencyKind {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}", self.as_str())
+    }
+}
+
+
 /// Describes where is a particular dependent crate located.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum CrateLocation {
-    /// Crate is hosted on crates.io.
-    Registry{ version: VersionReq },
+    /// Crate is hosted on crates.io,
+    /// or an alternative registry with given name (as configured in .cargo/config.toml).
+    Registry{ registry: Option<String>, version: VersionReq },
     /// Crate is available under given filesystem path.
     Filesystem{ path: PathBuf },
-    /// Crate is kept in a Git repository under given URL.
-    Git{ url: String },
+    /// Crate is kept in a Git repository under given URL,
+    /// optionally pinned to a particular branch, tag, or revision.
+    Git{ url: String, reference: Option<GitReference> },
 }
 
 impl CrateLocation {
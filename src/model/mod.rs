@@ -2,8 +2,10 @@
 
 #![allow(dead_code)]
 
-mod github;
+mod forge;
+mod git;
 mod manifest;
 
-pub use self::github::*;
+pub use self::forge::*;
+pub use self::git::*;
 pub use self::manifest::*;
@@ -0,0 +1,130 @@
+//! Utilities for tests.
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::process;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+
+/// Placeholder that is replaced with the server's base URL in response bodies.
+pub const SERVER_URL_PLACEHOLDER: &str = "{server_url}";
+
+/// Prefix of the response bodies which make the server redirect to the URL that follows it.
+pub const REDIRECT_PREFIX: &str = "redirect:";
+
+
+/// Create a new temporary directory with given files (as paths relative to it & their content),
+/// returning the directory's canonical path.
+///
+/// Every call creates a different directory, so the tests can run in parallel.
+pub fn create_temp_dir(files: &[(&str, &str)]) -> PathBuf {
+    lazy_static! {
+        static ref COUNTER: AtomicUsize = AtomicUsize::new(0);
+    }
+    let root = env::temp_dir().join(format!("cargo-contribute-test-{}-{}",
+        process::id(), COUNTER.fetch_add(1, Ordering::SeqCst)));
+    fs::create_dir_all(&root).unwrap();
+    for &(path, content) in files {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+    root.canonicalize().unwrap()
+}
+
+
+/// Minimal HTTP server running in a background thread,
+/// responding to GET requests with canned JSON bodies.
+///
+/// It stands in for the remote APIs (crates.io, code forges, etc.) in tests.
+#[derive(Debug)]
+pub struct MockServer {
+    url: String,
+    /// Paths of the requests made so far, in order.
+    requests: Arc<Mutex<Vec<String>>>,
+}
+
+impl MockServer {
+    /// Start the server, serving given bodies under their respective paths.
+    /// Requests for any other path get a 404.
+    pub fn start<P: ToString, B: ToString>(routes: Vec<(P, B)>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        let routes: HashMap<_, _> = routes.into_iter()
+            .map(|(p, b)| (p.to_string(), b.to_string().replace(SERVER_URL_PLACEHOLDER, &url)))
+            .collect();
+        let requests = Arc::new(Mutex::new(vec![]));
+        thread::spawn({
+            let requests = requests.clone();
+            move || {
+                for stream in listener.incoming() {
+                    match stream {
+                        Ok(s) => respond(s, &routes, &requests),
+                        Err(e) => { warn!("Mock server connection error: {}", e); }
+                    }
+                }
+            }
+        });
+        MockServer{url, requests}
+    }
+
+    /// Base URL of the server, like "http://127.0.0.1:12345".
+    #[inline]
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Return the number of requests made so far for given path.
+    pub fn request_count(&self, path: &str) -> usize {
+        self.requests.lock().unwrap().iter().filter(|p| *p == path).count()
+    }
+}
+
+fn respond(mut stream: TcpStream, routes: &HashMap<String, String>, requests: &Mutex<Vec<String>>) {
+    let mut path = String::new();
+    {
+        let mut reader = BufReader::new(&stream);
+        let mut line = String::new();
+        while reader.read_line(&mut line).map(|n| n > 0).unwrap_or(false) {
+            if line.trim().is_empty() {
+                break;
+            }
+            if path.is_empty() {
+                // Request line is "GET /some/path HTTP/1.1".
+                path = line.split_whitespace().nth(1).unwrap_or("").to_owned();
+            }
+            line.clear();
+        }
+    }
+    trace!("Mock server request: {}", path);
+    requests.lock().unwrap().push(path.clone());
+
+    let (status, body) = match routes.get(&path) {
+        Some(body) => ("200 OK", body.as_str()),
+        None => ("404 Not Found", "{}"),
+    };
+    if body.starts_with(REDIRECT_PREFIX) {
+        let location = &body[REDIRECT_PREFIX.len()..];
+        let _ = write!(stream, concat!(
+            "HTTP/1.1 301 Moved Permanently\r\n",
+            "Location: {}\r\n",
+            "Content-Length: 0\r\n",
+            "Connection: close\r\n",
+            "\r\n"), location);
+        return;
+    }
+    let _ = write!(stream, concat!(
+        "HTTP/1.1 {}\r\n",
+        "Content-Type: application/json\r\n",
+        "Content-Length: {}\r\n",
+        "Connection: close\r\n",
+        "\r\n",
+        "{}"), status, body.len(), body);
+}